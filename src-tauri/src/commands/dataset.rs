@@ -1,10 +1,16 @@
 use crate::error::{AppError, AppResult};
 use crate::models::SuccessResponse;
-use crate::services::dataset::{Column, PaginatedResponse, Row, UpdatableColumnFields};
+use crate::services::dataset::{
+    CellProvenance, Column, ColumnSettings, DatasetRelation, DatasetSettings, DatasetSnapshot, DistributionReport,
+    HistoryEntry, PaginatedResponse, PiiReport, PromptTemplate, ProvenanceFilter, Row, RowQuery, RowSearchHit,
+    RowSelection, SnapshotDiff, StorageLayout, UpdatableColumnFields,
+};
+use crate::services::generation::PromptPreview;
 use crate::services::{
-    DatasetMetadata, DatasetService, ExportService, GenerationService, RowGenerationProgress, RowGenerationStatus,
+    DatasetMetadata, DatasetService, ExportService, GenerationJob, GenerationService, RowGenerationProgress,
+    RowGenerationStatus,
 };
-use crate::utils::detect_optimal_gpu_layers;
+use crate::utils::{detect_optimal_gpu_layers, Locale};
 use std::collections::HashMap;
 use tauri::{Emitter, State, Window};
 use tokio_util::sync::CancellationToken;
@@ -44,6 +50,160 @@ pub async fn update_dataset(
     Ok(SuccessResponse::new(dataset))
 }
 
+#[tauri::command]
+pub async fn update_dataset_settings(
+    id: i64,
+    settings: DatasetSettings,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<DatasetMetadata>> {
+    let dataset = dataset_service
+        .update_settings(id, &settings)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(dataset))
+}
+
+#[tauri::command]
+pub async fn set_dataset_relation(
+    parent_dataset_id: i64,
+    child_dataset_id: i64,
+    min_children: usize,
+    max_children: usize,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<DatasetRelation>> {
+    let relation = dataset_service
+        .set_relation(&DatasetRelation {
+            parent_dataset_id,
+            child_dataset_id,
+            min_children,
+            max_children,
+        })
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(relation))
+}
+
+#[tauri::command]
+pub async fn remove_dataset_relation(
+    child_dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<String>> {
+    dataset_service
+        .remove_relation(child_dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new("Relation removed".to_string()))
+}
+
+#[tauri::command]
+pub async fn list_dataset_relations(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<DatasetRelation>>> {
+    let relations = dataset_service
+        .get_relations(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(relations))
+}
+
+#[tauri::command]
+pub async fn get_distribution_report(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<DistributionReport>>> {
+    let reports = dataset_service
+        .get_distribution_report(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(reports))
+}
+
+#[tauri::command]
+pub async fn get_pii_report(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<PiiReport>> {
+    let report = dataset_service
+        .get_pii_report(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(report))
+}
+
+#[tauri::command]
+pub async fn get_cell_provenance(
+    dataset_id: i64,
+    row_id: i64,
+    column_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Option<CellProvenance>>> {
+    let provenance = dataset_service
+        .get_cell_provenance(dataset_id, row_id, column_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(provenance))
+}
+
+#[tauri::command]
+pub async fn find_rows_by_provenance(
+    dataset_id: i64,
+    filter: ProvenanceFilter,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<i64>>> {
+    let row_ids = dataset_service
+        .find_rows_by_provenance(dataset_id, &filter)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(row_ids))
+}
+
+#[tauri::command]
+pub async fn get_prompt_template(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<PromptTemplate>> {
+    let template = dataset_service
+        .get_prompt_template(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(template))
+}
+
+#[tauri::command]
+pub async fn get_default_prompt_template(locale: Option<Locale>) -> AppResult<SuccessResponse<PromptTemplate>> {
+    let strings = locale.unwrap_or_default().strings();
+
+    Ok(SuccessResponse::new(PromptTemplate {
+        system_prompt: Some(strings.system_prompt.to_string()),
+        cell_template: Some(strings.cell_template.to_string()),
+    }))
+}
+
+#[tauri::command]
+pub async fn update_prompt_template(
+    dataset_id: i64,
+    system_prompt: Option<String>,
+    cell_template: Option<String>,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<PromptTemplate>> {
+    let template = dataset_service
+        .update_prompt_template(
+            dataset_id,
+            &PromptTemplate {
+                system_prompt,
+                cell_template,
+            },
+        )
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new(template))
+}
+
+#[tauri::command]
+pub async fn duplicate_dataset(
+    id: i64,
+    name: Option<String>,
+    include_rows: bool,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<DatasetMetadata>> {
+    let dataset = dataset_service
+        .duplicate(id, name.as_deref(), include_rows)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(dataset))
+}
+
 #[tauri::command]
 pub async fn delete_dataset(id: i64, dataset_service: State<'_, DatasetService>) -> AppResult<SuccessResponse<()>> {
     dataset_service.delete(id).map_err(|e| AppError::Io(e.to_string()))?;
@@ -69,6 +229,7 @@ pub async fn create_column(
     column_type: String,
     column_type_details: Option<String>,
     rules: String,
+    settings: Option<ColumnSettings>,
     dataset_service: State<'_, DatasetService>,
 ) -> AppResult<SuccessResponse<Vec<Column>>> {
     let dataset_metadata = dataset_service
@@ -87,6 +248,7 @@ pub async fn create_column(
                 column_type_details,
                 rules,
                 position: 0,
+                settings: settings.unwrap_or_default(),
             }],
         )
         .map_err(|e| AppError::Io(e.to_string()))?;
@@ -101,6 +263,7 @@ pub async fn update_column(
     column_type_details: Option<String>,
     rules: Option<String>,
     position: Option<String>,
+    settings: Option<ColumnSettings>,
     dataset_service: State<'_, DatasetService>,
 ) -> AppResult<SuccessResponse<Column>> {
     let column = dataset_service
@@ -112,6 +275,7 @@ pub async fn update_column(
                 column_type_details,
                 rules,
                 position,
+                settings,
             },
         )
         .map_err(|e| AppError::Io(e.to_string()))?;
@@ -131,20 +295,62 @@ pub async fn fetch_rows(
     dataset_id: i64,
     page: i64,
     page_size: i64,
+    query: Option<RowQuery>,
     dataset_service: State<'_, DatasetService>,
 ) -> AppResult<SuccessResponse<PaginatedResponse>> {
     let paginated_rows = dataset_service
-        .get_rows(dataset_id, page, page_size)
+        .query_rows(dataset_id, page, page_size, &query.unwrap_or_default())
         .map_err(|e| AppError::Io(e.to_string()))?;
 
     Ok(SuccessResponse::new(paginated_rows))
 }
 
+#[tauri::command]
+pub async fn get_storage_layout(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<StorageLayout>> {
+    let dataset = dataset_service
+        .find_by_id(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    let layout = dataset_service
+        .storage_layout(&dataset.table_name)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(layout))
+}
+
+#[tauri::command]
+pub async fn convert_to_columnar(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<StorageLayout>> {
+    let layout = dataset_service
+        .convert_to_columnar(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(layout))
+}
+
+#[tauri::command]
+pub async fn search_rows(
+    dataset_id: i64,
+    query: String,
+    limit: Option<i64>,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<RowSearchHit>>> {
+    let hits = dataset_service
+        .search_rows(dataset_id, &query, limit.unwrap_or(50))
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(hits))
+}
+
 #[tauri::command]
 pub async fn update_row(
     dataset_id: i64,
     row_id: i64,
-    data: HashMap<i64, String>,
+    data: HashMap<i64, Option<String>>,
     dataset_service: State<'_, DatasetService>,
 ) -> AppResult<SuccessResponse<Row>> {
     let row = dataset_service
@@ -167,6 +373,186 @@ pub async fn delete_row(
     Ok(SuccessResponse::new(()))
 }
 
+#[tauri::command]
+pub async fn delete_rows(
+    dataset_id: i64,
+    selection: RowSelection,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<usize>> {
+    let deleted = dataset_service
+        .delete_rows(dataset_id, &selection)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(deleted))
+}
+
+#[tauri::command]
+pub async fn update_rows(
+    dataset_id: i64,
+    selection: RowSelection,
+    column_id: i64,
+    value: Option<String>,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<usize>> {
+    let updated = dataset_service
+        .update_rows(dataset_id, &selection, column_id, value.as_deref())
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(updated))
+}
+
+#[tauri::command]
+pub async fn replace_in_column(
+    dataset_id: i64,
+    selection: RowSelection,
+    column_id: i64,
+    pattern: String,
+    replacement: String,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<usize>> {
+    let replaced = dataset_service
+        .replace_in_column(dataset_id, &selection, column_id, &pattern, &replacement)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(replaced))
+}
+
+#[tauri::command]
+pub async fn get_history(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<HistoryEntry>>> {
+    let history = dataset_service
+        .get_history(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(history))
+}
+
+#[tauri::command]
+pub async fn undo(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Option<HistoryEntry>>> {
+    let entry = dataset_service
+        .undo(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(entry))
+}
+
+#[tauri::command]
+pub async fn redo(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Option<HistoryEntry>>> {
+    let entry = dataset_service
+        .redo(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(entry))
+}
+
+#[tauri::command]
+pub async fn create_snapshot(
+    dataset_id: i64,
+    name: String,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<DatasetSnapshot>> {
+    let snapshot = dataset_service
+        .create_snapshot(dataset_id, &name)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(snapshot))
+}
+
+#[tauri::command]
+pub async fn list_snapshots(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<DatasetSnapshot>>> {
+    let snapshots = dataset_service
+        .list_snapshots(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(snapshots))
+}
+
+#[tauri::command]
+pub async fn restore_snapshot(
+    snapshot_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<()>> {
+    dataset_service
+        .restore_snapshot(snapshot_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(()))
+}
+
+#[tauri::command]
+pub async fn delete_snapshot(
+    snapshot_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<()>> {
+    dataset_service
+        .delete_snapshot(snapshot_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(()))
+}
+
+#[tauri::command]
+pub async fn diff_snapshots(
+    snapshot_id: i64,
+    other_snapshot_id: Option<i64>,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<SnapshotDiff>> {
+    let diff = dataset_service
+        .diff_snapshot(snapshot_id, other_snapshot_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(diff))
+}
+
+#[tauri::command]
+pub async fn pin_row(
+    dataset_id: i64,
+    row_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<()>> {
+    dataset_service
+        .pin_row(dataset_id, row_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(()))
+}
+
+#[tauri::command]
+pub async fn unpin_row(
+    dataset_id: i64,
+    row_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<()>> {
+    dataset_service
+        .unpin_row(dataset_id, row_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(()))
+}
+
+#[tauri::command]
+pub async fn list_pinned_rows(
+    dataset_id: i64,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<i64>>> {
+    let row_ids = dataset_service
+        .get_pinned_row_ids(dataset_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(row_ids))
+}
+
 #[tauri::command]
 pub async fn generate_rows(
     dataset_id: i64,
@@ -217,15 +603,63 @@ pub async fn generate_rows(
         let window_inner = window_clone.clone();
         let dataset_service_inner = dataset_service_clone.clone();
 
+        let has_children = dataset_service_clone
+            .get_child_relations(dataset_id)
+            .is_ok_and(|relations| !relations.is_empty());
+
+        let job = GenerationJob {
+            generation_id: generation_id.clone(),
+            model_id,
+        };
+
         let result = tokio::task::spawn_blocking(move || {
+            if has_children {
+                // child rows are persisted by the generation service, progress is reported per dataset
+                return generation_service_inner.generate_nested(
+                    dataset_id,
+                    &job,
+                    total_rows_to_generate,
+                    gpu_layers,
+                    cancel_token_inner,
+                    move |row_dataset_id, row, warnings, total_rows_generated, total_rows_to_generate| {
+                        for warning in warnings {
+                            let _ = window_inner.emit(
+                                "generation-status",
+                                RowGenerationStatus {
+                                    generation_id: generation_id_inner.clone(),
+                                    status: "warning".to_string(),
+                                    message: Some(warning.clone()),
+                                },
+                            );
+                        }
+
+                        let _ = window_inner.emit(
+                            "generation-progress",
+                            RowGenerationProgress {
+                                dataset_id: row_dataset_id,
+                                generation_id: generation_id_inner.clone(),
+                                last_row_generated: row,
+                                total_rows_generated,
+                                total_rows_to_generate,
+                                status: "generating".to_string(),
+                            },
+                        );
+                    },
+                );
+            }
+
             generation_service_inner.generate(
                 dataset_id,
-                model_id,
+                &job,
                 total_rows_to_generate,
                 gpu_layers,
                 cancel_token_inner,
-                move |last_row_generated, total_rows_generated, total_rows_to_generate| {
-                    let row = match dataset_service_inner.add_row(dataset_id, &last_row_generated) {
+                move |generated, total_rows_generated, total_rows_to_generate| {
+                    let row = match dataset_service_inner.add_row_with_provenance(
+                        dataset_id,
+                        &generated.data,
+                        &generated.provenance,
+                    ) {
                         Ok(row) => row,
                         Err(e) => {
                             let _ = window_inner.emit(
@@ -240,6 +674,22 @@ pub async fn generate_rows(
                         }
                     };
 
+                    // The row is saved and generation goes on, so these are not terminal statuses
+                    let mut warnings = generated.warnings;
+                    if let Err(e) = dataset_service_inner.flag_pii(dataset_id, &row) {
+                        warnings.push(e.to_string());
+                    }
+                    for warning in warnings {
+                        let _ = window_inner.emit(
+                            "generation-status",
+                            RowGenerationStatus {
+                                generation_id: generation_id_inner.clone(),
+                                status: "warning".to_string(),
+                                message: Some(warning),
+                            },
+                        );
+                    }
+
                     let _ = window_inner.emit(
                         "generation-progress",
                         RowGenerationProgress {
@@ -312,6 +762,18 @@ pub fn cancel_generation(
     Ok(SuccessResponse::new("Generation cancelled".to_string()))
 }
 
+#[tauri::command]
+pub fn preview_prompts(
+    dataset_id: i64,
+    values: Option<HashMap<i64, String>>,
+    dataset_service: State<'_, DatasetService>,
+) -> AppResult<SuccessResponse<Vec<PromptPreview>>> {
+    let previews = GenerationService::preview_prompts(&dataset_service, dataset_id, &values.unwrap_or_default())
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(previews))
+}
+
 #[tauri::command]
 #[allow(dead_code)]
 pub fn get_optimal_gpu_layers() -> AppResult<SuccessResponse<u32>> {
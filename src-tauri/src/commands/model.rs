@@ -1,7 +1,7 @@
 use crate::error::{AppError, AppResult};
 use crate::models::SuccessResponse;
-use crate::services::model::{DownloadProgress, ModelInfo};
-use crate::services::ModelService;
+use crate::services::model::{DownloadProgress, ModelBenchmark, ModelInfo};
+use crate::services::{GenerationService, ModelService};
 use crate::utils::detect_optimal_gpu_layers;
 
 use tauri::{AppHandle, Emitter, Manager, State, Window};
@@ -151,3 +151,32 @@ pub fn get_default_gpu_layers() -> AppResult<SuccessResponse<u32>> {
     let default = detect_optimal_gpu_layers();
     Ok(SuccessResponse::new(default))
 }
+
+#[tauri::command]
+pub async fn benchmark_model(
+    model_id: i64,
+    gpu_layers: Option<u32>,
+    generation_service: State<'_, GenerationService>,
+) -> AppResult<SuccessResponse<ModelBenchmark>> {
+    let gpu_layers = gpu_layers.unwrap_or_else(detect_optimal_gpu_layers);
+    let generation_service = generation_service.inner().clone();
+
+    let benchmark = tokio::task::spawn_blocking(move || generation_service.benchmark_model(model_id, gpu_layers))
+        .await
+        .map_err(|e| AppError::Internal(format!("Task panicked: {}", e)))?
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(benchmark))
+}
+
+#[tauri::command]
+pub fn list_model_benchmarks(
+    model_id: i64,
+    model_service: State<'_, ModelService>,
+) -> AppResult<SuccessResponse<Vec<ModelBenchmark>>> {
+    let benchmarks = model_service
+        .list_benchmarks(model_id)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(benchmarks))
+}
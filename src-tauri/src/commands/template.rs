@@ -0,0 +1,72 @@
+use crate::error::{AppError, AppResult};
+use crate::models::SuccessResponse;
+use crate::services::template::DatasetTemplate;
+use crate::services::{DatasetMetadata, TemplateService};
+use tauri::State;
+
+#[tauri::command]
+pub async fn list_templates(
+    template_service: State<'_, TemplateService>,
+) -> AppResult<SuccessResponse<Vec<DatasetTemplate>>> {
+    let templates = template_service.list().map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(templates))
+}
+
+#[tauri::command]
+pub async fn save_dataset_as_template(
+    dataset_id: i64,
+    name: String,
+    description: String,
+    template_service: State<'_, TemplateService>,
+) -> AppResult<SuccessResponse<DatasetTemplate>> {
+    let template = template_service
+        .save_from_dataset(dataset_id, &name, &description)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(template))
+}
+
+#[tauri::command]
+pub async fn delete_template(id: i64, template_service: State<'_, TemplateService>) -> AppResult<SuccessResponse<()>> {
+    template_service.delete(id).map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(()))
+}
+
+#[tauri::command]
+pub async fn create_dataset_from_template(
+    template: DatasetTemplate,
+    name: Option<String>,
+    template_service: State<'_, TemplateService>,
+) -> AppResult<SuccessResponse<DatasetMetadata>> {
+    let dataset = template_service
+        .instantiate(&template, name.as_deref())
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(dataset))
+}
+
+#[tauri::command]
+pub async fn export_template(
+    template: DatasetTemplate,
+    file_path: String,
+    template_service: State<'_, TemplateService>,
+) -> AppResult<SuccessResponse<String>> {
+    template_service
+        .export(&template, &file_path)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    Ok(SuccessResponse::new("Template exported".to_string()))
+}
+
+#[tauri::command]
+pub async fn import_template(
+    file_path: String,
+    template_service: State<'_, TemplateService>,
+) -> AppResult<SuccessResponse<DatasetTemplate>> {
+    let template = template_service
+        .import(&file_path)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    Ok(SuccessResponse::new(template))
+}
@@ -9,6 +9,7 @@ use services::database::DatabaseService;
 use services::dataset::DatasetService;
 use services::export::ExportService;
 use services::model::ModelService;
+use services::template::TemplateService;
 
 use tauri::Manager;
 
@@ -29,23 +30,62 @@ pub fn run() {
             commands::model::list_models,
             commands::model::delete_model,
             commands::model::get_default_gpu_layers,
+            commands::model::benchmark_model,
+            commands::model::list_model_benchmarks,
             // Dataset commands
             commands::dataset::create_dataset,
             commands::dataset::list_datasets,
             commands::dataset::update_dataset,
+            commands::dataset::update_dataset_settings,
+            commands::dataset::set_dataset_relation,
+            commands::dataset::remove_dataset_relation,
+            commands::dataset::list_dataset_relations,
+            commands::dataset::get_distribution_report,
+            commands::dataset::get_pii_report,
+            commands::dataset::get_cell_provenance,
+            commands::dataset::find_rows_by_provenance,
+            commands::dataset::get_prompt_template,
+            commands::dataset::get_default_prompt_template,
+            commands::dataset::update_prompt_template,
+            commands::dataset::duplicate_dataset,
             commands::dataset::delete_dataset,
             commands::dataset::get_columns,
             commands::dataset::create_column,
             commands::dataset::update_column,
             commands::dataset::delete_column,
             commands::dataset::fetch_rows,
+            commands::dataset::search_rows,
+            commands::dataset::get_storage_layout,
+            commands::dataset::convert_to_columnar,
             commands::dataset::update_row,
             commands::dataset::delete_row,
+            commands::dataset::delete_rows,
+            commands::dataset::update_rows,
+            commands::dataset::replace_in_column,
+            commands::dataset::get_history,
+            commands::dataset::undo,
+            commands::dataset::redo,
+            commands::dataset::create_snapshot,
+            commands::dataset::list_snapshots,
+            commands::dataset::restore_snapshot,
+            commands::dataset::delete_snapshot,
+            commands::dataset::diff_snapshots,
+            commands::dataset::pin_row,
+            commands::dataset::unpin_row,
+            commands::dataset::list_pinned_rows,
             commands::dataset::generate_rows,
             commands::dataset::cancel_generation,
+            commands::dataset::preview_prompts,
             commands::dataset::get_optimal_gpu_layers,
             // export commands
             commands::dataset::export_to_csv,
+            // template commands
+            commands::template::list_templates,
+            commands::template::save_dataset_as_template,
+            commands::template::delete_template,
+            commands::template::create_dataset_from_template,
+            commands::template::export_template,
+            commands::template::import_template,
         ])
         .setup(|app| {
             let db = DatabaseService::new(Some(app.handle()))
@@ -53,6 +93,7 @@ pub fn run() {
 
             let dataset_service = DatasetService::new(db.clone())?;
             let export_service = ExportService::new(db.clone(), dataset_service.clone());
+            let template_service = TemplateService::new(db.clone(), dataset_service.clone());
             let model_service = ModelService::new(Some(app.handle()), db.clone())?;
             let generation_service =
                 GenerationService::new(db.clone(), dataset_service.clone(), model_service.clone())?;
@@ -60,6 +101,7 @@ pub fn run() {
             app.manage(db);
             app.manage(dataset_service);
             app.manage(export_service);
+            app.manage(template_service);
             app.manage(model_service);
             app.manage(generation_service);
 
@@ -10,6 +10,8 @@ pub enum DatabaseError {
     SqliteError(String),
     FsError(String),
     InvalidQuery(String),
+    UnsupportedSchema(String),
+    MigrationFailed(String),
 }
 
 impl fmt::Display for DatabaseError {
@@ -18,6 +20,8 @@ impl fmt::Display for DatabaseError {
             DatabaseError::SqliteError(msg) => write!(f, "SQLite error: {}", msg),
             DatabaseError::FsError(msg) => write!(f, "File system error: {}", msg),
             DatabaseError::InvalidQuery(msg) => write!(f, "Invalid query: {}", msg),
+            DatabaseError::UnsupportedSchema(msg) => write!(f, "Unsupported schema: {}", msg),
+            DatabaseError::MigrationFailed(msg) => write!(f, "Migration failed: {}", msg),
         }
     }
 }
@@ -42,6 +46,286 @@ impl From<std::io::Error> for DatabaseError {
     }
 }
 
+#[derive(Clone)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: fn(&Connection) -> SqliteResult<()>,
+}
+
+/// Schema changes applied on top of the tables the services create with `CREATE TABLE IF NOT EXISTS`.
+/// Only the `datasets_metadata`, `columns` and `models` statements are the version 0 schema and stay frozen,
+/// since they never touch an existing table; every other table and column goes here, appended with the next
+/// version number. `DatasetService::new` applies them right after creating its tables, before the other
+/// services are built
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "add_settings_columns",
+        up: add_settings_columns,
+    },
+    Migration {
+        version: 2,
+        name: "add_dataset_history",
+        up: add_dataset_history,
+    },
+    Migration {
+        version: 3,
+        name: "add_dataset_snapshots",
+        up: add_dataset_snapshots,
+    },
+    Migration {
+        version: 4,
+        name: "add_dataset_templates",
+        up: add_dataset_templates,
+    },
+    Migration {
+        version: 5,
+        name: "add_pinned_rows",
+        up: add_pinned_rows,
+    },
+    Migration {
+        version: 6,
+        name: "add_column_values",
+        up: add_column_values,
+    },
+    Migration {
+        version: 7,
+        name: "add_prompt_templates",
+        up: add_prompt_templates,
+    },
+    Migration {
+        version: 8,
+        name: "add_dataset_relations",
+        up: add_dataset_relations,
+    },
+    Migration {
+        version: 9,
+        name: "add_pii_flags",
+        up: add_pii_flags,
+    },
+    Migration {
+        version: 10,
+        name: "add_cell_provenance",
+        up: add_cell_provenance,
+    },
+    Migration {
+        version: 11,
+        name: "add_model_benchmarks",
+        up: add_model_benchmarks,
+    },
+];
+
+fn add_settings_columns(conn: &Connection) -> SqliteResult<()> {
+    add_column(conn, "datasets_metadata", "settings", "TEXT DEFAULT '{}'")?;
+    add_column(conn, "columns", "settings", "TEXT DEFAULT '{}'")
+}
+
+fn add_dataset_history(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS dataset_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            dataset_id INTEGER NOT NULL,
+            operation TEXT NOT NULL CHECK(json_valid(operation)),
+            undone INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_dataset_history_dataset ON dataset_history(dataset_id, id);
+        ",
+    )
+}
+
+fn add_dataset_snapshots(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS dataset_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            dataset_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            columns TEXT NOT NULL CHECK(json_valid(columns)),
+            row_count INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_dataset_snapshots_dataset ON dataset_snapshots(dataset_id);
+        CREATE TABLE IF NOT EXISTS dataset_snapshot_rows (
+            snapshot_id INTEGER NOT NULL,
+            row_id INTEGER NOT NULL,
+            data TEXT NOT NULL,
+            created_at TIMESTAMP,
+            updated_at TIMESTAMP,
+            PRIMARY KEY (snapshot_id, row_id),
+            FOREIGN KEY (snapshot_id) REFERENCES dataset_snapshots(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+fn add_dataset_templates(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS dataset_templates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            columns TEXT NOT NULL CHECK(json_valid(columns)),
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        ",
+    )
+}
+
+fn add_pinned_rows(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS pinned_rows (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            dataset_id INTEGER NOT NULL,
+            row_id INTEGER NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (dataset_id, row_id),
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+fn add_column_values(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS column_values (
+            dataset_id INTEGER NOT NULL,
+            row_id INTEGER NOT NULL,
+            column_id INTEGER NOT NULL,
+            value TEXT NOT NULL,
+            normalized_value TEXT NOT NULL,
+            PRIMARY KEY (dataset_id, row_id, column_id),
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_column_values_value ON column_values(dataset_id, column_id, value);
+        CREATE INDEX IF NOT EXISTS idx_column_values_normalized ON column_values(dataset_id, column_id, normalized_value);
+        ",
+    )
+}
+
+fn add_prompt_templates(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS prompt_templates (
+            dataset_id INTEGER PRIMARY KEY,
+            system_prompt TEXT,
+            cell_template TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+fn add_dataset_relations(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS dataset_relations (
+            child_dataset_id INTEGER PRIMARY KEY,
+            parent_dataset_id INTEGER NOT NULL,
+            min_children INTEGER NOT NULL,
+            max_children INTEGER NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (child_dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE,
+            FOREIGN KEY (parent_dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+fn add_pii_flags(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS pii_flags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            dataset_id INTEGER NOT NULL,
+            row_id INTEGER NOT NULL,
+            column_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            value TEXT NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_pii_flags_row ON pii_flags(dataset_id, row_id);
+        ",
+    )
+}
+
+fn add_cell_provenance(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS cell_provenance (
+            dataset_id INTEGER NOT NULL,
+            row_id INTEGER NOT NULL,
+            column_id INTEGER NOT NULL,
+            model_id INTEGER,
+            generation_id TEXT,
+            prompt_hash TEXT NOT NULL,
+            rules_hash TEXT NOT NULL,
+            attempts INTEGER NOT NULL,
+            seed INTEGER,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (dataset_id, row_id, column_id),
+            FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+/// `models` belongs to `ModelService`, which is built after the migrations ran; SQLite only resolves the
+/// foreign key once rows are written
+fn add_model_benchmarks(conn: &Connection) -> SqliteResult<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS model_benchmarks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            model_id INTEGER NOT NULL,
+            gpu_layers INTEGER NOT NULL,
+            load_time_ms INTEGER NOT NULL,
+            tokens_generated INTEGER NOT NULL,
+            tokens_per_second REAL NOT NULL,
+            memory_usage_bytes INTEGER NOT NULL,
+            cells_total INTEGER NOT NULL,
+            cells_parsed INTEGER NOT NULL,
+            parse_success_rate REAL NOT NULL,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (model_id) REFERENCES models(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_model_benchmarks_model_id ON model_benchmarks(model_id);
+        ",
+    )
+}
+
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> SqliteResult<()> {
+    let count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?", table),
+        [column],
+        |row| row.get(0),
+    )?;
+
+    if count == 0 {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
 #[derive(Clone)]
 pub struct DatabaseService {
     pub conn: Arc<Mutex<Connection>>,
@@ -77,9 +361,88 @@ impl DatabaseService {
             conn: Arc::new(Mutex::new(conn)),
         };
 
+        db.check_schema_version(latest_schema_version())
+            .map_err(|e| AppError::Database(e.to_string()))?;
+
         Ok(db)
     }
 
+    pub fn schema_version(&self) -> Result<i64, DatabaseError> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?;
+
+        Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    pub fn check_schema_version(&self, latest: i64) -> Result<(), DatabaseError> {
+        let version = self.schema_version()?;
+
+        if version > latest {
+            return Err(DatabaseError::UnsupportedSchema(format!(
+                "database is at schema version {} but this app only supports up to {}, please update the app",
+                version, latest
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Applies every pending migration from `MIGRATIONS`, returns how many ran
+    pub fn migrate(&self) -> Result<usize, DatabaseError> {
+        self.apply_migrations(MIGRATIONS)
+    }
+
+    pub fn apply_migrations(&self, migrations: &[Migration]) -> Result<usize, DatabaseError> {
+        if migrations.windows(2).any(|pair| pair[0].version >= pair[1].version) {
+            return Err(DatabaseError::MigrationFailed(
+                "migrations must be sorted by strictly increasing version".to_string(),
+            ));
+        }
+
+        let latest = migrations.last().map(|migration| migration.version).unwrap_or(0);
+        self.check_schema_version(latest)?;
+
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?;
+
+        conn.execute(
+            "
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )
+        ",
+            [],
+        )?;
+
+        let current: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let mut applied = 0;
+
+        for migration in migrations.iter().filter(|migration| migration.version > current) {
+            let tx = conn.transaction()?;
+
+            (migration.up)(&tx).map_err(|e| {
+                DatabaseError::MigrationFailed(format!("{} ({}): {}", migration.version, migration.name, e))
+            })?;
+
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name) VALUES (?, ?)",
+                rusqlite::params![migration.version, migration.name],
+            )?;
+            tx.pragma_update(None, "user_version", migration.version)?;
+            tx.commit()?;
+
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+
     pub fn create_table(&self, table: &str, columns: &[&str], constraints: &[&str]) -> SqliteResult<()> {
         self.validate_table_name(table)?;
 
@@ -174,11 +537,12 @@ impl DatabaseService {
         Ok(results)
     }
 
+    /// Views count as tables, they are read the same way
     pub fn table_exists(&self, table: &str) -> SqliteResult<bool> {
         let conn = self.conn.lock().map_err(|_| SqliteError::InvalidQuery)?;
 
         let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?",
+            "SELECT COUNT(*) FROM sqlite_master WHERE type IN ('table', 'view') AND name=?",
             [table],
             |row| row.get(0),
         )?;
@@ -186,6 +550,20 @@ impl DatabaseService {
         Ok(count > 0)
     }
 
+    pub fn column_exists(&self, table: &str, column: &str) -> SqliteResult<bool> {
+        self.validate_table_name(table)?;
+
+        let conn = self.conn.lock().map_err(|_| SqliteError::InvalidQuery)?;
+
+        let count: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?", table),
+            [column],
+            |row| row.get(0),
+        )?;
+
+        Ok(count > 0)
+    }
+
     pub fn validate_table_name(&self, table: &str) -> SqliteResult<()> {
         if table.is_empty() || table.len() > 64 {
             return Err(SqliteError::InvalidParameterName(
@@ -462,6 +840,25 @@ mod tests {
             assert!(table_exists, "test table was not created");
         }
 
+        #[test]
+        fn test_add_column() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let conn = db.conn.lock().unwrap();
+            conn.execute("CREATE TABLE IF NOT EXISTS test_table (name TEXT NOT NULL)", [])
+                .expect("Failed to create test table");
+            conn.execute("INSERT INTO test_table (name) VALUES (?)", ["test"])
+                .expect("Failed to insert test");
+
+            add_column(&conn, "test_table", "settings", "TEXT DEFAULT '{}'").expect("Failed to add column");
+            add_column(&conn, "test_table", "settings", "TEXT DEFAULT '{}'")
+                .expect("Adding an existing column should be a no-op");
+
+            let settings: String = conn
+                .query_row("SELECT settings FROM test_table", [], |row| row.get(0))
+                .expect("Failed to query settings");
+            assert_eq!(settings, "{}", "Existing rows should get the column default");
+        }
+
         #[test]
         fn test_validate_table_name() {
             let db = DatabaseService::new(None).expect("Failed to create database");
@@ -483,4 +880,181 @@ mod tests {
             assert!(test_long_name.is_err(), "Failed to validate table name");
         }
     }
+
+    mod migrations {
+        use super::*;
+
+        fn create_notes(conn: &Connection) -> SqliteResult<()> {
+            conn.execute("CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT NOT NULL)", [])?;
+            Ok(())
+        }
+
+        fn add_notes_title(conn: &Connection) -> SqliteResult<()> {
+            conn.execute("ALTER TABLE notes ADD COLUMN title TEXT DEFAULT ''", [])?;
+            Ok(())
+        }
+
+        fn broken(conn: &Connection) -> SqliteResult<()> {
+            conn.execute("ALTER TABLE notes ADD COLUMN pinned INTEGER DEFAULT 0", [])?;
+            conn.execute("ALTER TABLE missing_table ADD COLUMN value TEXT", [])?;
+            Ok(())
+        }
+
+        const TEST_MIGRATIONS: &[Migration] = &[
+            Migration {
+                version: 1,
+                name: "create_notes",
+                up: create_notes,
+            },
+            Migration {
+                version: 2,
+                name: "add_notes_title",
+                up: add_notes_title,
+            },
+        ];
+
+        fn applied_versions(db: &DatabaseService) -> Vec<i64> {
+            db.query("SELECT version FROM schema_migrations ORDER BY version", [], |row| {
+                Ok(row.get::<_, i64>(0)?)
+            })
+            .expect("Failed to query schema_migrations")
+        }
+
+        #[test]
+        fn test_migrations_are_ordered() {
+            assert!(MIGRATIONS.windows(2).all(|pair| pair[0].version < pair[1].version));
+            assert!(MIGRATIONS.iter().all(|migration| migration.version > 0));
+            assert_eq!(latest_schema_version(), MIGRATIONS.last().unwrap().version);
+        }
+
+        #[test]
+        fn test_apply_migrations_in_order_once() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+
+            assert_eq!(db.apply_migrations(TEST_MIGRATIONS).unwrap(), 2);
+            assert_eq!(db.schema_version().unwrap(), 2);
+            assert!(db.column_exists("notes", "title").unwrap());
+            assert_eq!(applied_versions(&db), vec![1, 2]);
+
+            assert_eq!(
+                db.apply_migrations(TEST_MIGRATIONS).unwrap(),
+                0,
+                "Migrations should only run once"
+            );
+            assert_eq!(applied_versions(&db), vec![1, 2]);
+        }
+
+        #[test]
+        fn test_apply_only_pending_migrations() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+
+            assert_eq!(db.apply_migrations(&TEST_MIGRATIONS[..1]).unwrap(), 1);
+            assert!(!db.column_exists("notes", "title").unwrap());
+
+            assert_eq!(db.apply_migrations(TEST_MIGRATIONS).unwrap(), 1);
+            assert!(db.column_exists("notes", "title").unwrap());
+            assert_eq!(db.schema_version().unwrap(), 2);
+        }
+
+        #[test]
+        fn test_failed_migration_rolls_back() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            db.apply_migrations(&TEST_MIGRATIONS[..1]).unwrap();
+
+            let migrations = [
+                Migration {
+                    version: 1,
+                    name: "create_notes",
+                    up: create_notes,
+                },
+                Migration {
+                    version: 2,
+                    name: "broken",
+                    up: broken,
+                },
+            ];
+
+            let result = db.apply_migrations(&migrations);
+            assert!(matches!(result, Err(DatabaseError::MigrationFailed(_))));
+            assert_eq!(db.schema_version().unwrap(), 1);
+            assert!(
+                !db.column_exists("notes", "pinned").unwrap(),
+                "Partial migration was not rolled back"
+            );
+            assert_eq!(applied_versions(&db), vec![1]);
+        }
+
+        #[test]
+        fn test_refuses_newer_schema() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            db.execute("PRAGMA user_version = 99", []).unwrap();
+
+            assert!(matches!(
+                db.check_schema_version(latest_schema_version()),
+                Err(DatabaseError::UnsupportedSchema(_))
+            ));
+            assert!(matches!(db.migrate(), Err(DatabaseError::UnsupportedSchema(_))));
+            assert_eq!(db.schema_version().unwrap(), 99);
+        }
+
+        #[test]
+        fn test_rejects_unordered_migrations() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let migrations = [TEST_MIGRATIONS[1].clone(), TEST_MIGRATIONS[0].clone()];
+
+            assert!(matches!(
+                db.apply_migrations(&migrations),
+                Err(DatabaseError::MigrationFailed(_))
+            ));
+            assert_eq!(db.schema_version().unwrap(), 0);
+        }
+
+        #[test]
+        fn test_migrate_adds_settings_to_existing_tables() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+
+            {
+                let conn = db.conn.lock().unwrap();
+                conn.execute_batch(
+                    "
+                    CREATE TABLE datasets_metadata (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL);
+                    CREATE TABLE columns (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL);
+                    INSERT INTO datasets_metadata (name) VALUES ('legacy');
+                ",
+                )
+                .expect("Failed to create legacy tables");
+            }
+
+            db.migrate().expect("Failed to migrate");
+
+            assert!(db.column_exists("datasets_metadata", "settings").unwrap());
+            assert!(db.column_exists("columns", "settings").unwrap());
+            assert!(db.table_exists("dataset_history").unwrap());
+            assert!(db.table_exists("dataset_snapshot_rows").unwrap());
+            assert!(db.table_exists("dataset_templates").unwrap());
+            assert_eq!(db.schema_version().unwrap(), latest_schema_version());
+
+            for table in [
+                "pinned_rows",
+                "column_values",
+                "prompt_templates",
+                "dataset_relations",
+                "pii_flags",
+                "cell_provenance",
+                "model_benchmarks",
+            ] {
+                assert!(
+                    db.table_exists(table).unwrap(),
+                    "{} should come from a migration",
+                    table
+                );
+            }
+
+            let conn = db.conn.lock().unwrap();
+            let settings: String = conn
+                .query_row("SELECT settings FROM datasets_metadata", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(settings, "{}");
+        }
+    }
 }
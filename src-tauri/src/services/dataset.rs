@@ -1,8 +1,10 @@
+use rand::distributions::{Distribution, WeightedIndex};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use crate::services::{DatabaseError, DatabaseService};
+use crate::utils::{scan_pii, validate_cell_template, Locale, PiiKind};
 use rusqlite::Result as SqliteResult;
 
 #[derive(Debug)]
@@ -54,6 +56,124 @@ pub struct DatasetMetadata {
     pub row_count: i64,
     pub created_at: String, // sqlite doesn't support i64 for timestamp :(
     pub updated_at: String,
+    #[serde(default)]
+    pub settings: DatasetSettings,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DatasetSettings {
+    pub row_strategy: RowStrategy,
+    /// Columns per JSON completion when `row_strategy` is `jsonObject`, all of them when unset
+    pub row_group_size: Option<usize>,
+    /// Adds the dataset description and the row's generated values to every prompt
+    pub include_context: bool,
+    /// Language of the prompt instructions and of the number notation expected back
+    pub locale: Locale,
+    /// What happens to generated cells that look like real personal data
+    pub pii_policy: PiiPolicy,
+    /// Stores how every generated cell was produced, see `CellProvenance`
+    pub record_provenance: bool,
+}
+
+impl DatasetSettings {
+    /// Columns generated together, a column whose condition tests another column of its group waits for the
+    /// next completion; per-cell rows go one column at a time
+    pub fn group_size(&self, column_count: usize) -> usize {
+        match self.row_strategy {
+            RowStrategy::PerCell => 1,
+            RowStrategy::JsonObject => self.row_group_size.unwrap_or(column_count).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PiiPolicy {
+    #[default]
+    Off,
+    /// Keep the value and record a flag
+    Flag,
+    /// Retry the cell, values still matching after the last attempt are flagged
+    Regenerate,
+}
+
+/// Generated cell that matched a PII pattern
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PiiFlag {
+    pub id: i64,
+    pub row_id: i64,
+    pub column_id: i64,
+    pub kind: PiiKind,
+    pub value: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PiiReport {
+    pub dataset_id: i64,
+    pub total_flags: usize,
+    pub flagged_rows: usize,
+    pub by_kind: HashMap<PiiKind, usize>,
+    pub flags: Vec<PiiFlag>,
+}
+
+/// Full-text match, `snippet` wraps the matched terms in `<mark>` tags
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowSearchHit {
+    pub row_id: i64,
+    /// Negated bm25, higher is more relevant
+    pub score: f64,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RowStrategy {
+    #[default]
+    PerCell,
+    JsonObject,
+}
+
+/// One-to-many link: every generated parent row gets `min_children..=max_children` child rows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetRelation {
+    pub parent_dataset_id: i64,
+    pub child_dataset_id: i64,
+    pub min_children: usize,
+    pub max_children: usize,
+}
+
+/// Dataset-specific prompt wording, `None` falls back to the built-in templates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PromptTemplate {
+    pub system_prompt: Option<String>,
+    pub cell_template: Option<String>,
+}
+
+const DATASET_METADATA_FIELDS: &str = "id, table_name, name, description, created_at, updated_at, settings";
+
+fn dataset_metadata_from_row(row: &rusqlite::Row) -> Result<DatasetMetadata, DatabaseError> {
+    let settings = match row.get::<_, Option<String>>(6)? {
+        Some(settings) if !settings.trim().is_empty() => serde_json::from_str(&settings)?,
+        _ => DatasetSettings::default(),
+    };
+
+    Ok(DatasetMetadata {
+        id: row.get::<_, i64>(0)?,
+        table_name: row.get::<_, String>(1)?,
+        name: row.get::<_, String>(2)?,
+        description: row.get::<_, String>(3)?,
+        created_at: row.get::<_, String>(4)?,
+        updated_at: row.get::<_, String>(5)?,
+        row_count: 0,
+        settings,
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +187,341 @@ pub struct Column {
     pub column_type_details: Option<String>,
     pub rules: String,
     pub position: i64,
+    #[serde(default)]
+    pub settings: ColumnSettings,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ColumnSettings {
+    pub few_shot: Option<FewShotSettings>,
+    pub unique: Option<UniqueMode>,
+    pub avoid_recent: Option<usize>,
+    pub stop: Option<StopSettings>,
+    pub reference: Option<ReferenceSettings>,
+    pub distribution: Option<DistributionSettings>,
+    /// Cells may hold no value at all instead of an empty string
+    pub nullable: bool,
+    /// Chance of leaving the cell empty (null when `nullable`), rolled before inference
+    pub null_probability: Option<f64>,
+    /// Evaluated in order against the values generated so far, the first match replaces the column rules
+    pub conditions: Vec<ConditionalRule>,
+}
+
+impl ColumnSettings {
+    /// Columns whose values are mirrored into `column_values` for duplicate lookups
+    pub fn is_indexed(&self) -> bool {
+        self.unique.is_some() || self.avoid_recent.is_some_and(|count| count > 0)
+    }
+
+    pub fn validate(&self) -> Result<(), DatasetError> {
+        if let Some(distribution) = &self.distribution {
+            distribution.validate()?;
+        }
+
+        let probabilities = self
+            .conditions
+            .iter()
+            .map(|condition| condition.null_probability)
+            .chain([self.null_probability]);
+        for probability in probabilities.flatten() {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Null probability must be between 0 and 1, got {}",
+                    probability
+                )));
+            }
+        }
+
+        if self
+            .conditions
+            .iter()
+            .any(|condition| condition.column.trim().is_empty())
+        {
+            return Err(DatasetError::InvalidInput(
+                "Conditional rules need a column to compare".to_string(),
+            ));
+        }
+
+        let Some(stop) = &self.stop else {
+            return Ok(());
+        };
+
+        if let (Some(min), Some(max)) = (stop.min_tokens, stop.max_tokens) {
+            if min > max {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Min tokens ({}) cannot exceed max tokens ({})",
+                    min, max
+                )));
+            }
+        }
+
+        if let (Some(min), Some(max)) = (stop.min_chars, stop.max_chars) {
+            if min > max {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Min characters ({}) cannot exceed max characters ({})",
+                    min, max
+                )));
+            }
+        }
+
+        if stop.max_tokens == Some(0) || stop.max_chars == Some(0) {
+            return Err(DatasetError::InvalidInput(
+                "Max tokens and max characters must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Rule branch used when the value of `column` (by name) satisfies `operator` and `value`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalRule {
+    pub column: String,
+    pub operator: ConditionOperator,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default)]
+    pub rules: String,
+    /// Overrides the column null probability for this branch
+    #[serde(default)]
+    pub null_probability: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConditionOperator {
+    Equals,
+    NotEquals,
+    Contains,
+    IsEmpty,
+    IsNotEmpty,
+}
+
+impl ConditionalRule {
+    /// Case-insensitive comparison, a column without value is empty
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        let actual = value.unwrap_or_default().trim().to_lowercase();
+        let expected = self.value.trim().to_lowercase();
+
+        match self.operator {
+            ConditionOperator::Equals => actual == expected,
+            ConditionOperator::NotEquals => actual != expected,
+            ConditionOperator::Contains => actual.contains(&expected),
+            ConditionOperator::IsEmpty => actual.is_empty(),
+            ConditionOperator::IsNotEmpty => !actual.is_empty(),
+        }
+    }
+}
+
+/// Target shape of an INT/FLOAT column, generation steers every cell towards it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DistributionSettings {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Normal target, set together with `stddev`
+    pub mean: Option<f64>,
+    pub stddev: Option<f64>,
+    /// Takes precedence over `mean`/`stddev` when not empty
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// Inclusive `min..=max` range expected to hold `share` of the values, overlapping values count for the
+/// first matching bucket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramBucket {
+    pub min: f64,
+    pub max: f64,
+    pub share: f64,
+}
+
+impl DistributionSettings {
+    pub fn validate(&self) -> Result<(), DatasetError> {
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Distribution min ({}) cannot exceed max ({})",
+                    min, max
+                )));
+            }
+        }
+
+        match (self.mean, self.stddev) {
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(DatasetError::InvalidInput(
+                    "Distribution mean and standard deviation must be set together".to_string(),
+                ))
+            }
+            (_, Some(stddev)) if stddev < 0.0 => {
+                return Err(DatasetError::InvalidInput(
+                    "Standard deviation cannot be negative".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        for bucket in &self.buckets {
+            if bucket.min > bucket.max || bucket.share <= 0.0 {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Invalid histogram bucket {}..={} with share {}",
+                    bucket.min, bucket.max, bucket.share
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+
+    /// Index of the first bucket holding `value`
+    pub fn bucket_index(&self, value: f64) -> Option<usize> {
+        self.buckets
+            .iter()
+            .position(|bucket| value >= bucket.min && value <= bucket.max)
+    }
+}
+
+/// Realised statistics of a column compared to its distribution target
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DistributionReport {
+    pub column_id: i64,
+    pub column_name: String,
+    pub target: DistributionSettings,
+    pub count: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub stddev: Option<f64>,
+    /// Values outside the target `min..=max`
+    pub out_of_range: usize,
+    pub buckets: Vec<BucketReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketReport {
+    pub min: f64,
+    pub max: f64,
+    pub target_share: f64,
+    pub realized_share: f64,
+}
+
+impl DistributionReport {
+    pub fn from_values(column_id: i64, column_name: &str, target: &DistributionSettings, values: &[f64]) -> Self {
+        let count = values.len();
+        let mean = (count > 0).then(|| values.iter().sum::<f64>() / count as f64);
+        let stddev = mean.map(|mean| (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64).sqrt());
+
+        let total_share: f64 = target.buckets.iter().map(|bucket| bucket.share).sum();
+        let mut bucket_counts = vec![0usize; target.buckets.len()];
+        for value in values {
+            if let Some(index) = target.bucket_index(*value) {
+                bucket_counts[index] += 1;
+            }
+        }
+
+        let buckets = target
+            .buckets
+            .iter()
+            .zip(bucket_counts)
+            .map(|(bucket, bucket_count)| BucketReport {
+                min: bucket.min,
+                max: bucket.max,
+                target_share: bucket.share / total_share,
+                realized_share: if count > 0 {
+                    bucket_count as f64 / count as f64
+                } else {
+                    0.0
+                },
+            })
+            .collect();
+
+        Self {
+            column_id,
+            column_name: column_name.to_string(),
+            target: target.clone(),
+            count,
+            min: values.iter().copied().reduce(f64::min),
+            max: values.iter().copied().reduce(f64::max),
+            mean,
+            stddev,
+            out_of_range: values.iter().filter(|value| !target.contains(**value)).count(),
+            buckets,
+        }
+    }
+}
+
+/// Source of a `REFERENCE` column: each generated row samples one row of `dataset_id`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceSettings {
+    pub dataset_id: i64,
+    /// Column whose value is stored in the cell, the sampled row's id when unset
+    pub column_id: Option<i64>,
+    /// Numeric column used as sampling weight, rows are picked uniformly when unset
+    pub weight_column_id: Option<i64>,
+}
+
+/// Row picked for a `REFERENCE` column
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledReference {
+    pub value: String,
+    /// Values of the sampled row keyed by column name, plus its `id`
+    pub fields: HashMap<String, String>,
+}
+
+/// Per-column overrides of the default single-line stop heuristics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StopSettings {
+    pub stop_sequences: Vec<String>,
+    pub min_tokens: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub min_chars: Option<usize>,
+    pub max_chars: Option<usize>,
+    pub multiline: bool,
+    /// Defaults to stopping at the end of a sentence unless `multiline` is set
+    pub stop_at_sentence_end: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UniqueMode {
+    Exact,
+    Normalized,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FewShotStrategy {
+    Random,
+    MostRecent,
+    Pinned,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FewShotSettings {
+    pub strategy: FewShotStrategy,
+    pub count: usize,
+}
+
+/// Lowercases and strips punctuation so "John  Smith" and "john-smith." compare equal
+pub fn normalize_value(value: &str) -> String {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -76,6 +531,29 @@ pub struct UpdatableColumnFields {
     pub column_type: Option<String>,
     pub column_type_details: Option<String>,
     pub position: Option<String>,
+    pub settings: Option<ColumnSettings>,
+}
+
+const COLUMN_FIELDS: &str =
+    "id, table_name, dataset_id, name, column_type, column_type_details, rules, position, settings";
+
+fn column_from_row(row: &rusqlite::Row) -> Result<Column, DatabaseError> {
+    let settings = match row.get::<_, Option<String>>(8)? {
+        Some(settings) if !settings.trim().is_empty() => serde_json::from_str(&settings)?,
+        _ => ColumnSettings::default(),
+    };
+
+    Ok(Column {
+        id: Some(row.get::<_, i64>(0)?),
+        table_name: row.get::<_, String>(1)?,
+        dataset_id: row.get::<_, i64>(2)?,
+        name: row.get::<_, String>(3)?,
+        column_type: row.get::<_, String>(4)?,
+        column_type_details: Some(row.get::<_, String>(5)?),
+        rules: row.get::<_, String>(6)?,
+        position: row.get::<_, i64>(7)?,
+        settings,
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,1759 +568,7899 @@ pub struct Row {
 pub struct RowData {
     #[serde(rename = "columnId", alias = "column_id")]
     pub column_id: String,
-    pub value: String,
+    /// `None` is a missing value, only allowed in nullable columns
+    #[serde(default)]
+    pub value: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl RowData {
+    /// The value with null read as an empty string
+    pub fn value_or_empty(&self) -> &str {
+        self.value.as_deref().unwrap_or_default()
+    }
+}
+
+/// How a generated cell was produced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct PaginatedResponse {
-    pub data: Vec<HashMap<String, serde_json::Value>>,
-    pub page: i64,
-    pub page_size: i64,
-    pub total_rows: i64,
-    pub total_pages: i64,
-    pub has_next: bool,
-    pub has_previous: bool,
+pub struct CellProvenance {
+    pub model_id: Option<i64>,
+    pub generation_id: Option<String>,
+    /// Hash of the final rendered prompt, the JSON row prompt for `jsonObject` rows
+    pub prompt_hash: String,
+    /// Hash of the rules in effect, after conditional branches
+    pub rules_hash: String,
+    /// Completions requested for the cell, retries included
+    pub attempts: usize,
+    /// Sampler seed of the completion the value was kept from
+    #[serde(default)]
+    pub seed: Option<u32>,
+    #[serde(default)]
+    pub created_at: Option<String>,
 }
 
-#[derive(Clone)]
-pub struct DatasetService {
-    pub db: DatabaseService,
+/// Criteria for rows to audit or regenerate, unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProvenanceFilter {
+    pub column_id: Option<i64>,
+    pub model_id: Option<i64>,
+    pub generation_id: Option<String>,
+    pub rules_hash: Option<String>,
 }
 
-impl DatasetService {
-    pub fn new(db: DatabaseService) -> Result<Self, DatabaseError> {
-        let dataset_service = Self { db };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowSort {
+    pub column_id: i64,
+    #[serde(default)]
+    pub descending: bool,
+}
 
-        dataset_service.create_dataset_metadata_default_table()?;
-        dataset_service.create_columns_default_table()?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterOperator {
+    Equals,
+    Contains,
+    Range,
+    IsEmpty,
+}
 
-        Ok(dataset_service)
-    }
+/// `value` is used by `equals`/`contains`, `min`/`max` (both inclusive, either optional) by `range`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowFilter {
+    pub column_id: i64,
+    pub operator: FilterOperator,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub min: Option<String>,
+    #[serde(default)]
+    pub max: Option<String>,
+}
 
-    pub fn create_dataset_metadata_default_table(&self) -> SqliteResult<(), DatabaseError> {
-        let conn = self
-            .db
-            .conn
-            .lock()
-            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?;
+/// Sorting, filtering and search applied by `fetch_rows`, filters are combined with AND
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RowQuery {
+    pub sort: Option<RowSort>,
+    pub filters: Vec<RowFilter>,
+    /// Case-insensitive substring matched against every cell of the row
+    pub search: Option<String>,
+}
 
-        conn.execute(
-            "
-            CREATE TABLE IF NOT EXISTS datasets_metadata (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                table_name TEXT NOT NULL,
-                name TEXT NOT NULL,
-                description TEXT,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )
-        ",
-            [],
-        )?;
+/// `WHERE`/`ORDER BY` clauses over the rows table aliased `t`, with the positional parameters of `WHERE`
+#[derive(Debug, Default)]
+pub struct RowQuerySql {
+    pub where_clause: String,
+    pub where_params: Vec<rusqlite::types::Value>,
+    pub order_by: String,
+}
 
-        conn.execute(
-            "
-            CREATE INDEX IF NOT EXISTS idx_datasets_metadata_name ON datasets_metadata(name)
-        ",
-            [],
-        )?;
+/// Rows a bulk operation applies to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RowSelection {
+    /// Ids missing from the dataset are ignored
+    Ids(Vec<i64>),
+    /// Every row matching the filters and search, the sort is ignored
+    Query(RowQuery),
+}
 
-        Ok(())
-    }
+/// Tables holding per-row state that must follow the rows they point to
+const ROW_STATE_TABLES: &[&str] = &["pinned_rows", "column_values", "pii_flags", "cell_provenance"];
 
-    pub fn create_columns_default_table(&self) -> SqliteResult<(), DatabaseError> {
-        let conn = self
-            .db
-            .conn
-            .lock()
-            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?;
+/// History entries kept per dataset, older edits can no longer be undone
+pub const HISTORY_LIMIT: i64 = 100;
 
-        conn.execute(
-            "
-            CREATE TABLE IF NOT EXISTS columns (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                table_name TEXT NOT NULL,
-                dataset_id INTEGER NOT NULL,
-                name TEXT NOT NULL,
-                column_type TEXT NOT NULL,
-                column_type_details TEXT DEFAULT '',
-                rules TEXT,
-                position INTEGER NOT NULL,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
-            )
-        ",
-            [],
-        )?;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowChange {
+    pub row_id: i64,
+    pub before: Vec<RowData>,
+    pub after: Vec<RowData>,
+}
 
-        conn.execute(
-            "
-            CREATE INDEX IF NOT EXISTS idx_column_position ON columns(position)
-        ",
-            [],
-        )?;
+/// A recorded edit, holding what is needed to revert and replay it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum HistoryOperation {
+    RowEdit {
+        rows: Vec<RowChange>,
+    },
+    RowDelete {
+        rows: Vec<Row>,
+    },
+    ColumnEdit {
+        before: Box<Column>,
+        after: Box<Column>,
+    },
+    /// `values` are the cells the column had, by row id
+    ColumnDelete {
+        column: Box<Column>,
+        values: Vec<(i64, Option<String>)>,
+    },
+}
 
-        Ok(())
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub dataset_id: i64,
+    pub operation: HistoryOperation,
+    pub undone: bool,
+    pub created_at: String,
+}
 
-    pub fn create(&self, name: &str, description: &str) -> Result<DatasetMetadata, DatasetError> {
-        let next_id = self
-            .db
-            .conn
-            .lock()
-            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?
-            .query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM datasets_metadata", [], |row| {
-                row.get::<_, i64>(0)
-            })?;
+const HISTORY_FIELDS: &str = "id, dataset_id, operation, undone, created_at";
 
-        let table_name = format!("dataset{}", next_id);
+fn history_entry_from_row(row: &rusqlite::Row) -> Result<HistoryEntry, DatabaseError> {
+    let operation: String = row.get(2)?;
 
-        self.db.execute(
-            "INSERT INTO datasets_metadata (table_name, name, description, created_at, updated_at) VALUES (?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
-            [table_name, name.trim().to_string(), description.trim().to_string()],
-        )?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        dataset_id: row.get(1)?,
+        operation: serde_json::from_str(&operation)?,
+        undone: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
 
-        let datasets = self
-            .db
-            .query("SELECT * FROM datasets_metadata ORDER BY id DESC LIMIT 1", [], |row| {
-                Ok(DatasetMetadata {
-                    id: row.get::<_, i64>(0)?,
-                    table_name: row.get::<_, String>(1)?,
-                    name: row.get::<_, String>(2)?,
-                    description: row.get::<_, String>(3)?,
-                    created_at: row.get::<_, String>(4)?,
-                    updated_at: row.get::<_, String>(5)?,
-                    row_count: 0,
-                })
-            })?;
+/// A statement with owned parameters, so transactions can be assembled from several helpers
+type OwnedQuery = (String, Vec<rusqlite::types::Value>);
 
-        datasets
-            .into_iter()
-            .next()
-            .ok_or_else(|| DatasetError::NotFound(format!("Dataset not found")))
-    }
+/// A frozen copy of the columns and rows of a dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetSnapshot {
+    pub id: i64,
+    pub dataset_id: i64,
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub row_count: i64,
+    pub created_at: String,
+}
 
-    pub fn find_by_id(&self, id: i64) -> Result<DatasetMetadata, DatasetError> {
-        if id <= 0 {
-            return Err(DatasetError::InvalidInput(
-                "Dataset ID must be a positive integer".to_string(),
-            ));
-        }
+const SNAPSHOT_FIELDS: &str = "id, dataset_id, name, columns, row_count, created_at";
 
-        let datasets = self
-            .db
-            .query("SELECT * FROM datasets_metadata WHERE id = ?", [id], |row| {
-                Ok(DatasetMetadata {
-                    id: row.get::<_, i64>(0)?,
-                    table_name: row.get::<_, String>(1)?,
-                    name: row.get::<_, String>(2)?,
-                    description: row.get::<_, String>(3)?,
-                    created_at: row.get::<_, String>(4)?,
-                    updated_at: row.get::<_, String>(5)?,
-                    row_count: 0,
-                })
-            })?;
+fn snapshot_from_row(row: &rusqlite::Row) -> Result<DatasetSnapshot, DatabaseError> {
+    let columns: String = row.get(3)?;
 
-        let mut dataset = datasets
-            .into_iter()
-            .next()
-            .ok_or_else(|| DatasetError::NotFound(format!("Dataset not found")))?;
+    Ok(DatasetSnapshot {
+        id: row.get(0)?,
+        dataset_id: row.get(1)?,
+        name: row.get(2)?,
+        columns: serde_json::from_str(&columns)?,
+        row_count: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
 
-        dataset.row_count = self.count_rows(&dataset.table_name).unwrap_or(0);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnDiff {
+    pub column_id: i64,
+    pub name: String,
+    /// Rows present on both sides whose value in this column differs
+    pub changed_rows: Vec<i64>,
+}
 
-        Ok(dataset)
-    }
+/// Differences going from one version of a dataset to another, rows and columns are matched by id
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    pub added_rows: Vec<i64>,
+    pub removed_rows: Vec<i64>,
+    pub changed_rows: Vec<i64>,
+    pub added_columns: Vec<i64>,
+    pub removed_columns: Vec<i64>,
+    /// Columns with at least one changed cell
+    pub columns: Vec<ColumnDiff>,
+}
 
-    pub fn find_all(&self) -> Result<Vec<DatasetMetadata>, DatasetError> {
-        let mut datasets = self
-            .db
-            .query("SELECT * FROM datasets_metadata ORDER BY created_at DESC", [], |row| {
-                Ok(DatasetMetadata {
-                    id: row.get::<_, i64>(0)?,
-                    table_name: row.get::<_, String>(1)?,
-                    name: row.get::<_, String>(2)?,
-                    description: row.get::<_, String>(3)?,
-                    created_at: row.get::<_, String>(4)?,
-                    updated_at: row.get::<_, String>(5)?,
-                    row_count: 0,
+impl SnapshotDiff {
+    pub fn between(from_columns: &[Column], from_rows: &[Row], to_columns: &[Column], to_rows: &[Row]) -> Self {
+        let column_ids = |columns: &[Column]| columns.iter().filter_map(|column| column.id).collect::<Vec<_>>();
+        let (from_column_ids, to_column_ids) = (column_ids(from_columns), column_ids(to_columns));
+        let from_rows: HashMap<i64, &Row> = from_rows.iter().map(|row| (row.id, row)).collect();
+        let to_row_ids: HashSet<i64> = to_rows.iter().map(|row| row.id).collect();
+
+        let cell = |row: &Row, column_id: i64| {
+            row.data
+                .iter()
+                .find(|data| data.column_id == column_id.to_string())
+                .and_then(|data| data.value.clone())
+        };
+
+        let mut diff = SnapshotDiff {
+            added_columns: to_column_ids
+                .iter()
+                .filter(|id| !from_column_ids.contains(id))
+                .copied()
+                .collect(),
+            removed_columns: from_column_ids
+                .iter()
+                .filter(|id| !to_column_ids.contains(id))
+                .copied()
+                .collect(),
+            ..Default::default()
+        };
+        let mut columns: Vec<ColumnDiff> = to_columns
+            .iter()
+            .filter_map(|column| {
+                let column_id = column.id.filter(|id| from_column_ids.contains(id))?;
+                Some(ColumnDiff {
+                    column_id,
+                    name: column.name.clone(),
+                    changed_rows: Vec::new(),
                 })
-            })?;
+            })
+            .collect();
 
-        for dataset in &mut datasets {
-            dataset.row_count = self.count_rows(&dataset.table_name).unwrap_or(0);
+        for row in to_rows {
+            let Some(from_row) = from_rows.get(&row.id) else {
+                diff.added_rows.push(row.id);
+                continue;
+            };
+
+            let mut changed = false;
+            for column in &mut columns {
+                if cell(from_row, column.column_id) != cell(row, column.column_id) {
+                    column.changed_rows.push(row.id);
+                    changed = true;
+                }
+            }
+            if changed {
+                diff.changed_rows.push(row.id);
+            }
         }
 
-        Ok(datasets)
+        diff.removed_rows = from_rows
+            .keys()
+            .filter(|row_id| !to_row_ids.contains(row_id))
+            .copied()
+            .collect();
+        diff.removed_rows.sort_unstable();
+        diff.columns = columns
+            .into_iter()
+            .filter(|column| !column.changed_rows.is_empty())
+            .collect();
+
+        diff
     }
+}
 
-    pub fn update(
-        &self,
-        id: i64,
-        name: Option<&str>,
-        description: Option<&str>,
-    ) -> Result<DatasetMetadata, DatasetError> {
-        if id <= 0 {
-            return Err(DatasetError::InvalidInput(
-                "Dataset ID must be a positive integer".to_string(),
-            ));
-        }
+impl RowQuery {
+    pub fn to_sql(&self, columns: &[Column], layout: StorageLayout) -> Result<RowQuerySql, DatasetError> {
+        let find_column = |column_id: i64| {
+            columns
+                .iter()
+                .find(|column| column.id == Some(column_id))
+                .ok_or_else(|| DatasetError::InvalidInput(format!("Column {} not found in dataset", column_id)))
+        };
+        let is_numeric = |column: &Column| StoredType::of(column).is_numeric();
+        // `(is empty, value)` of a numeric cell, columnar datasets store numbers so only JSON cells are cast
+        let number_sql = |column: &Column| match layout {
+            StorageLayout::Json => {
+                let cell = cell_value_sql(layout, column);
+                (
+                    format!("COALESCE(TRIM({}), '') = ''", cell),
+                    format!("CAST({} AS REAL)", cell),
+                )
+            }
+            StorageLayout::Columnar => {
+                let stored = format!("t.{}", physical_column(column.id.unwrap_or_default()));
+                (format!("COALESCE({}, '') = ''", stored), stored)
+            }
+        };
+
+        let mut conditions = Vec::new();
+        let mut sql = RowQuerySql::default();
+
+        for filter in &self.filters {
+            let column = find_column(filter.column_id)?;
+            let cell = cell_value_sql(layout, column);
+
+            match filter.operator {
+                FilterOperator::Equals => {
+                    let value = filter.value.as_deref().unwrap_or_default().trim().to_string();
+                    if is_numeric(column) {
+                        let number = parse_filter_number(&value)?;
+                        let (empty, stored) = number_sql(column);
+                        conditions.push(format!("(NOT {} AND {} = ?)", empty, stored));
+                        sql.where_params.push(rusqlite::types::Value::Real(number));
+                    } else {
+                        conditions.push(format!("TRIM({}) = ? COLLATE NOCASE", cell));
+                        sql.where_params.push(rusqlite::types::Value::Text(value));
+                    }
+                }
+                FilterOperator::Contains => {
+                    let value = filter.value.as_deref().unwrap_or_default();
+                    conditions.push(format!("{} LIKE ? ESCAPE '\\'", cell));
+                    sql.where_params.push(rusqlite::types::Value::Text(like_pattern(value)));
+                }
+                FilterOperator::Range => {
+                    if filter.min.is_none() && filter.max.is_none() {
+                        return Err(DatasetError::InvalidInput(
+                            "Range filter needs a min or a max".to_string(),
+                        ));
+                    }
+
+                    let (empty, number) = number_sql(column);
+                    let mut bounds = vec![if is_numeric(column) {
+                        format!("NOT {}", empty)
+                    } else {
+                        format!("COALESCE(TRIM({}), '') != ''", cell)
+                    }];
+
+                    for (bound, operator) in [(&filter.min, ">="), (&filter.max, "<=")] {
+                        let Some(bound) = bound.as_deref().map(str::trim) else {
+                            continue;
+                        };
+
+                        if is_numeric(column) {
+                            bounds.push(format!("{} {} ?", number, operator));
+                            sql.where_params
+                                .push(rusqlite::types::Value::Real(parse_filter_number(bound)?));
+                        } else {
+                            bounds.push(format!("CAST({} AS TEXT) {} ?", cell, operator));
+                            sql.where_params.push(rusqlite::types::Value::Text(bound.to_string()));
+                        }
+                    }
 
-        if let Some(name) = name {
-            if name.trim().is_empty() {
-                return Err(DatasetError::InvalidInput("Dataset name cannot be empty".to_string()));
+                    conditions.push(format!("({})", bounds.join(" AND ")));
+                }
+                FilterOperator::IsEmpty => {
+                    conditions.push(format!("COALESCE(TRIM({}), '') = ''", cell));
+                }
             }
-            self.db.execute(
-                "UPDATE datasets_metadata SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-                [name.trim(), &id.to_string()],
-            )?;
         }
 
-        if let Some(description) = description {
-            self.db.execute(
-                "UPDATE datasets_metadata SET description = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-                [description, &id.to_string()],
-            )?;
+        if let Some(search) = self
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+        {
+            let pattern = rusqlite::types::Value::Text(like_pattern(search));
+
+            match layout {
+                StorageLayout::Json => {
+                    conditions.push(
+                        "EXISTS (SELECT 1 FROM json_each(t.data) item WHERE json_extract(item.value, '$.value') LIKE ? ESCAPE '\\')"
+                            .to_string(),
+                    );
+                    sql.where_params.push(pattern);
+                }
+                StorageLayout::Columnar => {
+                    let cells = columns
+                        .iter()
+                        .filter(|column| column.id.is_some())
+                        .map(|column| format!("{} LIKE ? ESCAPE '\\'", cell_value_sql(layout, column)))
+                        .collect::<Vec<_>>();
+
+                    sql.where_params.extend(std::iter::repeat_n(pattern, cells.len()));
+                    conditions.push(if cells.is_empty() {
+                        "0".to_string()
+                    } else {
+                        format!("({})", cells.join(" OR "))
+                    });
+                }
+            }
         }
 
-        self.find_by_id(id)
-    }
-
-    pub fn delete(&self, id: i64) -> Result<(), DatasetError> {
-        if id <= 0 {
-            return Err(DatasetError::InvalidInput(
-                "Dataset ID must be a positive integer".to_string(),
-            ));
+        if !conditions.is_empty() {
+            sql.where_clause = format!("WHERE {}", conditions.join(" AND "));
         }
 
-        self.find_by_id(id)?;
+        sql.order_by = match &self.sort {
+            Some(sort) => {
+                let column = find_column(sort.column_id)?;
+                let direction = if sort.descending { "DESC" } else { "ASC" };
+                let cell = cell_value_sql(layout, column);
+
+                // Empty cells always come last, numeric columns order by value rather than text
+                let order_by = if is_numeric(column) {
+                    let (empty, number) = number_sql(column);
+                    format!("{} ASC, {} {}, t.id ASC", empty, number, direction)
+                } else {
+                    format!(
+                        "COALESCE(TRIM({0}), '') = '' ASC, CAST({0} AS TEXT) COLLATE NOCASE {1}, t.id ASC",
+                        cell, direction
+                    )
+                };
+
+                format!("ORDER BY {}", order_by)
+            }
+            None => "ORDER BY t.id ASC".to_string(),
+        };
 
-        self.db.execute("DELETE FROM datasets_metadata WHERE id = ?", [id])?;
-        Ok(())
+        Ok(sql)
     }
+}
 
-    pub fn add_columns(&self, dataset_id: i64, columns: &[Column]) -> Result<Vec<Column>, DatasetError> {
-        let dataset_metadata = self.find_by_id(dataset_id)?;
-
-        let table_name = dataset_metadata.table_name;
+fn parse_filter_number(value: &str) -> Result<f64, DatasetError> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| DatasetError::InvalidInput(format!("'{}' is not a number", value)))
+}
 
-        if !self.db.table_exists(&table_name)? {
-            self.db
-                .create_table(&table_name, &["data JSON DEFAULT '{}' CHECK(json_valid(data))"], &[])?;
-        }
+fn like_pattern(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
 
-        let insert_query = "INSERT INTO columns (dataset_id, table_name, name, column_type, column_type_details, rules, position) VALUES (?, ?, ?, ?, ?, ?, ?)";
-        self.db.execute_batch(
-            &insert_query,
-            &columns
-                .iter()
-                .map(|c| {
-                    [
-                        c.dataset_id.to_string(),
-                        c.table_name.to_string(),
-                        c.name.trim().to_string(),
-                        c.column_type.trim().to_string(),
-                        c.column_type_details
-                            .clone()
-                            .unwrap_or("".to_string())
-                            .trim()
-                            .to_string(),
-                        c.rules.trim().to_string(),
-                        c.position.to_string(),
-                    ]
-                })
-                .collect::<Vec<_>>(),
-        )?;
+fn search_table(table_name: &str) -> String {
+    format!("{}_fts", table_name)
+}
 
-        let new_columns = self.get_columns(dataset_id)?;
+/// Quotes every term so user input never reaches the FTS5 query syntax
+fn fts_match_query(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
 
-        let update_query = format!(
-            "UPDATE {} SET data = json_insert(
-                data,
-                '$[#]',
-                json_object('column_id', ?, 'value', ?)
-            )
-            WHERE json_type(data) = 'array' AND data != '[]'",
-            table_name
-        );
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
 
-        let params: Vec<[String; 2]> = new_columns
-            .iter()
-            .rev()
-            .take(columns.len())
-            .map(|c| {
-                let column_id = c.id.expect("Column should have an ID after insertion");
-                [column_id.to_string(), "".to_string()]
-            })
-            .collect();
+/// How the rows of a dataset are stored. `Json` keeps one `data` array per row, `Columnar` keeps one SQLite
+/// column per dataset column in `<table>_cols` and exposes the same `id, data, created_at, updated_at`
+/// shape through a view named after the dataset table, so reads and row writes work on both
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageLayout {
+    #[default]
+    Json,
+    Columnar,
+}
 
-        self.db.execute_batch(&update_query, &params)?;
+fn columnar_table(table_name: &str) -> String {
+    format!("{}_cols", table_name)
+}
 
-        Ok(new_columns)
-    }
+fn physical_column(column_id: i64) -> String {
+    format!("c_{}", column_id)
+}
 
-    pub fn get_columns(&self, dataset_id: i64) -> Result<Vec<Column>, DatasetError> {
-        let columns = self.db.query(
-            "SELECT * FROM columns WHERE dataset_id = ? ORDER BY position ASC",
-            [dataset_id],
-            |row| {
-                Ok(Column {
-                    id: Some(row.get::<_, i64>(0)?),
-                    table_name: row.get::<_, String>(1)?,
-                    dataset_id: row.get::<_, i64>(2)?,
-                    name: row.get::<_, String>(3)?,
-                    column_type: row.get::<_, String>(4)?,
-                    column_type_details: Some(row.get::<_, String>(5)?),
-                    rules: row.get::<_, String>(6)?,
-                    position: row.get::<_, i64>(7)?,
-                })
-            },
-        )?;
+/// How the cells of a column are kept in the storage table of a columnar dataset. Only `Text` keeps values
+/// exactly as written, `INT` and `FLOAT` cells are stored as numbers and `BOOL` cells as `0`/`1`. Empty cells
+/// stay `''` (or `NULL` in nullable columns) whatever the type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoredType {
+    Text,
+    Integer,
+    Real,
+    Bool,
+}
 
-        Ok(columns)
+impl StoredType {
+    fn of(column: &Column) -> Self {
+        match column.column_type.trim() {
+            "INT" => StoredType::Integer,
+            "FLOAT" => StoredType::Real,
+            "BOOL" => StoredType::Bool,
+            _ => StoredType::Text,
+        }
     }
 
-    pub fn update_column(&self, id: i64, updates: UpdatableColumnFields) -> Result<Column, DatasetError> {
-        let mut set_parts: Vec<String> = Vec::new();
-        let mut dyn_params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    fn is_numeric(self) -> bool {
+        matches!(self, StoredType::Integer | StoredType::Real)
+    }
 
-        for (column_name, value_option) in [
-            ("name", updates.name.as_ref()),
-            ("rules", updates.rules.as_ref()),
-            ("column_type", updates.column_type.as_ref()),
-            ("column_type_details", updates.column_type_details.as_ref()),
-            ("position", updates.position.as_ref()),
-        ] {
-            if let Some(value) = value_option {
-                let trimmed_value = value.trim();
-                if !trimmed_value.is_empty() {
-                    set_parts.push(format!("{} = ?", column_name));
-                    dyn_params.push(Box::new(trimmed_value.to_string()));
-                }
-            }
+    /// Declaration of the storage column, the check refuses cells that didn't convert
+    fn definition(self, name: &str) -> String {
+        match self {
+            StoredType::Text => format!("{} TEXT", name),
+            StoredType::Integer => format!(
+                "{0} INTEGER CHECK ({0} IS NULL OR {0} = '' OR typeof({0}) = 'integer')",
+                name
+            ),
+            StoredType::Real => format!("{0} REAL CHECK ({0} IS NULL OR {0} = '' OR typeof({0}) = 'real')", name),
+            StoredType::Bool => format!("{0} INTEGER CHECK ({0} IS NULL OR {0} = '' OR {0} IN (0, 1))", name),
         }
+    }
 
-        if set_parts.is_empty() {
-            return Err(DatasetError::InvalidInput("No updates provided".to_string()));
+    /// Converts the text cell `value` for storage, numbers go through the column affinity
+    fn write_sql(self, value: &str) -> String {
+        match self {
+            StoredType::Text => value.to_string(),
+            StoredType::Integer | StoredType::Real => format!("TRIM({})", value),
+            StoredType::Bool => format!(
+                "CASE LOWER(TRIM({0})) WHEN 'true' THEN 1 WHEN '1' THEN 1 WHEN 'false' THEN 0 WHEN '0' THEN 0 ELSE {0} END",
+                value
+            ),
         }
+    }
 
-        let set_clause = format!("{} , updated_at = CURRENT_TIMESTAMP", set_parts.join(", "));
-        let query = format!("UPDATE columns SET {} WHERE id = ?", set_clause);
-
-        let mut param_refs: Vec<&dyn rusqlite::ToSql> =
-            dyn_params.iter().map(|p| p.as_ref() as &dyn rusqlite::ToSql).collect();
-        param_refs.push(&id);
+    /// Text of the stored cell `stored`, as rows expose it
+    fn read_sql(self, stored: &str) -> String {
+        match self {
+            StoredType::Text => stored.to_string(),
+            StoredType::Integer | StoredType::Real => format!("CAST({} AS TEXT)", stored),
+            StoredType::Bool => format!("CASE {0} WHEN 1 THEN 'true' WHEN 0 THEN 'false' ELSE {0} END", stored),
+        }
+    }
 
-        self.db.execute(&query, &param_refs[..])?;
+    /// Same rules as `write_sql` and the column check, so a bad value is refused before it reaches SQLite
+    fn accepts(self, value: &str) -> bool {
+        let value = value.trim();
+        value.is_empty()
+            || match self {
+                StoredType::Text => true,
+                StoredType::Integer => value.parse::<i64>().is_ok(),
+                StoredType::Real => value.parse::<f64>().is_ok_and(f64::is_finite),
+                StoredType::Bool => ["true", "false", "1", "0"].contains(&value.to_lowercase().as_str()),
+            }
+    }
+}
 
-        let column = self
-            .db
-            .query("SELECT * FROM columns WHERE id = ?", [id], |row| {
-                Ok(Column {
-                    id: Some(row.get::<_, i64>(0)?),
-                    table_name: row.get::<_, String>(1)?,
-                    dataset_id: row.get::<_, i64>(2)?,
-                    name: row.get::<_, String>(3)?,
-                    column_type: row.get::<_, String>(4)?,
-                    column_type_details: Some(row.get::<_, String>(5)?),
-                    rules: row.get::<_, String>(6)?,
-                    position: row.get::<_, i64>(7)?,
-                })
-            })?
-            .into_iter()
-            .next()
-            .ok_or_else(|| DatasetError::NotFound(format!("Column with id {} not found", id)))?;
+/// Table the row filters run against, aliased `t`
+fn rows_source_sql(layout: StorageLayout, table_name: &str) -> String {
+    match layout {
+        StorageLayout::Json => format!("{} t", table_name),
+        StorageLayout::Columnar => format!("{} t", columnar_table(table_name)),
+    }
+}
 
-        Ok(column)
+/// Text of `column` in the row aliased `t` of `rows_source_sql`
+fn cell_value_sql(layout: StorageLayout, column: &Column) -> String {
+    let column_id = column.id.unwrap_or_default();
+    match layout {
+        StorageLayout::Json => json_cell_sql("t.data", column_id),
+        StorageLayout::Columnar => StoredType::of(column).read_sql(&format!("t.{}", physical_column(column_id))),
     }
+}
 
-    pub fn delete_column(&self, id: i64) -> Result<(), DatasetError> {
-        let column = self.db.query("SELECT * FROM columns WHERE id = ?", [id], |row| {
-            Ok(Column {
-                id: Some(row.get::<_, i64>(0)?),
-                table_name: row.get::<_, String>(1)?,
-                dataset_id: row.get::<_, i64>(2)?,
-                name: row.get::<_, String>(3)?,
-                column_type: row.get::<_, String>(4)?,
-                column_type_details: Some(row.get::<_, String>(5)?),
-                rules: row.get::<_, String>(6)?,
-                position: row.get::<_, i64>(7)?,
-            })
-        })?;
+fn json_cell_sql(data: &str, column_id: i64) -> String {
+    format!(
+        "(SELECT json_extract(item.value, '$.value') FROM json_each({}) item
+        WHERE CAST(COALESCE(json_extract(item.value, '$.columnId'), json_extract(item.value, '$.column_id')) AS TEXT) = '{}')",
+        data, column_id
+    )
+}
 
-        if column.is_empty() {
-            return Err(DatasetError::NotFound(format!("Column with id {} not found", id)));
-        }
+/// Physical columns and the stored value of each column out of a `json_each` over a `data` array aliased
+/// `item`, so a row is read in one pass whatever its number of columns
+fn columnar_cells_sql(columns: &[Column]) -> (Vec<String>, Vec<String>) {
+    columns
+        .iter()
+        .filter_map(|column| {
+            let column_id = column.id?;
+            let value = format!(
+                "MAX(CASE WHEN CAST(COALESCE(json_extract(item.value, '$.columnId'), json_extract(item.value, '$.column_id')) AS TEXT) = '{}'
+                THEN json_extract(item.value, '$.value') END)",
+                column_id
+            );
+            Some((physical_column(column_id), StoredType::of(column).write_sql(&value)))
+        })
+        .unzip()
+}
 
-        let table_name = &column[0].table_name;
+/// `id`, `created_at`, `updated_at` and the text of every column straight from the storage table aliased `t`
+fn columnar_read_sql(table_name: &str, columns: &[Column]) -> String {
+    let cells = columns
+        .iter()
+        .map(|column| format!(", {}", cell_value_sql(StorageLayout::Columnar, column)))
+        .collect::<String>();
+
+    format!(
+        "SELECT t.id, t.created_at, t.updated_at{} FROM {} t",
+        cells,
+        columnar_table(table_name)
+    )
+}
 
-        let update_query = format!(
-            "UPDATE {} SET data = (
-                SELECT COALESCE(json_group_array(value), '[]')
-                FROM json_each(data)
-                WHERE CAST(json_extract(value, '$.column_id') AS TEXT) != ?
-            )
-            WHERE EXISTS (
-                SELECT 1 FROM json_each(data)
-                WHERE CAST(json_extract(value, '$.column_id') AS TEXT) = ?
-            )",
-            table_name
-        );
+fn columnar_select_sql(table_name: &str, columns: &[Column]) -> String {
+    let cells = columns
+        .iter()
+        .filter_map(|column| {
+            Some(format!(
+                "json_object('columnId', '{}', 'value', {})",
+                column.id?,
+                cell_value_sql(StorageLayout::Columnar, column)
+            ))
+        })
+        .collect::<Vec<_>>();
 
-        let position_str = column[0].position.to_string();
-        let dataset_id_str = column[0].dataset_id.to_string();
-        let id_str = id.to_string();
+    format!(
+        "SELECT t.id, json_array({}) AS data, t.created_at, t.updated_at FROM {} t",
+        cells.join(", "),
+        columnar_table(table_name)
+    )
+}
 
-        self.db.execute_transaction(&[
-            (
-                "UPDATE columns SET position = position - 1 WHERE position > ? AND dataset_id = ?",
-                &[
-                    &position_str as &dyn rusqlite::ToSql,
-                    &dataset_id_str as &dyn rusqlite::ToSql,
-                ],
+fn create_columnar_table_sql(table_name: &str, columns: &[Column]) -> String {
+    let definitions = columns
+        .iter()
+        .filter_map(|column| {
+            let name = physical_column(column.id?);
+            Some(format!("{}, ", StoredType::of(column).definition(&name)))
+        })
+        .collect::<String>();
+
+    format!(
+        "CREATE TABLE {} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            {}created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );",
+        columnar_table(table_name),
+        definitions
+    )
+}
+
+/// Copies JSON rows into the storage table, `source` is aliased `t` and has `id`, `data` and timestamps
+fn copy_into_columnar_sql(table_name: &str, columns: &[Column], source: &str) -> String {
+    let (names, values) = columnar_cells_sql(columns);
+
+    format!(
+        "INSERT INTO {} (id, {}created_at, updated_at)
+        SELECT t.id, {}t.created_at, t.updated_at FROM {} LEFT JOIN json_each(t.data) item GROUP BY t.id;",
+        columnar_table(table_name),
+        names.iter().map(|name| format!("{}, ", name)).collect::<String>(),
+        values.iter().map(|value| format!("{}, ", value)).collect::<String>(),
+        source
+    )
+}
+
+/// The view and the `INSTEAD OF` triggers turning its `data` writes into column writes, they list every
+/// column so they are recreated whenever columns change
+fn create_columnar_view_sql(table_name: &str, columns: &[Column]) -> String {
+    let storage = columnar_table(table_name);
+    let (names, values) = columnar_cells_sql(columns);
+
+    // Restored rows come back with their id and timestamps, new rows get fresh ones
+    let row = "NEW.id, COALESCE(NEW.created_at, CURRENT_TIMESTAMP), COALESCE(NEW.updated_at, CURRENT_TIMESTAMP)";
+    let (insert, assignments) = if names.is_empty() {
+        (
+            format!("INSERT INTO {} (id, created_at, updated_at) VALUES ({});", storage, row),
+            String::new(),
+        )
+    } else {
+        (
+            format!(
+                "INSERT INTO {} ({}, id, created_at, updated_at) SELECT {}, {} FROM json_each(NEW.data) item;",
+                storage,
+                names.join(", "),
+                values.join(", "),
+                row
             ),
-            (
-                &update_query,
-                &[&id_str as &dyn rusqlite::ToSql, &id_str as &dyn rusqlite::ToSql],
+            format!(
+                "({}) = (SELECT {} FROM json_each(NEW.data) item), ",
+                names.join(", "),
+                values.join(", ")
             ),
-            ("DELETE FROM columns WHERE id = ?", &[&id_str as &dyn rusqlite::ToSql]),
-        ])?;
+        )
+    };
+
+    format!(
+        "CREATE VIEW {0} AS {1};
+        CREATE TRIGGER {0}_insert INSTEAD OF INSERT ON {0} BEGIN {2} END;
+        CREATE TRIGGER {0}_update INSTEAD OF UPDATE ON {0} BEGIN
+            UPDATE {3} SET {4}updated_at = NEW.updated_at WHERE id = OLD.id;
+        END;
+        CREATE TRIGGER {0}_delete INSTEAD OF DELETE ON {0} BEGIN DELETE FROM {3} WHERE id = OLD.id; END;",
+        table_name,
+        columnar_select_sql(table_name, columns),
+        insert,
+        storage,
+        assignments
+    )
+}
+
+/// Recreates the storage table of a columnar dataset for `columns`, converting every stored row. Used when a
+/// column changes type, a row that doesn't fit the new type fails the whole rebuild
+fn rebuild_columnar_storage_sql(table_name: &str, columns: &[Column]) -> String {
+    let rows = format!("{}_rows", table_name);
+
+    format!(
+        "CREATE TEMP TABLE {0} AS SELECT id, data, created_at, updated_at FROM {1};{2}DROP TABLE {3};{4}{5}DROP TABLE temp.{0};{6}",
+        rows,
+        table_name,
+        drop_columnar_view_sql(table_name),
+        columnar_table(table_name),
+        create_columnar_table_sql(table_name, columns),
+        copy_into_columnar_sql(table_name, columns, &format!("temp.{} t", rows)),
+        create_columnar_view_sql(table_name, columns)
+    )
+}
+
+fn add_physical_column_sql(table_name: &str, column_id: i64, column: &Column) -> String {
+    format!(
+        "ALTER TABLE {} ADD COLUMN {}{};",
+        columnar_table(table_name),
+        StoredType::of(column).definition(&physical_column(column_id)),
+        if column.settings.nullable { "" } else { " DEFAULT ''" }
+    )
+}
+
+fn drop_columnar_view_sql(table_name: &str) -> String {
+    format!(
+        "DROP TRIGGER IF EXISTS {0}_insert;
+        DROP TRIGGER IF EXISTS {0}_update;
+        DROP TRIGGER IF EXISTS {0}_delete;
+        DROP VIEW IF EXISTS {0};",
+        table_name
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedResponse {
+    pub data: Vec<HashMap<String, serde_json::Value>>,
+    pub page: i64,
+    pub page_size: i64,
+    pub total_rows: i64,
+    pub total_pages: i64,
+    pub has_next: bool,
+    pub has_previous: bool,
+}
+
+#[derive(Clone)]
+pub struct DatasetService {
+    pub db: DatabaseService,
+}
+
+impl DatasetService {
+    pub fn new(db: DatabaseService) -> Result<Self, DatabaseError> {
+        let dataset_service = Self { db };
+
+        dataset_service.create_dataset_metadata_default_table()?;
+        dataset_service.create_columns_default_table()?;
+
+        // Migrations build on the base tables above, every other table comes from them
+        dataset_service.db.migrate()?;
+
+        Ok(dataset_service)
+    }
+
+    pub fn create_dataset_metadata_default_table(&self) -> SqliteResult<(), DatabaseError> {
+        let conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?;
+
+        conn.execute(
+            "
+            CREATE TABLE IF NOT EXISTS datasets_metadata (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                name TEXT NOT NULL,
+                description TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )
+        ",
+            [],
+        )?;
+
+        conn.execute(
+            "
+            CREATE INDEX IF NOT EXISTS idx_datasets_metadata_name ON datasets_metadata(name)
+        ",
+            [],
+        )?;
 
         Ok(())
     }
 
-    pub fn get_all_rows(&self, table_name: &str) -> Result<Vec<Row>, DatasetError> {
-        let rows = self.db.query(
+    pub fn create_columns_default_table(&self) -> SqliteResult<(), DatabaseError> {
+        let conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?;
+
+        conn.execute(
+            "
+            CREATE TABLE IF NOT EXISTS columns (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                dataset_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                column_type TEXT NOT NULL,
+                column_type_details TEXT DEFAULT '',
+                rules TEXT,
+                position INTEGER NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (dataset_id) REFERENCES datasets_metadata(id) ON DELETE CASCADE
+            )
+        ",
+            [],
+        )?;
+
+        conn.execute(
+            "
+            CREATE INDEX IF NOT EXISTS idx_column_position ON columns(position)
+        ",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn create(&self, name: &str, description: &str) -> Result<DatasetMetadata, DatasetError> {
+        let next_id = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatabaseError::SqliteError("Failed to acquire mutex lock".to_string()))?
+            .query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM datasets_metadata", [], |row| {
+                row.get::<_, i64>(0)
+            })?;
+
+        let table_name = format!("dataset{}", next_id);
+
+        self.db.execute(
+            "INSERT INTO datasets_metadata (table_name, name, description, created_at, updated_at) VALUES (?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
+            [table_name, name.trim().to_string(), description.trim().to_string()],
+        )?;
+
+        let datasets = self.db.query(
             &format!(
-                "SELECT id, data, created_at, updated_at FROM {} ORDER BY id ASC",
-                table_name
+                "SELECT {} FROM datasets_metadata ORDER BY id DESC LIMIT 1",
+                DATASET_METADATA_FIELDS
+            ),
+            [],
+            dataset_metadata_from_row,
+        )?;
+
+        datasets
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Dataset not found")))
+    }
+
+    pub fn find_by_id(&self, id: i64) -> Result<DatasetMetadata, DatasetError> {
+        if id <= 0 {
+            return Err(DatasetError::InvalidInput(
+                "Dataset ID must be a positive integer".to_string(),
+            ));
+        }
+
+        let datasets = self.db.query(
+            &format!("SELECT {} FROM datasets_metadata WHERE id = ?", DATASET_METADATA_FIELDS),
+            [id],
+            dataset_metadata_from_row,
+        )?;
+
+        let mut dataset = datasets
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Dataset not found")))?;
+
+        dataset.row_count = self.count_rows(&dataset.table_name).unwrap_or(0);
+
+        Ok(dataset)
+    }
+
+    pub fn find_all(&self) -> Result<Vec<DatasetMetadata>, DatasetError> {
+        let mut datasets = self.db.query(
+            &format!(
+                "SELECT {} FROM datasets_metadata ORDER BY created_at DESC",
+                DATASET_METADATA_FIELDS
             ),
             [],
+            dataset_metadata_from_row,
+        )?;
+
+        for dataset in &mut datasets {
+            dataset.row_count = self.count_rows(&dataset.table_name).unwrap_or(0);
+        }
+
+        Ok(datasets)
+    }
+
+    pub fn update(
+        &self,
+        id: i64,
+        name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<DatasetMetadata, DatasetError> {
+        if id <= 0 {
+            return Err(DatasetError::InvalidInput(
+                "Dataset ID must be a positive integer".to_string(),
+            ));
+        }
+
+        if let Some(name) = name {
+            if name.trim().is_empty() {
+                return Err(DatasetError::InvalidInput("Dataset name cannot be empty".to_string()));
+            }
+            self.db.execute(
+                "UPDATE datasets_metadata SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                [name.trim(), &id.to_string()],
+            )?;
+        }
+
+        if let Some(description) = description {
+            self.db.execute(
+                "UPDATE datasets_metadata SET description = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                [description, &id.to_string()],
+            )?;
+        }
+
+        self.find_by_id(id)
+    }
+
+    /// Settings only, without the row count lookup done by `find_by_id`
+    pub fn get_settings(&self, dataset_id: i64) -> Result<DatasetSettings, DatasetError> {
+        let settings = self.db.query(
+            "SELECT settings FROM datasets_metadata WHERE id = ?",
+            [dataset_id],
+            |row| Ok(row.get::<_, Option<String>>(0)?),
+        )?;
+
+        match settings.into_iter().next() {
+            Some(Some(settings)) if !settings.trim().is_empty() => Ok(serde_json::from_str(&settings)?),
+            Some(_) => Ok(DatasetSettings::default()),
+            None => Err(DatasetError::NotFound(format!(
+                "Dataset with id {} not found",
+                dataset_id
+            ))),
+        }
+    }
+
+    pub fn update_settings(&self, id: i64, settings: &DatasetSettings) -> Result<DatasetMetadata, DatasetError> {
+        self.find_by_id(id)?;
+
+        if settings.row_group_size == Some(0) {
+            return Err(DatasetError::InvalidInput(
+                "Row group size must be a positive integer".to_string(),
+            ));
+        }
+
+        self.db.execute(
+            "UPDATE datasets_metadata SET settings = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            rusqlite::params![serde_json::to_string(settings)?, id],
+        )?;
+
+        self.find_by_id(id)
+    }
+
+    pub fn get_prompt_template(&self, dataset_id: i64) -> Result<PromptTemplate, DatasetError> {
+        let templates = self.db.query(
+            "SELECT system_prompt, cell_template FROM prompt_templates WHERE dataset_id = ?",
+            [dataset_id],
             |row| {
-                let data_json: String = row.get(1)?;
-                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+                Ok(PromptTemplate {
+                    system_prompt: row.get::<_, Option<String>>(0)?,
+                    cell_template: row.get::<_, Option<String>>(1)?,
+                })
+            },
+        )?;
+
+        Ok(templates.into_iter().next().unwrap_or_default())
+    }
+
+    pub fn update_prompt_template(
+        &self,
+        dataset_id: i64,
+        template: &PromptTemplate,
+    ) -> Result<PromptTemplate, DatasetError> {
+        self.find_by_id(dataset_id)?;
+
+        let system_prompt = template
+            .system_prompt
+            .as_deref()
+            .map(str::trim)
+            .filter(|prompt| !prompt.is_empty());
+        let cell_template = template
+            .cell_template
+            .as_deref()
+            .map(str::trim)
+            .filter(|template| !template.is_empty());
+
+        if let Some(cell_template) = cell_template {
+            validate_cell_template(cell_template).map_err(DatasetError::InvalidInput)?;
+        }
+
+        self.db.execute(
+            "INSERT INTO prompt_templates (dataset_id, system_prompt, cell_template) VALUES (?, ?, ?)
+            ON CONFLICT(dataset_id) DO UPDATE SET
+                system_prompt = excluded.system_prompt,
+                cell_template = excluded.cell_template,
+                updated_at = CURRENT_TIMESTAMP",
+            rusqlite::params![dataset_id, system_prompt, cell_template],
+        )?;
+
+        self.get_prompt_template(dataset_id)
+    }
+
+    pub fn delete(&self, id: i64) -> Result<(), DatasetError> {
+        if id <= 0 {
+            return Err(DatasetError::InvalidInput(
+                "Dataset ID must be a positive integer".to_string(),
+            ));
+        }
+
+        self.find_by_id(id)?;
+
+        // REFERENCE columns of other datasets would be left pointing at nothing
+        let referencing = self.db.query(
+            "SELECT m.name, c.name FROM columns c JOIN datasets_metadata m ON m.id = c.dataset_id
+            WHERE c.dataset_id != ?1 AND CASE WHEN json_valid(c.settings)
+                THEN json_extract(c.settings, '$.reference.datasetId') = ?1 ELSE 0 END",
+            [id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        if let Some((dataset_name, column_name)) = referencing.first() {
+            return Err(DatasetError::InvalidInput(format!(
+                "Dataset is referenced by column '{}' of '{}', delete or change that column first",
+                column_name, dataset_name
+            )));
+        }
+
+        self.db.execute("DELETE FROM datasets_metadata WHERE id = ?", [id])?;
+        Ok(())
+    }
+
+    /// Removes a dataset left behind by a failed copy or template, rows tables included. The next dataset
+    /// gets the same table name, so nothing of it may stay around
+    pub fn discard(&self, dataset: &DatasetMetadata) -> Result<(), DatasetError> {
+        let table_name = &dataset.table_name;
+        self.db.validate_table_name(table_name)?;
+
+        let mut batch = match self.storage_layout(table_name)? {
+            StorageLayout::Columnar => drop_columnar_view_sql(table_name),
+            StorageLayout::Json => format!("DROP TABLE IF EXISTS {};", table_name),
+        };
+        batch.push_str(&format!(
+            "DROP TABLE IF EXISTS {};DROP TABLE IF EXISTS {};",
+            columnar_table(table_name),
+            search_table(table_name)
+        ));
+
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(&batch)?;
+        tx.execute("DELETE FROM datasets_metadata WHERE id = ?", [dataset.id])?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Copies a dataset with its settings, prompt template, columns and, when `include_rows` is set, its rows and
+    /// their pins. Copied cells point at the new column ids, rows keep their ids and timestamps
+    pub fn duplicate(&self, id: i64, name: Option<&str>, include_rows: bool) -> Result<DatasetMetadata, DatasetError> {
+        let source = self.find_by_id(id)?;
+        let name = match name.map(str::trim) {
+            Some("") => return Err(DatasetError::InvalidInput("Dataset name cannot be empty".to_string())),
+            Some(name) => name.to_string(),
+            None => format!("{} (copy)", source.name),
+        };
+
+        let copy = self.create(&name, &source.description)?;
+        if let Err(e) = self.copy_dataset(&source, &copy, include_rows) {
+            // Don't leave a half copied dataset behind
+            if let Err(cleanup) = self.discard(&copy) {
+                return Err(DatasetError::DatabaseError(format!(
+                    "{}, the partial copy could not be removed: {}",
+                    e, cleanup
+                )));
+            }
+            return Err(e);
+        }
+
+        self.find_by_id(copy.id)
+    }
+
+    fn copy_dataset(
+        &self,
+        source: &DatasetMetadata,
+        copy: &DatasetMetadata,
+        include_rows: bool,
+    ) -> Result<(), DatasetError> {
+        // A table of a deleted dataset may still carry this name, its rows must not end up in the copy
+        if self.db.table_exists(&copy.table_name)? {
+            return Err(DatasetError::InvalidInput(format!(
+                "Table '{}' already exists",
+                copy.table_name
+            )));
+        }
+
+        let columns = self.get_columns(source.id)?;
+        let has_rows_table = self.db.table_exists(&source.table_name)?;
+        let layout = if has_rows_table {
+            self.storage_layout(&source.table_name)?
+        } else {
+            StorageLayout::Json
+        };
+        let rows = if include_rows && has_rows_table {
+            self.get_all_rows(&source.table_name)?
+        } else {
+            Vec::new()
+        };
+
+        self.db.create_table(
+            &copy.table_name,
+            &["data JSON DEFAULT '{}' CHECK(json_valid(data))"],
+            &[],
+        )?;
+
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE datasets_metadata SET settings = ? WHERE id = ?",
+            rusqlite::params![serde_json::to_string(&source.settings)?, copy.id],
+        )?;
+        tx.execute(
+            "INSERT INTO prompt_templates (dataset_id, system_prompt, cell_template)
+            SELECT ?, system_prompt, cell_template FROM prompt_templates WHERE dataset_id = ?",
+            [copy.id, source.id],
+        )?;
+
+        let mut column_ids: HashMap<i64, i64> = HashMap::new();
+        for column in &columns {
+            tx.execute(
+                "INSERT INTO columns (dataset_id, table_name, name, column_type, column_type_details, rules, position, settings) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    copy.id,
+                    copy.table_name,
+                    column.name,
+                    column.column_type,
+                    column.column_type_details.clone().unwrap_or_default(),
+                    column.rules,
+                    column.position,
+                    serde_json::to_string(&column.settings)?,
+                ],
+            )?;
+            if let Some(column_id) = column.id {
+                column_ids.insert(column_id, tx.last_insert_rowid());
+            }
+        }
+
+        let insert_row_query = format!(
+            "INSERT INTO {} (id, data, created_at, updated_at) VALUES (?, ?, ?, ?)",
+            copy.table_name
+        );
+        for row in &rows {
+            let data = row
+                .data
+                .iter()
+                .filter_map(|cell| {
+                    let column_id = column_ids.get(&cell.column_id.parse::<i64>().ok()?)?;
+                    Some(RowData {
+                        column_id: column_id.to_string(),
+                        value: cell.value.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            tx.execute(
+                &insert_row_query,
+                rusqlite::params![row.id, serde_json::to_string(&data)?, row.created_at, row.updated_at],
+            )?;
+        }
+        if include_rows {
+            tx.execute(
+                "INSERT INTO pinned_rows (dataset_id, row_id) SELECT ?, row_id FROM pinned_rows WHERE dataset_id = ?",
+                [copy.id, source.id],
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        self.ensure_search_index(&copy.table_name)?;
+        for column in self.get_columns(copy.id)? {
+            self.rebuild_value_index(&column)?;
+        }
+        if layout == StorageLayout::Columnar {
+            self.convert_to_columnar(copy.id)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_columns(&self, dataset_id: i64, columns: &[Column]) -> Result<Vec<Column>, DatasetError> {
+        for column in columns {
+            column.settings.validate()?;
+            self.validate_column(column)?;
+        }
+
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+
+        let table_name = dataset_metadata.table_name;
+
+        if !self.db.table_exists(&table_name)? {
+            self.db
+                .create_table(&table_name, &["data JSON DEFAULT '{}' CHECK(json_valid(data))"], &[])?;
+        }
+
+        self.ensure_search_index(&table_name)?;
+
+        let insert_query = "INSERT INTO columns (dataset_id, table_name, name, column_type, column_type_details, rules, position, settings) VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+        let settings = columns
+            .iter()
+            .map(|c| serde_json::to_string(&c.settings))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.db.execute_batch(
+            &insert_query,
+            &columns
+                .iter()
+                .zip(settings)
+                .map(|(c, settings)| {
+                    [
+                        c.dataset_id.to_string(),
+                        c.table_name.to_string(),
+                        c.name.trim().to_string(),
+                        c.column_type.trim().to_string(),
+                        c.column_type_details
+                            .clone()
+                            .unwrap_or("".to_string())
+                            .trim()
+                            .to_string(),
+                        c.rules.trim().to_string(),
+                        c.position.to_string(),
+                        settings,
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        let new_columns = self.get_columns(dataset_id)?;
+
+        if self.storage_layout(&table_name)? == StorageLayout::Columnar {
+            let existing = self.db.query(
+                &format!("PRAGMA table_info({})", columnar_table(&table_name)),
+                [],
+                |row| Ok(row.get::<_, String>(1)?),
+            )?;
+            let alteration = new_columns
+                .iter()
+                .filter_map(|column| column.id.map(|id| (id, column)))
+                .filter(|(id, _)| !existing.contains(&physical_column(*id)))
+                .map(|(id, column)| add_physical_column_sql(&table_name, id, column))
+                .collect::<String>();
+
+            self.rebuild_columnar_view(&table_name, &new_columns, &alteration)?;
+            return Ok(new_columns);
+        }
+
+        let update_query = format!(
+            "UPDATE {} SET data = json_insert(
+                data,
+                '$[#]',
+                json_object('column_id', ?, 'value', ?)
+            )
+            WHERE json_type(data) = 'array' AND data != '[]'",
+            table_name
+        );
+
+        let params: Vec<(String, Option<&str>)> = new_columns
+            .iter()
+            .rev()
+            .take(columns.len())
+            .map(|c| {
+                let column_id = c.id.expect("Column should have an ID after insertion");
+                (column_id.to_string(), (!c.settings.nullable).then_some(""))
+            })
+            .collect();
+
+        self.db.execute_batch(&update_query, &params)?;
+
+        Ok(new_columns)
+    }
+
+    pub fn get_columns(&self, dataset_id: i64) -> Result<Vec<Column>, DatasetError> {
+        let columns = self.db.query(
+            &format!(
+                "SELECT {} FROM columns WHERE dataset_id = ? ORDER BY position ASC",
+                COLUMN_FIELDS
+            ),
+            [dataset_id],
+            column_from_row,
+        )?;
+
+        Ok(columns)
+    }
+
+    pub fn update_column(&self, id: i64, updates: UpdatableColumnFields) -> Result<Column, DatasetError> {
+        let mut set_parts: Vec<String> = Vec::new();
+        let mut dyn_params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(settings) = &updates.settings {
+            settings.validate()?;
+        }
+
+        let before = self.find_column(id)?;
+        let mut retyped = before.clone();
+        if let Some(column_type) = updates.column_type.as_deref().filter(|t| !t.trim().is_empty()) {
+            retyped.column_type = column_type.trim().to_string();
+        }
+        let storage_rebuild = self.retype_storage_sql(&before, &retyped)?;
+
+        if updates.settings.is_some() || updates.column_type.is_some() {
+            let mut column = before.clone();
+            if let Some(settings) = &updates.settings {
+                column.settings = settings.clone();
+            }
+            if let Some(column_type) = updates.column_type.as_deref().filter(|t| !t.trim().is_empty()) {
+                column.column_type = column_type.trim().to_string();
+            }
+            self.validate_column(&column)?;
+        }
+
+        let settings = updates.settings.as_ref().map(serde_json::to_string).transpose()?;
+
+        for (column_name, value_option) in [
+            ("name", updates.name.as_ref()),
+            ("rules", updates.rules.as_ref()),
+            ("column_type", updates.column_type.as_ref()),
+            ("column_type_details", updates.column_type_details.as_ref()),
+            ("position", updates.position.as_ref()),
+            ("settings", settings.as_ref()),
+        ] {
+            if let Some(value) = value_option {
+                let trimmed_value = value.trim();
+                if !trimmed_value.is_empty() {
+                    set_parts.push(format!("{} = ?", column_name));
+                    dyn_params.push(Box::new(trimmed_value.to_string()));
+                }
+            }
+        }
+
+        if set_parts.is_empty() {
+            return Err(DatasetError::InvalidInput("No updates provided".to_string()));
+        }
+
+        let set_clause = format!("{} , updated_at = CURRENT_TIMESTAMP", set_parts.join(", "));
+        let query = format!("UPDATE columns SET {} WHERE id = ?", set_clause);
+
+        let mut param_refs: Vec<&dyn rusqlite::ToSql> =
+            dyn_params.iter().map(|p| p.as_ref() as &dyn rusqlite::ToSql).collect();
+        param_refs.push(&id);
+
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        tx.execute(&query, &param_refs[..])?;
+        if let Some(batch) = &storage_rebuild {
+            tx.execute_batch(batch)?;
+        }
+        let column = tx.query_row(
+            &format!("SELECT {} FROM columns WHERE id = ?", COLUMN_FIELDS),
+            [id],
+            |row| column_from_row(row).map_err(|_| rusqlite::Error::InvalidQuery),
+        )?;
+        let history = Self::history_queries(
+            column.dataset_id,
+            &HistoryOperation::ColumnEdit {
+                before: Box::new(before),
+                after: Box::new(column.clone()),
+            },
+        )?;
+        for (query, params) in &history {
+            tx.execute(query, rusqlite::params_from_iter(params))?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        if updates.settings.is_some() {
+            self.rebuild_value_index(&column)?;
+        }
+
+        Ok(column)
+    }
+
+    fn find_column(&self, id: i64) -> Result<Column, DatasetError> {
+        self.db
+            .query(
+                &format!("SELECT {} FROM columns WHERE id = ?", COLUMN_FIELDS),
+                [id],
+                column_from_row,
+            )?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Column with id {} not found", id)))
+    }
+
+    /// Checks the settings that only make sense for some column types
+    fn validate_column(&self, column: &Column) -> Result<(), DatasetError> {
+        let is_numeric = matches!(column.column_type.trim(), "INT" | "FLOAT");
+        if column.settings.distribution.is_some() && !is_numeric {
+            return Err(DatasetError::InvalidInput(format!(
+                "Distribution targets need an INT or FLOAT column, '{}' is {}",
+                column.name, column.column_type
+            )));
+        }
+
+        self.validate_reference(column)
+    }
+
+    /// `REFERENCE` columns must point at another dataset, and only at columns of that dataset
+    fn validate_reference(&self, column: &Column) -> Result<(), DatasetError> {
+        let is_reference = column.column_type.trim() == "REFERENCE";
+
+        let reference = match &column.settings.reference {
+            Some(reference) if is_reference => reference,
+            None if !is_reference => return Ok(()),
+            None => {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Reference column '{}' needs a referenced dataset",
+                    column.name
+                )))
+            }
+            Some(_) => {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Only REFERENCE columns can reference another dataset, '{}' is {}",
+                    column.name, column.column_type
+                )))
+            }
+        };
+
+        if reference.dataset_id == column.dataset_id {
+            return Err(DatasetError::InvalidInput(
+                "A dataset cannot reference itself".to_string(),
+            ));
+        }
+
+        self.find_by_id(reference.dataset_id)?;
+        let referenced_columns = self.get_columns(reference.dataset_id)?;
+
+        for column_id in [reference.column_id, reference.weight_column_id].into_iter().flatten() {
+            if !referenced_columns.iter().any(|c| c.id == Some(column_id)) {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Column {} does not belong to dataset {}",
+                    column_id, reference.dataset_id
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_column(&self, id: i64) -> Result<(), DatasetError> {
+        let column = self.find_column(id)?;
+        let operation = HistoryOperation::ColumnDelete {
+            values: self.column_cells(&column)?,
+            column: Box::new(column.clone()),
+        };
+
+        self.remove_column(&column, &Self::history_queries(column.dataset_id, &operation)?)
+    }
+
+    /// Value of `column` in every row holding a cell for it
+    fn column_cells(&self, column: &Column) -> Result<Vec<(i64, Option<String>)>, DatasetError> {
+        if !self.db.table_exists(&column.table_name)? {
+            return Ok(Vec::new());
+        }
+
+        let column_id = column.id.map(|id| id.to_string()).unwrap_or_default();
+        let cells = self
+            .get_all_rows(&column.table_name)?
+            .into_iter()
+            .filter_map(|row| {
+                row.data
+                    .iter()
+                    .find(|data| data.column_id == column_id)
+                    .map(|data| (row.id, data.value.clone()))
+            })
+            .collect();
+
+        Ok(cells)
+    }
+
+    /// Drops `column` and its cells, `history` is written in the same transaction
+    fn remove_column(&self, column: &Column, history: &[OwnedQuery]) -> Result<(), DatasetError> {
+        let id = column
+            .id
+            .ok_or_else(|| DatasetError::InvalidInput("Column must have an ID".to_string()))?;
+        let table_name = &column.table_name;
+        let layout = self.storage_layout(table_name)?;
+
+        let update_query = format!(
+            "UPDATE {} SET data = (
+                SELECT COALESCE(json_group_array(json(value)), '[]')
+                FROM json_each(data)
+                WHERE CAST(COALESCE(json_extract(value, '$.columnId'), json_extract(value, '$.column_id')) AS TEXT) != ?
+            )
+            WHERE EXISTS (
+                SELECT 1 FROM json_each(data)
+                WHERE CAST(COALESCE(json_extract(value, '$.columnId'), json_extract(value, '$.column_id')) AS TEXT) = ?
+            )",
+            table_name
+        );
+
+        let position_str = column.position.to_string();
+        let dataset_id_str = column.dataset_id.to_string();
+        let id_str = id.to_string();
+
+        let mut queries: Vec<(&str, Vec<&dyn rusqlite::ToSql>)> = vec![(
+            "UPDATE columns SET position = position - 1 WHERE position > ? AND dataset_id = ?",
+            vec![&position_str, &dataset_id_str],
+        )];
+        // Columnar rows lose the whole SQLite column below instead of being rewritten one by one
+        if layout == StorageLayout::Json {
+            queries.push((&update_query, vec![&id_str, &id_str]));
+        }
+        queries.extend([
+            (
+                "DELETE FROM columns WHERE id = ?",
+                vec![&id_str as &dyn rusqlite::ToSql],
+            ),
+            ("DELETE FROM column_values WHERE column_id = ?", vec![&id_str]),
+            ("DELETE FROM pii_flags WHERE column_id = ?", vec![&id_str]),
+            ("DELETE FROM cell_provenance WHERE column_id = ?", vec![&id_str]),
+        ]);
+        for (query, params) in history {
+            queries.push((
+                query,
+                params.iter().map(|param| param as &dyn rusqlite::ToSql).collect(),
+            ));
+        }
+
+        self.db.execute_transaction(
+            &queries
+                .iter()
+                .map(|(query, params)| (*query, params.as_slice()))
+                .collect::<Vec<_>>(),
+        )?;
+
+        if layout == StorageLayout::Columnar {
+            self.rebuild_columnar_view(
+                table_name,
+                &self.get_columns(column.dataset_id)?,
+                &format!(
+                    "ALTER TABLE {} DROP COLUMN {};",
+                    columnar_table(table_name),
+                    physical_column(id)
+                ),
+            )?;
+        }
+
+        if self.db.table_exists(&search_table(table_name))? {
+            self.rebuild_search_index(table_name)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn storage_layout(&self, table_name: &str) -> Result<StorageLayout, DatasetError> {
+        let kinds = self
+            .db
+            .query("SELECT type FROM sqlite_master WHERE name = ?", [table_name], |row| {
+                Ok(row.get::<_, String>(0)?)
+            })?;
+
+        Ok(match kinds.first().map(String::as_str) {
+            Some("view") => StorageLayout::Columnar,
+            _ => StorageLayout::Json,
+        })
+    }
+
+    /// Moves the rows of a dataset to one SQLite column per dataset column, keeping row ids and
+    /// timestamps. A dataset without rows table yet starts out columnar
+    pub fn convert_to_columnar(&self, dataset_id: i64) -> Result<StorageLayout, DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        if self.storage_layout(&table_name)? == StorageLayout::Columnar {
+            return Ok(StorageLayout::Columnar);
+        }
+
+        let columns = self.get_columns(dataset_id)?;
+        let has_rows_table = self.db.table_exists(&table_name)?;
+        let storage = columnar_table(&table_name);
+        self.db.validate_table_name(&storage)?;
+
+        let mut batch = create_columnar_table_sql(&table_name, &columns);
+
+        if has_rows_table {
+            batch.push_str(&copy_into_columnar_sql(
+                &table_name,
+                &columns,
+                &format!("{} t", table_name),
+            ));
+            batch.push_str(&format!("DROP TABLE {};", table_name));
+        }
+
+        batch.push_str(&create_columnar_view_sql(&table_name, &columns));
+
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(&batch)?;
+        tx.commit()?;
+        drop(conn);
+
+        self.ensure_search_index(&table_name)?;
+
+        Ok(StorageLayout::Columnar)
+    }
+
+    /// Storage rebuild of a columnar dataset whose column `before` becomes `after`, `None` when the cells of
+    /// the column keep their stored type
+    fn retype_storage_sql(&self, before: &Column, after: &Column) -> Result<Option<String>, DatasetError> {
+        if StoredType::of(before) == StoredType::of(after)
+            || self.storage_layout(&after.table_name)? != StorageLayout::Columnar
+        {
+            return Ok(None);
+        }
+
+        let columns = self
+            .get_columns(after.dataset_id)?
+            .into_iter()
+            .map(|column| if column.id == after.id { after.clone() } else { column })
+            .collect::<Vec<_>>();
+
+        Ok(Some(rebuild_columnar_storage_sql(&after.table_name, &columns)))
+    }
+
+    /// Runs `alteration` on the storage table of a columnar dataset while its view is down, SQLite refuses to
+    /// drop columns a view or trigger still refers to
+    fn rebuild_columnar_view(
+        &self,
+        table_name: &str,
+        columns: &[Column],
+        alteration: &str,
+    ) -> Result<(), DatasetError> {
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(&drop_columnar_view_sql(table_name))?;
+        tx.execute_batch(alteration)?;
+        tx.execute_batch(&create_columnar_view_sql(table_name, columns))?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Creates the full-text index of a rows table, filling it from the existing rows when it is new
+    pub fn ensure_search_index(&self, table_name: &str) -> Result<(), DatasetError> {
+        let search_table = search_table(table_name);
+        if self.db.table_exists(&search_table)? {
+            return Ok(());
+        }
+
+        self.db.validate_table_name(&search_table)?;
+        self.db.execute(
+            &format!(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS {} USING fts5(content, tokenize = 'unicode61 remove_diacritics 2')",
+                search_table
+            ),
+            [],
+        )?;
+
+        self.rebuild_search_index(table_name)
+    }
+
+    pub fn rebuild_search_index(&self, table_name: &str) -> Result<(), DatasetError> {
+        let search_table = search_table(table_name);
+
+        self.db.execute_transaction(&[
+            (&format!("DELETE FROM {}", search_table), &[]),
+            (
+                &format!(
+                    "INSERT INTO {} (rowid, content)
+                    SELECT t.id, COALESCE((
+                        SELECT group_concat(json_extract(item.value, '$.value'), char(10))
+                        FROM json_each(t.data) item
+                        WHERE TRIM(COALESCE(json_extract(item.value, '$.value'), '')) != ''
+                    ), '')
+                    FROM {} t",
+                    search_table, table_name
+                ),
+                &[],
+            ),
+        ])?;
+
+        Ok(())
+    }
+
+    /// Rows matching every term of `query` (as a prefix), best match first
+    pub fn search_rows(&self, dataset_id: i64, query: &str, limit: i64) -> Result<Vec<RowSearchHit>, DatasetError> {
+        if limit <= 0 {
+            return Err(DatasetError::InvalidInput(
+                "Limit must be a positive integer".to_string(),
+            ));
+        }
+
+        let Some(match_query) = fts_match_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        if !self.db.table_exists(&table_name)? {
+            return Ok(Vec::new());
+        }
+
+        self.ensure_search_index(&table_name)?;
+
+        let search_table = search_table(&table_name);
+        let hits = self.db.query(
+            &format!(
+                "SELECT rowid, bm25({0}), snippet({0}, 0, '<mark>', '</mark>', '…', 16)
+                FROM {0} WHERE {0} MATCH ? ORDER BY bm25({0}) ASC, rowid ASC LIMIT ?",
+                search_table
+            ),
+            rusqlite::params![match_query, limit],
+            |row| {
+                Ok(RowSearchHit {
+                    row_id: row.get::<_, i64>(0)?,
+                    score: -row.get::<_, f64>(1)?,
+                    snippet: row.get::<_, String>(2)?,
+                })
+            },
+        )?;
+
+        Ok(hits)
+    }
+
+    /// Text indexed for full-text search, one line per non-empty cell
+    fn search_content(row_data: &[RowData]) -> String {
+        row_data
+            .iter()
+            .map(|data| data.value_or_empty())
+            .filter(|value| !value.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn get_all_rows(&self, table_name: &str) -> Result<Vec<Row>, DatasetError> {
+        let rows = self.db.query(
+            &format!(
+                "SELECT id, data, created_at, updated_at FROM {} ORDER BY id ASC",
+                table_name
+            ),
+            [],
+            |row| {
+                let data_json: String = row.get(1)?;
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+
+                Ok(Row {
+                    id: row.get::<_, i64>(0)?,
+                    data: row_data.into_boxed_slice(),
+                    created_at: row.get::<_, String>(2)?,
+                    updated_at: row.get::<_, String>(3)?,
+                })
+            },
+        )?;
+
+        Ok(rows)
+    }
+
+    pub fn get_rows(&self, dataset_id: i64, page: i64, page_size: i64) -> Result<PaginatedResponse, DatasetError> {
+        self.query_rows(dataset_id, page, page_size, &RowQuery::default())
+    }
+
+    pub fn query_rows(
+        &self,
+        dataset_id: i64,
+        page: i64,
+        page_size: i64,
+        query: &RowQuery,
+    ) -> Result<PaginatedResponse, DatasetError> {
+        if page <= 0 {
+            return Err(DatasetError::InvalidInput(
+                "Page number must be a positive integer".to_string(),
+            ));
+        }
+
+        if page_size <= 0 {
+            return Err(DatasetError::InvalidInput(
+                "Page size must be a positive integer".to_string(),
+            ));
+        }
+
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        let table_exists = self.db.table_exists(&table_name)?;
+        if !table_exists {
+            return Ok(PaginatedResponse {
+                data: Vec::new(),
+                page,
+                page_size,
+                total_rows: 0,
+                total_pages: 0,
+                has_next: false,
+                has_previous: false,
+            });
+        }
+
+        let columns = self.get_columns(dataset_id)?;
+        let layout = self.storage_layout(&table_name)?;
+        let sql = query.to_sql(&columns, layout)?;
+
+        let total_rows = self
+            .db
+            .query(
+                &format!(
+                    "SELECT COUNT(*) FROM {} {}",
+                    rows_source_sql(layout, &table_name),
+                    sql.where_clause
+                ),
+                rusqlite::params_from_iter(&sql.where_params),
+                |row| Ok(row.get::<_, i64>(0)?),
+            )?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::DatabaseError("Failed to retrieve row count".to_string()))?;
+        let total_pages = ((total_rows as f64) / (page_size as f64)).ceil() as i64;
+
+        if total_pages > 0 && page > total_pages {
+            return Err(DatasetError::InvalidInput(format!(
+                "Page {} exceeds total pages {}",
+                page, total_pages
+            )));
+        }
+
+        let offset = (page - 1) * page_size;
+
+        let mut params = sql.where_params;
+        params.push(rusqlite::types::Value::Integer(page_size));
+        params.push(rusqlite::types::Value::Integer(offset));
+
+        let rows = match layout {
+            StorageLayout::Json => self.query_json_rows(&table_name, &sql.where_clause, &sql.order_by, &params)?,
+            StorageLayout::Columnar => {
+                self.query_columnar_rows(&table_name, &columns, &sql.where_clause, &sql.order_by, &params)?
+            }
+        };
+
+        Ok(PaginatedResponse {
+            data: rows,
+            page,
+            page_size,
+            total_rows,
+            total_pages,
+            has_next: page < total_pages,
+            has_previous: page > 1,
+        })
+    }
+
+    fn query_json_rows(
+        &self,
+        table_name: &str,
+        where_clause: &str,
+        order_by: &str,
+        params: &[rusqlite::types::Value],
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, DatasetError> {
+        let column_info = self
+            .db
+            .query(&format!("PRAGMA table_info({})", table_name), [], |row| {
+                Ok(row.get::<_, String>(1)?)
+            })?;
+
+        let rows = self.db.query(
+            &format!(
+                "SELECT t.* FROM {} {} {} LIMIT ? OFFSET ?",
+                rows_source_sql(StorageLayout::Json, table_name),
+                where_clause,
+                order_by
+            ),
+            rusqlite::params_from_iter(params),
+            |row| {
+                let mut map = HashMap::new();
+                for (i, column_name) in column_info.iter().enumerate() {
+                    let value: serde_json::Value = if column_name == "data" {
+                        if let Ok(text_val) = row.get::<_, String>(i) {
+                            serde_json::from_str(&text_val).unwrap_or(serde_json::Value::String(text_val))
+                        } else {
+                            serde_json::Value::Null
+                        }
+                    } else if let Ok(int_val) = row.get::<_, i64>(i) {
+                        serde_json::Value::String(int_val.to_string())
+                    } else if let Ok(text_val) = row.get::<_, String>(i) {
+                        serde_json::Value::String(text_val)
+                    } else {
+                        serde_json::Value::String("".to_string())
+                    };
+                    map.insert(column_name.clone(), value);
+                }
+                Ok(map)
+            },
+        )?;
+
+        Ok(rows)
+    }
+
+    /// Same shape as `query_json_rows`, the `data` array is built from the storage columns rather than
+    /// through the view
+    fn query_columnar_rows(
+        &self,
+        table_name: &str,
+        columns: &[Column],
+        where_clause: &str,
+        order_by: &str,
+        params: &[rusqlite::types::Value],
+    ) -> Result<Vec<HashMap<String, serde_json::Value>>, DatasetError> {
+        let rows = self.db.query(
+            &format!(
+                "{} {} {} LIMIT ? OFFSET ?",
+                columnar_read_sql(table_name, columns),
+                where_clause,
+                order_by
+            ),
+            rusqlite::params_from_iter(params),
+            |row| {
+                let mut data = Vec::with_capacity(columns.len());
+                for (i, column) in columns.iter().enumerate() {
+                    let value = row.get::<_, Option<String>>(3 + i)?;
+                    data.push(serde_json::json!({
+                        "columnId": column.id.unwrap_or_default().to_string(),
+                        "value": value,
+                    }));
+                }
+
+                Ok(HashMap::from([
+                    (
+                        "id".to_string(),
+                        serde_json::Value::String(row.get::<_, i64>(0)?.to_string()),
+                    ),
+                    ("data".to_string(), serde_json::Value::Array(data)),
+                    ("created_at".to_string(), serde_json::Value::String(row.get(1)?)),
+                    ("updated_at".to_string(), serde_json::Value::String(row.get(2)?)),
+                ]))
+            },
+        )?;
+
+        Ok(rows)
+    }
+
+    pub fn add_row(&self, dataset_id: i64, data: &Vec<RowData>) -> Result<Row, DatasetError> {
+        self.add_row_with_provenance(dataset_id, data, &HashMap::new())
+    }
+
+    /// Adds a generated row, its cells' provenance (keyed by column id) is written in the same transaction
+    pub fn add_row_with_provenance(
+        &self,
+        dataset_id: i64,
+        data: &[RowData],
+        provenance: &HashMap<String, CellProvenance>,
+    ) -> Result<Row, DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        let columns = self.get_columns(dataset_id)?;
+
+        let mut row_data = Vec::new();
+        for column in &columns {
+            let column_id = column
+                .id
+                .expect("Column should have an ID when retrieved from database");
+
+            let value = data
+                .iter()
+                .find(|r| r.column_id == column_id.to_string())
+                .map(|r| r.value.clone())
+                .ok_or_else(|| DatasetError::NotFound(format!("Column with id {} not found", column_id)))?;
+
+            row_data.push(RowData {
+                column_id: column_id.to_string(),
+                value,
+            });
+        }
+
+        Self::check_nullable(&columns, &row_data)?;
+        Self::check_stored_types(self.storage_layout(&table_name)?, &columns, &row_data)?;
+        self.check_unique_values(dataset_id, &columns, &row_data, None)?;
+
+        let json_data = serde_json::to_string(&row_data)?;
+        let indexed_values = Self::indexed_values(&columns, &row_data);
+        let search_content = Self::search_content(&row_data);
+
+        self.ensure_search_index(&table_name)?;
+
+        let insert_row_query = format!("INSERT INTO {} (data) VALUES (?)", table_name);
+        let insert_search_query = format!(
+            "INSERT INTO {} (rowid, content) VALUES ((SELECT MAX(id) FROM {}), ?)",
+            search_table(&table_name),
+            table_name
+        );
+        let insert_value_query = format!(
+            "INSERT INTO column_values (dataset_id, row_id, column_id, value, normalized_value)
+            VALUES (?, (SELECT MAX(id) FROM {}), ?, ?, ?)",
+            table_name
+        );
+
+        let mut queries: Vec<(&str, Vec<&dyn rusqlite::ToSql>)> = vec![
+            (&insert_row_query, vec![&json_data]),
+            (&insert_search_query, vec![&search_content]),
+            (
+                "UPDATE datasets_metadata SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                vec![&dataset_id],
+            ),
+        ];
+        for (column_id, value, normalized_value) in &indexed_values {
+            queries.push((
+                &insert_value_query,
+                vec![&dataset_id, column_id, value, normalized_value],
+            ));
+        }
+
+        let insert_provenance_query = format!(
+            "INSERT OR REPLACE INTO cell_provenance
+            (dataset_id, row_id, column_id, model_id, generation_id, prompt_hash, rules_hash, attempts, seed)
+            VALUES (?, (SELECT MAX(id) FROM {}), ?, ?, ?, ?, ?, ?, ?)",
+            table_name
+        );
+        let provenance = provenance
+            .iter()
+            .filter_map(|(column_id, cell)| Some((column_id.parse::<i64>().ok()?, cell, cell.attempts as i64)))
+            .collect::<Vec<_>>();
+        for (column_id, cell, attempts) in &provenance {
+            queries.push((
+                &insert_provenance_query,
+                vec![
+                    &dataset_id,
+                    column_id,
+                    &cell.model_id,
+                    &cell.generation_id,
+                    &cell.prompt_hash,
+                    &cell.rules_hash,
+                    attempts,
+                    &cell.seed,
+                ],
+            ));
+        }
+
+        self.db.execute_transaction(
+            &queries
+                .iter()
+                .map(|(query, params)| (*query, params.as_slice()))
+                .collect::<Vec<_>>(),
+        )?;
+
+        let row = self.db.query(
+            &format!(
+                "SELECT id, data, created_at, updated_at FROM {} ORDER BY id DESC LIMIT 1",
+                table_name
+            ),
+            [],
+            |row| {
+                let data_json: String = row.get(1)?;
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+                Ok(Row {
+                    id: row.get::<_, i64>(0)?,
+                    data: row_data.into_boxed_slice(),
+                    created_at: row.get::<_, String>(2)?,
+                    updated_at: row.get::<_, String>(3)?,
+                })
+            },
+        )?;
+
+        Ok(row
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Dataset not found")))?)
+    }
+
+    pub fn update_row(
+        &self,
+        dataset_id: i64,
+        row_id: i64,
+        updates: &HashMap<i64, Option<String>>,
+    ) -> Result<Row, DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        let rows = self.db.query(
+            &format!("SELECT data FROM {} WHERE id = ?", table_name),
+            [row_id],
+            |row| {
+                row.get::<_, String>(0)
+                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
+            },
+        )?;
+
+        if rows.is_empty() {
+            return Err(DatasetError::NotFound(format!("Row with id {} not found", row_id)));
+        }
+
+        let mut row_data: Vec<RowData> = serde_json::from_str(&rows[0])?;
+        let before = row_data.clone();
+
+        for data_item in &mut row_data {
+            if let Ok(column_id_i64) = data_item.column_id.parse::<i64>() {
+                if let Some(new_value) = updates.get(&column_id_i64) {
+                    data_item.value = new_value.clone();
+                }
+            }
+        }
+
+        let columns = self.get_columns(dataset_id)?;
+        Self::check_nullable(&columns, &row_data)?;
+        Self::check_stored_types(self.storage_layout(&table_name)?, &columns, &row_data)?;
+        self.check_unique_values(dataset_id, &columns, &row_data, Some(row_id))?;
+
+        let json_data = serde_json::to_string(&row_data)?;
+        let indexed_values = Self::indexed_values(&columns, &row_data);
+        let search_content = Self::search_content(&row_data);
+
+        self.ensure_search_index(&table_name)?;
+
+        let update_row_query = format!(
+            "UPDATE {} SET data = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            table_name
+        );
+        let update_search_query = format!("UPDATE {} SET content = ? WHERE rowid = ?", search_table(&table_name));
+
+        let mut queries: Vec<(&str, Vec<&dyn rusqlite::ToSql>)> = vec![
+            (&update_row_query, vec![&json_data, &row_id]),
+            (&update_search_query, vec![&search_content, &row_id]),
+            (
+                "UPDATE datasets_metadata SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                vec![&dataset_id],
+            ),
+            (
+                "DELETE FROM column_values WHERE dataset_id = ? AND row_id = ?",
+                vec![&dataset_id, &row_id],
+            ),
+            (
+                "DELETE FROM pii_flags WHERE dataset_id = ? AND row_id = ?",
+                vec![&dataset_id, &row_id],
+            ),
+        ];
+        for (column_id, value, normalized_value) in &indexed_values {
+            queries.push((
+                "INSERT INTO column_values (dataset_id, row_id, column_id, value, normalized_value) VALUES (?, ?, ?, ?, ?)",
+                vec![&dataset_id, &row_id, column_id, value, normalized_value],
+            ));
+        }
+        // Edited cells no longer come from the model that generated them
+        for column_id in updates.keys() {
+            queries.push((
+                "DELETE FROM cell_provenance WHERE dataset_id = ? AND row_id = ? AND column_id = ?",
+                vec![&dataset_id, &row_id, column_id],
+            ));
+        }
+        let history = Self::history_queries(
+            dataset_id,
+            &HistoryOperation::RowEdit {
+                rows: vec![RowChange {
+                    row_id,
+                    before,
+                    after: row_data.clone(),
+                }],
+            },
+        )?;
+        for (query, params) in &history {
+            queries.push((
+                query,
+                params.iter().map(|param| param as &dyn rusqlite::ToSql).collect(),
+            ));
+        }
+
+        self.db.execute_transaction(
+            &queries
+                .iter()
+                .map(|(query, params)| (*query, params.as_slice()))
+                .collect::<Vec<_>>(),
+        )?;
+
+        let row = self.db.query(
+            &format!(
+                "SELECT id, data, created_at, updated_at FROM {} WHERE id = ?",
+                table_name
+            ),
+            [row_id],
+            |row| {
+                let data_json: String = row.get(1)?;
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+
+                Ok(Row {
+                    id: row.get::<_, i64>(0)?,
+                    data: row_data.into_boxed_slice(),
+                    created_at: row.get::<_, String>(2)?,
+                    updated_at: row.get::<_, String>(3)?,
+                })
+            },
+        )?;
+
+        row.into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Row with id {} not found", row_id)))
+    }
+
+    pub fn delete_row(&self, dataset_id: i64, row_id: i64) -> Result<(), DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        let rows = self.rows_by_id(&table_name, &[row_id])?;
+        if rows.is_empty() {
+            return Err(DatasetError::NotFound(format!("Row with id {} not found", row_id)));
+        }
+
+        self.delete_recorded_rows(dataset_id, &table_name, rows)?;
+
+        Ok(())
+    }
+
+    /// Rows of `table_name` among `row_ids`, oldest first
+    fn rows_by_id(&self, table_name: &str, row_ids: &[i64]) -> Result<Vec<Row>, DatasetError> {
+        let rows = self.db.query(
+            &format!(
+                "SELECT id, data, created_at, updated_at FROM {} WHERE id IN (SELECT value FROM json_each(?)) ORDER BY id ASC",
+                table_name
+            ),
+            [serde_json::to_string(row_ids)?],
+            |row| {
+                let data_json: String = row.get(1)?;
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+
+                Ok(Row {
+                    id: row.get::<_, i64>(0)?,
+                    data: row_data.into_boxed_slice(),
+                    created_at: row.get::<_, String>(2)?,
+                    updated_at: row.get::<_, String>(3)?,
+                })
+            },
+        )?;
+
+        Ok(rows)
+    }
+
+    /// Deletes `rows` with their search entry and per-row state, recording them in the history
+    fn delete_recorded_rows(&self, dataset_id: i64, table_name: &str, rows: Vec<Row>) -> Result<usize, DatasetError> {
+        self.ensure_search_index(table_name)?;
+
+        let row_ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+        let mut queries = Self::row_delete_queries(dataset_id, table_name, &row_ids)?;
+        queries.push(Self::touch_dataset_query(dataset_id));
+        queries.extend(Self::history_queries(
+            dataset_id,
+            &HistoryOperation::RowDelete { rows },
+        )?);
+        self.execute_queries(&queries)?;
+
+        Ok(row_ids.len())
+    }
+
+    /// Ids of the rows in `selection`, oldest first
+    fn selected_row_ids(
+        &self,
+        dataset_id: i64,
+        table_name: &str,
+        selection: &RowSelection,
+    ) -> Result<Vec<i64>, DatasetError> {
+        if !self.db.table_exists(table_name)? {
+            return Ok(Vec::new());
+        }
+
+        let layout = self.storage_layout(table_name)?;
+        let (where_clause, where_params) = match selection {
+            RowSelection::Ids(row_ids) => (
+                "WHERE t.id IN (SELECT value FROM json_each(?))".to_string(),
+                vec![rusqlite::types::Value::Text(serde_json::to_string(row_ids)?)],
+            ),
+            RowSelection::Query(query) => {
+                let sql = query.to_sql(&self.get_columns(dataset_id)?, layout)?;
+                (sql.where_clause, sql.where_params)
+            }
+        };
+
+        let row_ids = self.db.query(
+            &format!(
+                "SELECT t.id FROM {} {} ORDER BY t.id ASC",
+                rows_source_sql(layout, table_name),
+                where_clause
+            ),
+            rusqlite::params_from_iter(&where_params),
+            |row| Ok(row.get::<_, i64>(0)?),
+        )?;
+
+        Ok(row_ids)
+    }
+
+    /// Deletes every row of `selection` in a single transaction, returns the number of deleted rows
+    pub fn delete_rows(&self, dataset_id: i64, selection: &RowSelection) -> Result<usize, DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        let row_ids = self.selected_row_ids(dataset_id, &table_name, selection)?;
+        if row_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let rows = self.rows_by_id(&table_name, &row_ids)?;
+        self.delete_recorded_rows(dataset_id, &table_name, rows)
+    }
+
+    /// Sets `column_id` to `value` in every row of `selection`, returns the number of updated rows
+    pub fn update_rows(
+        &self,
+        dataset_id: i64,
+        selection: &RowSelection,
+        column_id: i64,
+        value: Option<&str>,
+    ) -> Result<usize, DatasetError> {
+        self.edit_column(dataset_id, selection, column_id, |_| value.map(str::to_string))
+    }
+
+    /// Replaces every match of the regex `pattern` in the `column_id` cells of `selection`, `replacement` may
+    /// use `$1`/`${name}` groups. Null cells are left alone, returns the number of changed rows
+    pub fn replace_in_column(
+        &self,
+        dataset_id: i64,
+        selection: &RowSelection,
+        column_id: i64,
+        pattern: &str,
+        replacement: &str,
+    ) -> Result<usize, DatasetError> {
+        let regex = regex::Regex::new(pattern)
+            .map_err(|e| DatasetError::InvalidInput(format!("Invalid pattern '{}': {}", pattern, e)))?;
+
+        self.edit_column(dataset_id, selection, column_id, |value| {
+            value.map(|value| regex.replace_all(value, replacement).into_owned())
+        })
+    }
+
+    /// Rewrites the `column_id` cell of every row of `selection` with `edit`, all changed rows are saved in a
+    /// single transaction. Rows whose value does not change are not touched nor counted
+    fn edit_column<F>(
+        &self,
+        dataset_id: i64,
+        selection: &RowSelection,
+        column_id: i64,
+        mut edit: F,
+    ) -> Result<usize, DatasetError>
+    where
+        F: FnMut(Option<&str>) -> Option<String>,
+    {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        let columns = self.get_columns(dataset_id)?;
+        let column = columns
+            .iter()
+            .find(|column| column.id == Some(column_id))
+            .ok_or_else(|| DatasetError::InvalidInput(format!("Column {} not found in dataset", column_id)))?;
+
+        let row_ids = self.selected_row_ids(dataset_id, &table_name, selection)?;
+        if row_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let layout = self.storage_layout(&table_name)?;
+        let column_key = column_id.to_string();
+        let mut changes: Vec<RowChange> = Vec::new();
+        for row in self.rows_by_id(&table_name, &row_ids)? {
+            let before = row.data.into_vec();
+            let current = before.iter().find(|data| data.column_id == column_key);
+            let value = edit(current.and_then(|data| data.value.as_deref()));
+            if current.is_some_and(|data| data.value == value) {
+                continue;
+            }
+
+            let mut after = before.clone();
+            match after.iter_mut().find(|data| data.column_id == column_key) {
+                Some(data) => data.value = value,
+                None => after.push(RowData {
+                    column_id: column_key.clone(),
+                    value,
+                }),
+            }
+
+            Self::check_nullable(std::slice::from_ref(column), &after)?;
+            Self::check_stored_types(layout, std::slice::from_ref(column), &after)?;
+            changes.push(RowChange {
+                row_id: row.id,
+                before,
+                after,
+            });
+        }
+
+        if changes.is_empty() {
+            return Ok(0);
+        }
+
+        self.check_bulk_unique_values(dataset_id, column, &changes)?;
+        self.ensure_search_index(&table_name)?;
+
+        let edited = changes
+            .iter()
+            .map(|change| (change.row_id, change.after.clone()))
+            .collect::<Vec<_>>();
+        let mut queries = Self::row_write_queries(dataset_id, &table_name, &columns, &edited)?;
+        // Edited cells lose their flags and no longer come from the model that generated them
+        for (row_id, _) in &edited {
+            for table in ["pii_flags", "cell_provenance"] {
+                queries.push((
+                    format!(
+                        "DELETE FROM {} WHERE dataset_id = ? AND row_id = ? AND column_id = ?",
+                        table
+                    ),
+                    vec![dataset_id.into(), (*row_id).into(), column_id.into()],
+                ));
+            }
+        }
+        queries.push(Self::touch_dataset_query(dataset_id));
+        queries.extend(Self::history_queries(
+            dataset_id,
+            &HistoryOperation::RowEdit { rows: changes },
+        )?);
+        self.execute_queries(&queries)?;
+
+        Ok(edited.len())
+    }
+
+    /// Unique check for a batch of edited rows, against each other and the rows outside the batch
+    fn check_bulk_unique_values(
+        &self,
+        dataset_id: i64,
+        column: &Column,
+        changes: &[RowChange],
+    ) -> Result<(), DatasetError> {
+        let (Some(mode), Some(column_id)) = (column.settings.unique, column.id) else {
+            return Ok(());
+        };
+
+        let unique_key = |value: &str| match mode {
+            UniqueMode::Exact => value.trim().to_string(),
+            UniqueMode::Normalized => normalize_value(value),
+        };
+
+        let edited_ids: HashSet<i64> = changes.iter().map(|change| change.row_id).collect();
+        let mut taken: HashSet<String> = self
+            .db
+            .query(
+                "SELECT row_id, value FROM column_values WHERE dataset_id = ? AND column_id = ?",
+                [dataset_id, column_id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )?
+            .into_iter()
+            .filter(|(row_id, _)| !edited_ids.contains(row_id))
+            .map(|(_, value)| unique_key(&value))
+            .collect();
+
+        let column_key = column_id.to_string();
+        for change in changes {
+            // Like SQL, missing values never collide
+            let Some(value) = change
+                .after
+                .iter()
+                .find(|data| data.column_id == column_key)
+                .and_then(|data| data.value.as_deref())
+            else {
+                continue;
+            };
+
+            let key = unique_key(value);
+            if !key.is_empty() && !taken.insert(key) {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Value '{}' already exists in unique column '{}'",
+                    value, column.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recorded edits of a dataset, newest first
+    pub fn get_history(&self, dataset_id: i64) -> Result<Vec<HistoryEntry>, DatasetError> {
+        self.find_by_id(dataset_id)?;
+
+        let entries = self.db.query(
+            &format!(
+                "SELECT {} FROM dataset_history WHERE dataset_id = ? ORDER BY id DESC",
+                HISTORY_FIELDS
+            ),
+            [dataset_id],
+            history_entry_from_row,
+        )?;
+
+        Ok(entries)
+    }
+
+    /// Reverts the latest edit still in effect, `None` when there is nothing to undo
+    pub fn undo(&self, dataset_id: i64) -> Result<Option<HistoryEntry>, DatasetError> {
+        self.step_history(dataset_id, true)
+    }
+
+    /// Replays the earliest undone edit, `None` when there is nothing to redo
+    pub fn redo(&self, dataset_id: i64) -> Result<Option<HistoryEntry>, DatasetError> {
+        self.step_history(dataset_id, false)
+    }
+
+    fn step_history(&self, dataset_id: i64, undo: bool) -> Result<Option<HistoryEntry>, DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+
+        let entry = self
+            .db
+            .query(
+                &format!(
+                    "SELECT {} FROM dataset_history WHERE dataset_id = ? AND undone = ? ORDER BY id {} LIMIT 1",
+                    HISTORY_FIELDS,
+                    if undo { "DESC" } else { "ASC" }
+                ),
+                rusqlite::params![dataset_id, !undo],
+                history_entry_from_row,
+            )?
+            .into_iter()
+            .next();
+        let Some(entry) = entry else {
+            return Ok(None);
+        };
+
+        // Flipping `undone` shares the transaction of the write, a failed step can be retried
+        let marker: OwnedQuery = (
+            "UPDATE dataset_history SET undone = ? WHERE id = ?".to_string(),
+            vec![undo.into(), entry.id.into()],
+        );
+        self.apply_history(dataset_id, &dataset_metadata.table_name, &entry.operation, undo, marker)?;
+
+        Ok(Some(HistoryEntry { undone: undo, ..entry }))
+    }
+
+    /// Writes the state before (`undo`) or after the operation, without recording it again, and runs `marker` in
+    /// the same transaction. Provenance and PII flags dropped by the original edit are not brought back
+    fn apply_history(
+        &self,
+        dataset_id: i64,
+        table_name: &str,
+        operation: &HistoryOperation,
+        undo: bool,
+        marker: OwnedQuery,
+    ) -> Result<(), DatasetError> {
+        match operation {
+            HistoryOperation::RowEdit { rows } => {
+                self.ensure_search_index(table_name)?;
+
+                let rows = rows
+                    .iter()
+                    .map(|change| {
+                        let data = if undo { &change.before } else { &change.after };
+                        (change.row_id, data.clone())
+                    })
+                    .collect::<Vec<_>>();
+                let mut queries =
+                    Self::row_write_queries(dataset_id, table_name, &self.get_columns(dataset_id)?, &rows)?;
+                queries.push(Self::touch_dataset_query(dataset_id));
+                queries.push(marker);
+                self.execute_queries(&queries)
+            }
+            HistoryOperation::RowDelete { rows } => {
+                self.ensure_search_index(table_name)?;
+
+                let mut queries = if undo {
+                    let layout = self.storage_layout(table_name)?;
+                    Self::row_restore_queries(dataset_id, table_name, layout, &self.get_columns(dataset_id)?, rows)?
+                } else {
+                    Self::row_delete_queries(
+                        dataset_id,
+                        table_name,
+                        &rows.iter().map(|row| row.id).collect::<Vec<_>>(),
+                    )?
+                };
+                queries.push(Self::touch_dataset_query(dataset_id));
+                queries.push(marker);
+                self.execute_queries(&queries)
+            }
+            HistoryOperation::ColumnEdit { before, after } => {
+                let (current, column) = if undo { (after, before) } else { (before, after) };
+                let storage_rebuild = self.retype_storage_sql(current, column)?;
+
+                let mut conn = self
+                    .db
+                    .conn
+                    .lock()
+                    .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+                let tx = conn.transaction()?;
+                for (query, params) in [Self::column_write_query(column)?, marker] {
+                    tx.execute(&query, rusqlite::params_from_iter(params))?;
+                }
+                if let Some(batch) = &storage_rebuild {
+                    tx.execute_batch(batch)?;
+                }
+                tx.commit()?;
+                drop(conn);
+
+                self.rebuild_value_index(column)
+            }
+            HistoryOperation::ColumnDelete { column, values } if undo => self.restore_column(column, values, marker),
+            HistoryOperation::ColumnDelete { column, .. } => {
+                let id = column
+                    .id
+                    .ok_or_else(|| DatasetError::InvalidInput("Column must have an ID".to_string()))?;
+                self.remove_column(&self.find_column(id)?, &[marker])
+            }
+        }
+    }
+
+    /// Puts a deleted column back with its id, position and cells, rows added since it was deleted get the
+    /// value new columns are backfilled with. `marker` is written in the same transaction as the column
+    fn restore_column(
+        &self,
+        column: &Column,
+        values: &[(i64, Option<String>)],
+        marker: OwnedQuery,
+    ) -> Result<(), DatasetError> {
+        let column_id = column
+            .id
+            .ok_or_else(|| DatasetError::InvalidInput("Column must have an ID".to_string()))?;
+        let table_name = &column.table_name;
+        let layout = self.storage_layout(table_name)?;
+
+        let queries: Vec<OwnedQuery> = vec![
+            (
+                "UPDATE columns SET position = position + 1 WHERE position >= ? AND dataset_id = ?".to_string(),
+                vec![column.position.into(), column.dataset_id.into()],
+            ),
+            (
+                "INSERT INTO columns (id, dataset_id, table_name, name, column_type, column_type_details, rules, position, settings) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)".to_string(),
+                vec![
+                    column_id.into(),
+                    column.dataset_id.into(),
+                    column.table_name.clone().into(),
+                    column.name.clone().into(),
+                    column.column_type.clone().into(),
+                    column.column_type_details.clone().unwrap_or_default().into(),
+                    column.rules.clone().into(),
+                    column.position.into(),
+                    serde_json::to_string(&column.settings)?.into(),
+                ],
+            ),
+        ];
+
+        let mut cell_queries: Vec<OwnedQuery> = Vec::new();
+        let has_rows_table = self.db.table_exists(table_name)?;
+        if has_rows_table && layout == StorageLayout::Json {
+            let insert_cell = format!(
+                "UPDATE {} SET data = json_insert(data, '$[#]', json_object('columnId', ?, 'value', ?)) WHERE id = ?",
+                table_name
+            );
+            for (row_id, value) in values {
+                cell_queries.push((
+                    insert_cell.clone(),
+                    vec![column_id.to_string().into(), value.clone().into(), (*row_id).into()],
+                ));
+            }
+            cell_queries.push((
+                format!(
+                    "UPDATE {} SET data = json_insert(data, '$[#]', json_object('columnId', ?, 'value', ?))
+                    WHERE json_type(data) = 'array' AND data != '[]' AND NOT EXISTS (
+                        SELECT 1 FROM json_each(data)
+                        WHERE CAST(COALESCE(json_extract(value, '$.columnId'), json_extract(value, '$.column_id')) AS TEXT) = ?
+                    )",
+                    table_name
+                ),
+                vec![
+                    column_id.to_string().into(),
+                    (!column.settings.nullable).then(String::new).into(),
+                    column_id.to_string().into(),
+                ],
+            ));
+        }
+
+        if layout == StorageLayout::Columnar {
+            let update_cell = format!(
+                "UPDATE {} SET {} = {} WHERE id = ?2",
+                columnar_table(table_name),
+                physical_column(column_id),
+                StoredType::of(column).write_sql("?1")
+            );
+            for (row_id, value) in values {
+                cell_queries.push((update_cell.clone(), vec![value.clone().into(), (*row_id).into()]));
+            }
+        }
+        cell_queries.push(marker);
+
+        // The view lists the restored column, which is only inserted by the transaction below
+        let mut columns = self.get_columns(column.dataset_id)?;
+        for existing in columns
+            .iter_mut()
+            .filter(|existing| existing.position >= column.position)
+        {
+            existing.position += 1;
+        }
+        columns.push(column.clone());
+        columns.sort_by_key(|column| column.position);
+
+        // Like `convert_to_columnar`, the physical column, the view and the cells land together or not at all
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        for (query, params) in &queries {
+            tx.execute(query, rusqlite::params_from_iter(params))?;
+        }
+        if layout == StorageLayout::Columnar {
+            tx.execute_batch(&drop_columnar_view_sql(table_name))?;
+            tx.execute_batch(&add_physical_column_sql(table_name, column_id, column))?;
+            tx.execute_batch(&create_columnar_view_sql(table_name, &columns))?;
+        }
+        for (query, params) in &cell_queries {
+            tx.execute(query, rusqlite::params_from_iter(params))?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        self.rebuild_value_index(column)?;
+        if self.db.table_exists(&search_table(table_name))? {
+            self.rebuild_search_index(table_name)?;
+        }
+
+        Ok(())
+    }
+
+    fn execute_queries(&self, queries: &[OwnedQuery]) -> Result<(), DatasetError> {
+        let params = queries
+            .iter()
+            .map(|(_, params)| {
+                params
+                    .iter()
+                    .map(|param| param as &dyn rusqlite::ToSql)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        self.db.execute_transaction(
+            &queries
+                .iter()
+                .zip(&params)
+                .map(|((query, _), params)| (query.as_str(), params.as_slice()))
+                .collect::<Vec<_>>(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Records `operation` as the newest history entry, dropping the undone entries it replaces and the ones
+    /// past `HISTORY_LIMIT`
+    fn history_queries(dataset_id: i64, operation: &HistoryOperation) -> Result<Vec<OwnedQuery>, DatasetError> {
+        Ok(vec![
+            (
+                "DELETE FROM dataset_history WHERE dataset_id = ? AND undone = 1".to_string(),
+                vec![dataset_id.into()],
+            ),
+            (
+                "INSERT INTO dataset_history (dataset_id, operation) VALUES (?, ?)".to_string(),
+                vec![dataset_id.into(), serde_json::to_string(operation)?.into()],
+            ),
+            (
+                "DELETE FROM dataset_history WHERE dataset_id = ? AND id NOT IN (
+                    SELECT id FROM dataset_history WHERE dataset_id = ? ORDER BY id DESC LIMIT ?
+                )"
+                .to_string(),
+                vec![dataset_id.into(), dataset_id.into(), HISTORY_LIMIT.into()],
+            ),
+        ])
+    }
+
+    fn touch_dataset_query(dataset_id: i64) -> OwnedQuery {
+        (
+            "UPDATE datasets_metadata SET updated_at = CURRENT_TIMESTAMP WHERE id = ?".to_string(),
+            vec![dataset_id.into()],
+        )
+    }
+
+    /// Replaces the data of existing rows, keeping their search entry and indexed values in sync
+    fn row_write_queries(
+        dataset_id: i64,
+        table_name: &str,
+        columns: &[Column],
+        rows: &[(i64, Vec<RowData>)],
+    ) -> Result<Vec<OwnedQuery>, DatasetError> {
+        let mut queries = Vec::new();
+
+        for (row_id, row_data) in rows {
+            queries.push((
+                format!(
+                    "UPDATE {} SET data = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                    table_name
+                ),
+                vec![serde_json::to_string(row_data)?.into(), (*row_id).into()],
+            ));
+            queries.push((
+                format!("UPDATE {} SET content = ? WHERE rowid = ?", search_table(table_name)),
+                vec![Self::search_content(row_data).into(), (*row_id).into()],
+            ));
+            queries.extend(Self::value_index_queries(dataset_id, columns, *row_id, row_data));
+        }
+
+        Ok(queries)
+    }
+
+    /// Inserts deleted rows back with their ids and timestamps
+    fn row_restore_queries(
+        dataset_id: i64,
+        table_name: &str,
+        layout: StorageLayout,
+        columns: &[Column],
+        rows: &[Row],
+    ) -> Result<Vec<OwnedQuery>, DatasetError> {
+        let mut queries = Vec::new();
+
+        for row in rows {
+            let data = serde_json::to_string(&row.data)?;
+            match layout {
+                StorageLayout::Json => queries.push((
+                    format!(
+                        "INSERT INTO {} (id, data, created_at, updated_at) VALUES (?, ?, ?, ?)",
+                        table_name
+                    ),
+                    vec![
+                        row.id.into(),
+                        data.into(),
+                        row.created_at.clone().into(),
+                        row.updated_at.clone().into(),
+                    ],
+                )),
+                // The view insert trigger assigns new ids, the row is created in storage and filled through the view
+                StorageLayout::Columnar => queries.extend([
+                    (
+                        format!(
+                            "INSERT INTO {} (id, created_at, updated_at) VALUES (?, ?, ?)",
+                            columnar_table(table_name)
+                        ),
+                        vec![
+                            row.id.into(),
+                            row.created_at.clone().into(),
+                            row.updated_at.clone().into(),
+                        ],
+                    ),
+                    (
+                        format!("UPDATE {} SET data = ?, updated_at = ? WHERE id = ?", table_name),
+                        vec![data.into(), row.updated_at.clone().into(), row.id.into()],
+                    ),
+                ]),
+            }
+            queries.push((
+                format!(
+                    "INSERT INTO {} (rowid, content) VALUES (?, ?)",
+                    search_table(table_name)
+                ),
+                vec![row.id.into(), Self::search_content(&row.data).into()],
+            ));
+            queries.extend(Self::value_index_queries(dataset_id, columns, row.id, &row.data));
+        }
+
+        Ok(queries)
+    }
+
+    /// Removes rows with their search entry and per-row state
+    fn row_delete_queries(dataset_id: i64, table_name: &str, row_ids: &[i64]) -> Result<Vec<OwnedQuery>, DatasetError> {
+        let ids_json = serde_json::to_string(row_ids)?;
+
+        let mut queries: Vec<OwnedQuery> = vec![
+            (
+                format!(
+                    "DELETE FROM {} WHERE id IN (SELECT value FROM json_each(?))",
+                    table_name
+                ),
+                vec![ids_json.clone().into()],
+            ),
+            (
+                format!(
+                    "DELETE FROM {} WHERE rowid IN (SELECT value FROM json_each(?))",
+                    search_table(table_name)
+                ),
+                vec![ids_json.clone().into()],
+            ),
+        ];
+        for table in ROW_STATE_TABLES {
+            queries.push((
+                format!(
+                    "DELETE FROM {} WHERE dataset_id = ? AND row_id IN (SELECT value FROM json_each(?))",
+                    table
+                ),
+                vec![dataset_id.into(), ids_json.clone().into()],
+            ));
+        }
+
+        Ok(queries)
+    }
+
+    fn value_index_queries(dataset_id: i64, columns: &[Column], row_id: i64, row_data: &[RowData]) -> Vec<OwnedQuery> {
+        let mut queries = vec![(
+            "DELETE FROM column_values WHERE dataset_id = ? AND row_id = ?".to_string(),
+            vec![dataset_id.into(), row_id.into()],
+        )];
+
+        for (column_id, value, normalized_value) in Self::indexed_values(columns, row_data) {
+            queries.push((
+                "INSERT INTO column_values (dataset_id, row_id, column_id, value, normalized_value) VALUES (?, ?, ?, ?, ?)"
+                    .to_string(),
+                vec![
+                    dataset_id.into(),
+                    row_id.into(),
+                    column_id.into(),
+                    value.into(),
+                    normalized_value.into(),
+                ],
+            ));
+        }
+
+        queries
+    }
+
+    fn column_write_query(column: &Column) -> Result<OwnedQuery, DatasetError> {
+        let column_id = column
+            .id
+            .ok_or_else(|| DatasetError::InvalidInput("Column must have an ID".to_string()))?;
+
+        Ok((
+            "UPDATE columns SET name = ?, column_type = ?, column_type_details = ?, rules = ?, position = ?, settings = ?,
+            updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+                .to_string(),
+            vec![
+                column.name.clone().into(),
+                column.column_type.clone().into(),
+                column.column_type_details.clone().unwrap_or_default().into(),
+                column.rules.clone().into(),
+                column.position.into(),
+                serde_json::to_string(&column.settings)?.into(),
+                column_id.into(),
+            ],
+        ))
+    }
+
+    /// Copies the current columns and rows of a dataset into a new snapshot
+    pub fn create_snapshot(&self, dataset_id: i64, name: &str) -> Result<DatasetSnapshot, DatasetError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DatasetError::InvalidInput("Snapshot name cannot be empty".to_string()));
+        }
+
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+        let columns = serde_json::to_string(&self.get_columns(dataset_id)?)?;
+        let has_rows_table = self.db.table_exists(&table_name)?;
+
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO dataset_snapshots (dataset_id, name, columns) VALUES (?, ?, ?)",
+            rusqlite::params![dataset_id, name, columns],
+        )?;
+        let snapshot_id = tx.last_insert_rowid();
+        if has_rows_table {
+            let row_count = tx.execute(
+                &format!(
+                    "INSERT INTO dataset_snapshot_rows (snapshot_id, row_id, data, created_at, updated_at)
+                    SELECT ?, id, data, created_at, updated_at FROM {}",
+                    table_name
+                ),
+                [snapshot_id],
+            )?;
+            tx.execute(
+                "UPDATE dataset_snapshots SET row_count = ? WHERE id = ?",
+                rusqlite::params![row_count as i64, snapshot_id],
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        self.find_snapshot(snapshot_id)
+    }
+
+    /// Snapshots of a dataset, newest first
+    pub fn list_snapshots(&self, dataset_id: i64) -> Result<Vec<DatasetSnapshot>, DatasetError> {
+        self.find_by_id(dataset_id)?;
+
+        let snapshots = self.db.query(
+            &format!(
+                "SELECT {} FROM dataset_snapshots WHERE dataset_id = ? ORDER BY id DESC",
+                SNAPSHOT_FIELDS
+            ),
+            [dataset_id],
+            snapshot_from_row,
+        )?;
+
+        Ok(snapshots)
+    }
+
+    pub fn find_snapshot(&self, snapshot_id: i64) -> Result<DatasetSnapshot, DatasetError> {
+        self.db
+            .query(
+                &format!("SELECT {} FROM dataset_snapshots WHERE id = ?", SNAPSHOT_FIELDS),
+                [snapshot_id],
+                snapshot_from_row,
+            )?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Snapshot with id {} not found", snapshot_id)))
+    }
+
+    pub fn delete_snapshot(&self, snapshot_id: i64) -> Result<(), DatasetError> {
+        self.find_snapshot(snapshot_id)?;
+        self.db
+            .execute("DELETE FROM dataset_snapshots WHERE id = ?", [snapshot_id])?;
+
+        Ok(())
+    }
+
+    fn snapshot_rows(&self, snapshot_id: i64) -> Result<Vec<Row>, DatasetError> {
+        let rows = self.db.query(
+            "SELECT row_id, data, created_at, updated_at FROM dataset_snapshot_rows WHERE snapshot_id = ? ORDER BY row_id ASC",
+            [snapshot_id],
+            |row| {
+                let data_json: String = row.get(1)?;
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+
+                Ok(Row {
+                    id: row.get::<_, i64>(0)?,
+                    data: row_data.into_boxed_slice(),
+                    created_at: row.get::<_, String>(2)?,
+                    updated_at: row.get::<_, String>(3)?,
+                })
+            },
+        )?;
+
+        Ok(rows)
+    }
+
+    /// Changes from `snapshot_id` to `other_snapshot_id`, or to the current dataset when `None`
+    pub fn diff_snapshot(
+        &self,
+        snapshot_id: i64,
+        other_snapshot_id: Option<i64>,
+    ) -> Result<SnapshotDiff, DatasetError> {
+        let snapshot = self.find_snapshot(snapshot_id)?;
+        let from_rows = self.snapshot_rows(snapshot_id)?;
+
+        let (to_columns, to_rows) = match other_snapshot_id {
+            Some(other_snapshot_id) => {
+                let other = self.find_snapshot(other_snapshot_id)?;
+                if other.dataset_id != snapshot.dataset_id {
+                    return Err(DatasetError::InvalidInput(
+                        "Snapshots belong to different datasets".to_string(),
+                    ));
+                }
+                (other.columns, self.snapshot_rows(other_snapshot_id)?)
+            }
+            None => {
+                let table_name = self.find_by_id(snapshot.dataset_id)?.table_name;
+                let rows = if self.db.table_exists(&table_name)? {
+                    self.get_all_rows(&table_name)?
+                } else {
+                    Vec::new()
+                };
+                (self.get_columns(snapshot.dataset_id)?, rows)
+            }
+        };
+
+        Ok(SnapshotDiff::between(
+            &snapshot.columns,
+            &from_rows,
+            &to_columns,
+            &to_rows,
+        ))
+    }
+
+    /// Brings the columns and rows of the dataset back to `snapshot_id`, keeping its storage layout. Rows and
+    /// columns get their snapshot ids back; per-row state survives only on rows left unchanged and the undo
+    /// history is cleared
+    pub fn restore_snapshot(&self, snapshot_id: i64) -> Result<(), DatasetError> {
+        let snapshot = self.find_snapshot(snapshot_id)?;
+        let dataset_id = snapshot.dataset_id;
+        let table_name = self.find_by_id(dataset_id)?.table_name;
+
+        if !self.db.table_exists(&table_name)? {
+            self.db
+                .create_table(&table_name, &["data JSON DEFAULT '{}' CHECK(json_valid(data))"], &[])?;
+        }
+        let layout = self.storage_layout(&table_name)?;
+
+        let mut queries: Vec<OwnedQuery> = Vec::new();
+        for table in ROW_STATE_TABLES {
+            queries.push((
+                format!(
+                    "DELETE FROM {} WHERE dataset_id = ? AND row_id NOT IN (
+                        SELECT s.row_id FROM dataset_snapshot_rows s JOIN {} t ON t.id = s.row_id AND t.data = s.data
+                        WHERE s.snapshot_id = ?
+                    )",
+                    table, table_name
+                ),
+                vec![dataset_id.into(), snapshot_id.into()],
+            ));
+        }
+        // The value index is rebuilt once the columns are back
+        queries.push((
+            "DELETE FROM column_values WHERE dataset_id = ?".to_string(),
+            vec![dataset_id.into()],
+        ));
+        queries.push((
+            "DELETE FROM columns WHERE dataset_id = ?".to_string(),
+            vec![dataset_id.into()],
+        ));
+        for column in &snapshot.columns {
+            queries.push((
+                "INSERT INTO columns (id, dataset_id, table_name, name, column_type, column_type_details, rules, position, settings) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)".to_string(),
+                vec![
+                    column.id.into(),
+                    dataset_id.into(),
+                    table_name.clone().into(),
+                    column.name.clone().into(),
+                    column.column_type.clone().into(),
+                    column.column_type_details.clone().unwrap_or_default().into(),
+                    column.rules.clone().into(),
+                    column.position.into(),
+                    serde_json::to_string(&column.settings)?.into(),
+                ],
+            ));
+        }
+        if layout == StorageLayout::Json {
+            queries.push((format!("DELETE FROM {}", table_name), Vec::new()));
+            queries.push((
+                format!(
+                    "INSERT INTO {} (id, data, created_at, updated_at)
+                    SELECT row_id, data, created_at, updated_at FROM dataset_snapshot_rows WHERE snapshot_id = ?",
+                    table_name
+                ),
+                vec![snapshot_id.into()],
+            ));
+        }
+        queries.push((
+            "DELETE FROM dataset_history WHERE dataset_id = ?".to_string(),
+            vec![dataset_id.into()],
+        ));
+        queries.push(Self::touch_dataset_query(dataset_id));
+
+        // Columnar storage is rebuilt from scratch, the snapshot may not have the same columns
+        let storage_batch = (layout == StorageLayout::Columnar).then(|| {
+            format!(
+                "{}DROP TABLE {};{}{}{}",
+                drop_columnar_view_sql(&table_name),
+                columnar_table(&table_name),
+                create_columnar_table_sql(&table_name, &snapshot.columns),
+                copy_into_columnar_sql(
+                    &table_name,
+                    &snapshot.columns,
+                    &format!(
+                        "(SELECT row_id AS id, data, created_at, updated_at FROM dataset_snapshot_rows WHERE snapshot_id = {}) t",
+                        snapshot_id
+                    ),
+                ),
+                create_columnar_view_sql(&table_name, &snapshot.columns)
+            )
+        });
+
+        let mut conn = self
+            .db
+            .conn
+            .lock()
+            .map_err(|_| DatasetError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+        let tx = conn.transaction()?;
+        for (query, params) in &queries {
+            tx.execute(query, rusqlite::params_from_iter(params))?;
+        }
+        if let Some(batch) = &storage_batch {
+            tx.execute_batch(batch)?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        for column in &snapshot.columns {
+            self.rebuild_value_index(column)?;
+        }
+        if self.db.table_exists(&search_table(&table_name))? {
+            self.rebuild_search_index(&table_name)
+        } else {
+            self.ensure_search_index(&table_name)
+        }
+    }
+
+    /// Links `child_dataset_id` to its parent, replacing any previous parent
+    pub fn set_relation(&self, relation: &DatasetRelation) -> Result<DatasetRelation, DatasetError> {
+        if relation.min_children > relation.max_children {
+            return Err(DatasetError::InvalidInput(format!(
+                "Min children ({}) cannot exceed max children ({})",
+                relation.min_children, relation.max_children
+            )));
+        }
+
+        if relation.parent_dataset_id == relation.child_dataset_id {
+            return Err(DatasetError::InvalidInput(
+                "A dataset cannot be its own parent".to_string(),
+            ));
+        }
+
+        self.find_by_id(relation.parent_dataset_id)?;
+        self.find_by_id(relation.child_dataset_id)?;
+
+        let mut ancestor = self.get_parent_relation(relation.parent_dataset_id)?;
+        while let Some(parent) = ancestor {
+            if parent.parent_dataset_id == relation.child_dataset_id {
+                return Err(DatasetError::InvalidInput(
+                    "Dataset relations cannot form a cycle".to_string(),
+                ));
+            }
+            ancestor = self.get_parent_relation(parent.parent_dataset_id)?;
+        }
+
+        let min_children = relation.min_children as i64;
+        let max_children = relation.max_children as i64;
+
+        self.db.execute(
+            "INSERT INTO dataset_relations (child_dataset_id, parent_dataset_id, min_children, max_children)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(child_dataset_id) DO UPDATE SET
+                parent_dataset_id = excluded.parent_dataset_id,
+                min_children = excluded.min_children,
+                max_children = excluded.max_children",
+            [
+                relation.child_dataset_id,
+                relation.parent_dataset_id,
+                min_children,
+                max_children,
+            ],
+        )?;
+
+        Ok(relation.clone())
+    }
+
+    pub fn remove_relation(&self, child_dataset_id: i64) -> Result<(), DatasetError> {
+        self.db.execute(
+            "DELETE FROM dataset_relations WHERE child_dataset_id = ?",
+            [child_dataset_id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_parent_relation(&self, child_dataset_id: i64) -> Result<Option<DatasetRelation>, DatasetError> {
+        let relations = self.query_relations("child_dataset_id = ?", child_dataset_id)?;
+        Ok(relations.into_iter().next())
+    }
+
+    pub fn get_child_relations(&self, parent_dataset_id: i64) -> Result<Vec<DatasetRelation>, DatasetError> {
+        self.query_relations("parent_dataset_id = ?", parent_dataset_id)
+    }
+
+    /// Relations where `dataset_id` is either the parent or the child
+    pub fn get_relations(&self, dataset_id: i64) -> Result<Vec<DatasetRelation>, DatasetError> {
+        let mut relations: Vec<DatasetRelation> = self.get_parent_relation(dataset_id)?.into_iter().collect();
+        relations.extend(self.get_child_relations(dataset_id)?);
+        Ok(relations)
+    }
+
+    fn query_relations(&self, filter: &str, dataset_id: i64) -> Result<Vec<DatasetRelation>, DatasetError> {
+        let relations = self.db.query(
+            &format!(
+                "SELECT parent_dataset_id, child_dataset_id, min_children, max_children
+                FROM dataset_relations WHERE {} ORDER BY child_dataset_id ASC",
+                filter
+            ),
+            [dataset_id],
+            |row| {
+                Ok(DatasetRelation {
+                    parent_dataset_id: row.get::<_, i64>(0)?,
+                    child_dataset_id: row.get::<_, i64>(1)?,
+                    min_children: row.get::<_, i64>(2)? as usize,
+                    max_children: row.get::<_, i64>(3)? as usize,
+                })
+            },
+        )?;
+
+        Ok(relations)
+    }
+
+    /// Records a flag for every cell of a generated row matching a PII pattern, unless the dataset
+    /// policy is off. Returns the number of flags recorded.
+    pub fn flag_pii(&self, dataset_id: i64, row: &Row) -> Result<usize, DatasetError> {
+        if self.get_settings(dataset_id)?.pii_policy == PiiPolicy::Off {
+            return Ok(0);
+        }
+
+        let flags: Vec<(i64, &'static str, String)> = row
+            .data
+            .iter()
+            .filter_map(|row_data| Some((row_data.column_id.parse::<i64>().ok()?, row_data.value.as_deref()?)))
+            .flat_map(|(column_id, value)| {
+                scan_pii(value)
+                    .into_iter()
+                    .map(move |found| (column_id, found.kind.as_str(), found.value))
+            })
+            .collect();
+
+        for (column_id, kind, value) in &flags {
+            self.db.execute(
+                "INSERT INTO pii_flags (dataset_id, row_id, column_id, kind, value) VALUES (?, ?, ?, ?, ?)",
+                rusqlite::params![dataset_id, row.id, column_id, kind, value],
+            )?;
+        }
+
+        Ok(flags.len())
+    }
+
+    pub fn get_pii_report(&self, dataset_id: i64) -> Result<PiiReport, DatasetError> {
+        self.find_by_id(dataset_id)?;
+
+        let flags = self.db.query(
+            "SELECT id, row_id, column_id, kind, value, created_at
+            FROM pii_flags WHERE dataset_id = ? ORDER BY row_id ASC, id ASC",
+            [dataset_id],
+            |row| {
+                let Some(kind) = PiiKind::from_name(&row.get::<_, String>(3)?) else {
+                    return Ok(None);
+                };
+
+                Ok(Some(PiiFlag {
+                    id: row.get(0)?,
+                    row_id: row.get(1)?,
+                    column_id: row.get(2)?,
+                    kind,
+                    value: row.get(4)?,
+                    created_at: row.get(5)?,
+                }))
+            },
+        )?;
+        let flags: Vec<PiiFlag> = flags.into_iter().flatten().collect();
+
+        let mut by_kind = HashMap::new();
+        for flag in &flags {
+            *by_kind.entry(flag.kind).or_insert(0) += 1;
+        }
+
+        let mut row_ids: Vec<i64> = flags.iter().map(|flag| flag.row_id).collect();
+        row_ids.dedup();
+
+        Ok(PiiReport {
+            dataset_id,
+            total_flags: flags.len(),
+            flagged_rows: row_ids.len(),
+            by_kind,
+            flags,
+        })
+    }
+
+    pub fn get_cell_provenance(
+        &self,
+        dataset_id: i64,
+        row_id: i64,
+        column_id: i64,
+    ) -> Result<Option<CellProvenance>, DatasetError> {
+        let provenance = self.db.query(
+            "SELECT model_id, generation_id, prompt_hash, rules_hash, attempts, seed, created_at
+            FROM cell_provenance WHERE dataset_id = ? AND row_id = ? AND column_id = ?",
+            [dataset_id, row_id, column_id],
+            |row| {
+                Ok(CellProvenance {
+                    model_id: row.get(0)?,
+                    generation_id: row.get(1)?,
+                    prompt_hash: row.get(2)?,
+                    rules_hash: row.get(3)?,
+                    attempts: row.get::<_, i64>(4)? as usize,
+                    seed: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            },
+        )?;
+
+        Ok(provenance.into_iter().next())
+    }
+
+    /// Ids of the rows with at least one cell matching `filter`, e.g. to regenerate what a bad model produced
+    pub fn find_rows_by_provenance(
+        &self,
+        dataset_id: i64,
+        filter: &ProvenanceFilter,
+    ) -> Result<Vec<i64>, DatasetError> {
+        let mut conditions = vec!["dataset_id = ?"];
+        let mut params: Vec<&dyn rusqlite::ToSql> = vec![&dataset_id];
+
+        if let Some(column_id) = &filter.column_id {
+            conditions.push("column_id = ?");
+            params.push(column_id);
+        }
+        if let Some(model_id) = &filter.model_id {
+            conditions.push("model_id = ?");
+            params.push(model_id);
+        }
+        if let Some(generation_id) = &filter.generation_id {
+            conditions.push("generation_id = ?");
+            params.push(generation_id);
+        }
+        if let Some(rules_hash) = &filter.rules_hash {
+            conditions.push("rules_hash = ?");
+            params.push(rules_hash);
+        }
+
+        let row_ids = self.db.query(
+            &format!(
+                "SELECT DISTINCT row_id FROM cell_provenance WHERE {} ORDER BY row_id ASC",
+                conditions.join(" AND ")
+            ),
+            params.as_slice(),
+            |row| Ok(row.get::<_, i64>(0)?),
+        )?;
+
+        Ok(row_ids)
+    }
+
+    pub fn pin_row(&self, dataset_id: i64, row_id: i64) -> Result<(), DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+
+        let rows = self.db.query(
+            &format!("SELECT id FROM {} WHERE id = ?", dataset_metadata.table_name),
+            [row_id],
+            |row| Ok(row.get::<_, i64>(0)?),
+        )?;
+
+        if rows.is_empty() {
+            return Err(DatasetError::NotFound(format!("Row with id {} not found", row_id)));
+        }
+
+        self.db.execute(
+            "INSERT OR IGNORE INTO pinned_rows (dataset_id, row_id) VALUES (?, ?)",
+            [dataset_id, row_id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn unpin_row(&self, dataset_id: i64, row_id: i64) -> Result<(), DatasetError> {
+        self.db.execute(
+            "DELETE FROM pinned_rows WHERE dataset_id = ? AND row_id = ?",
+            [dataset_id, row_id],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_pinned_row_ids(&self, dataset_id: i64) -> Result<Vec<i64>, DatasetError> {
+        let row_ids = self.db.query(
+            "SELECT row_id FROM pinned_rows WHERE dataset_id = ? ORDER BY row_id ASC",
+            [dataset_id],
+            |row| Ok(row.get::<_, i64>(0)?),
+        )?;
+
+        Ok(row_ids)
+    }
+
+    /// Values already stored for `column_id`, picked with the column's few-shot strategy
+    pub fn get_example_values(
+        &self,
+        dataset_id: i64,
+        column_id: i64,
+        few_shot: &FewShotSettings,
+    ) -> Result<Vec<String>, DatasetError> {
+        if few_shot.count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        if !self.db.table_exists(&table_name)? {
+            return Ok(Vec::new());
+        }
+
+        let (row_filter, order_by) = match few_shot.strategy {
+            FewShotStrategy::Random => ("", "RANDOM()"),
+            FewShotStrategy::MostRecent => ("", "t.id DESC"),
+            FewShotStrategy::Pinned => (
+                "AND t.id IN (SELECT row_id FROM pinned_rows WHERE dataset_id = ?3)",
+                "t.id DESC",
+            ),
+        };
+
+        let query = format!(
+            "SELECT CAST(json_extract(item.value, '$.value') AS TEXT)
+            FROM {} t, json_each(t.data) item
+            WHERE CAST(COALESCE(json_extract(item.value, '$.columnId'), json_extract(item.value, '$.column_id')) AS TEXT) = ?1
+                AND COALESCE(json_extract(item.value, '$.value'), '') != ''
+                {}
+            ORDER BY {}
+            LIMIT ?2",
+            table_name, row_filter, order_by
+        );
+
+        let column_id = column_id.to_string();
+        let limit = few_shot.count as i64;
+
+        let values = if few_shot.strategy == FewShotStrategy::Pinned {
+            self.db
+                .query(&query, rusqlite::params![column_id, limit, dataset_id], |row| {
+                    Ok(row.get::<_, String>(0)?)
+                })?
+        } else {
+            self.db.query(&query, rusqlite::params![column_id, limit], |row| {
+                Ok(row.get::<_, String>(0)?)
+            })?
+        };
+
+        Ok(values)
+    }
+
+    /// Whether `value` is already stored in `column_id`, ignoring `exclude_row_id`
+    pub fn value_exists(
+        &self,
+        dataset_id: i64,
+        column_id: i64,
+        value: &str,
+        mode: UniqueMode,
+        exclude_row_id: Option<i64>,
+    ) -> Result<bool, DatasetError> {
+        let (field, lookup) = match mode {
+            UniqueMode::Exact => ("value", value.trim().to_string()),
+            UniqueMode::Normalized => ("normalized_value", normalize_value(value)),
+        };
+
+        if lookup.is_empty() {
+            return Ok(false);
+        }
+
+        let query = format!(
+            "SELECT COUNT(*) FROM column_values
+            WHERE dataset_id = ? AND column_id = ? AND {} = ? AND row_id != ?",
+            field
+        );
+
+        let counts = self.db.query(
+            &query,
+            rusqlite::params![dataset_id, column_id, lookup, exclude_row_id.unwrap_or(0)],
+            |row| Ok(row.get::<_, i64>(0)?),
+        )?;
+
+        Ok(counts.into_iter().next().unwrap_or(0) > 0)
+    }
+
+    /// Latest distinct values of `column_id`, newest first
+    pub fn get_recent_values(
+        &self,
+        dataset_id: i64,
+        column_id: i64,
+        limit: usize,
+    ) -> Result<Vec<String>, DatasetError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let values = self.db.query(
+            "SELECT value FROM column_values
+            WHERE dataset_id = ? AND column_id = ?
+            GROUP BY value
+            ORDER BY MAX(row_id) DESC
+            LIMIT ?",
+            rusqlite::params![dataset_id, column_id, limit as i64],
+            |row| Ok(row.get::<_, String>(0)?),
+        )?;
+
+        Ok(values)
+    }
+
+    pub fn rebuild_value_index(&self, column: &Column) -> Result<(), DatasetError> {
+        let column_id = column
+            .id
+            .ok_or_else(|| DatasetError::InvalidInput("Column must have an ID".to_string()))?;
+
+        self.db
+            .execute("DELETE FROM column_values WHERE column_id = ?", [column_id])?;
+
+        if !column.settings.is_indexed() || !self.db.table_exists(&column.table_name)? {
+            return Ok(());
+        }
+
+        let column_id_str = column_id.to_string();
+        let params: Vec<(i64, i64, i64, String, String)> = self
+            .get_all_rows(&column.table_name)?
+            .into_iter()
+            .filter_map(|row| {
+                row.data
+                    .iter()
+                    .find(|data| data.column_id == column_id_str && !data.value_or_empty().trim().is_empty())
+                    .map(|data| {
+                        (
+                            column.dataset_id,
+                            row.id,
+                            column_id,
+                            data.value_or_empty().trim().to_string(),
+                            normalize_value(data.value_or_empty()),
+                        )
+                    })
+            })
+            .collect();
+
+        self.db.execute_batch(
+            "INSERT OR REPLACE INTO column_values (dataset_id, row_id, column_id, value, normalized_value) VALUES (?, ?, ?, ?, ?)",
+            &params,
+        )?;
+
+        Ok(())
+    }
+
+    fn check_nullable(columns: &[Column], row_data: &[RowData]) -> Result<(), DatasetError> {
+        for column in columns.iter().filter(|column| !column.settings.nullable) {
+            let Some(column_id) = column.id.map(|id| id.to_string()) else {
+                continue;
+            };
+
+            if row_data
+                .iter()
+                .any(|data| data.column_id == column_id && data.value.is_none())
+            {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Column '{}' is not nullable",
+                    column.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Columnar datasets store `INT`, `FLOAT` and `BOOL` cells typed, a value that doesn't parse is refused
+    fn check_stored_types(layout: StorageLayout, columns: &[Column], row_data: &[RowData]) -> Result<(), DatasetError> {
+        if layout != StorageLayout::Columnar {
+            return Ok(());
+        }
+
+        for column in columns {
+            let Some(column_id) = column.id.map(|id| id.to_string()) else {
+                continue;
+            };
+
+            let stored_type = StoredType::of(column);
+            if let Some(value) = row_data
+                .iter()
+                .filter(|data| data.column_id == column_id)
+                .filter_map(|data| data.value.as_deref())
+                .find(|value| !stored_type.accepts(value))
+            {
+                return Err(DatasetError::InvalidInput(format!(
+                    "'{}' is not a valid {} value for column '{}'",
+                    value,
+                    column.column_type.trim(),
+                    column.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_unique_values(
+        &self,
+        dataset_id: i64,
+        columns: &[Column],
+        row_data: &[RowData],
+        exclude_row_id: Option<i64>,
+    ) -> Result<(), DatasetError> {
+        for column in columns {
+            let (Some(mode), Some(column_id)) = (column.settings.unique, column.id) else {
+                continue;
+            };
+
+            // Like SQL, missing values never collide
+            let Some(value) = row_data
+                .iter()
+                .find(|data| data.column_id == column_id.to_string())
+                .and_then(|data| data.value.as_deref())
+            else {
+                continue;
+            };
+
+            if self.value_exists(dataset_id, column_id, value, mode, exclude_row_id)? {
+                return Err(DatasetError::InvalidInput(format!(
+                    "Value '{}' already exists in unique column '{}'",
+                    value, column.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn indexed_values(columns: &[Column], row_data: &[RowData]) -> Vec<(i64, String, String)> {
+        columns
+            .iter()
+            .filter(|column| column.settings.is_indexed())
+            .filter_map(|column| {
+                let column_id = column.id?;
+                row_data
+                    .iter()
+                    .find(|data| data.column_id == column_id.to_string() && !data.value_or_empty().trim().is_empty())
+                    .map(|data| {
+                        (
+                            column_id,
+                            data.value_or_empty().trim().to_string(),
+                            normalize_value(data.value_or_empty()),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Every value of `column_id` that parses as a number, oldest row first
+    pub fn get_numeric_values(&self, dataset_id: i64, column_id: i64) -> Result<Vec<f64>, DatasetError> {
+        let dataset_metadata = self.find_by_id(dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+
+        if !self.db.table_exists(&table_name)? {
+            return Ok(Vec::new());
+        }
+
+        let layout = self.storage_layout(&table_name)?;
+        let column = self.find_column(column_id)?;
+        let values = self.db.query(
+            &format!(
+                "SELECT CAST({} AS TEXT) FROM {} ORDER BY t.id ASC",
+                cell_value_sql(layout, &column),
+                rows_source_sql(layout, &table_name)
+            ),
+            [],
+            |row| Ok(row.get::<_, Option<String>>(0)?),
+        )?;
+
+        Ok(values
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite())
+            .collect())
+    }
+
+    pub fn get_distribution_report(&self, dataset_id: i64) -> Result<Vec<DistributionReport>, DatasetError> {
+        let mut reports = Vec::new();
+
+        for column in self.get_columns(dataset_id)? {
+            let (Some(target), Some(column_id)) = (&column.settings.distribution, column.id) else {
+                continue;
+            };
+
+            let values = self.get_numeric_values(dataset_id, column_id)?;
+            reports.push(DistributionReport::from_values(
+                column_id,
+                &column.name,
+                target,
+                &values,
+            ));
+        }
+
+        Ok(reports)
+    }
+
+    /// Picks a row of the referenced dataset, weighted by `weight_column_id` when set
+    pub fn sample_reference(&self, reference: &ReferenceSettings) -> Result<SampledReference, DatasetError> {
+        let dataset_metadata = self.find_by_id(reference.dataset_id)?;
+        let table_name = dataset_metadata.table_name;
+        let no_rows =
+            || DatasetError::NotFound(format!("Dataset '{}' has no rows to reference", dataset_metadata.name));
+
+        if !self.db.table_exists(&table_name)? {
+            return Err(no_rows());
+        }
+
+        let weighted_row_id = match reference.weight_column_id {
+            Some(weight_column_id) => self.weighted_row_id(&table_name, weight_column_id)?,
+            None => None,
+        };
+
+        // rows without a positive weight are only picked when no row has one
+        let row_id = match weighted_row_id {
+            Some(row_id) => row_id,
+            None => self
+                .db
+                .query(
+                    &format!("SELECT id FROM {} ORDER BY RANDOM() LIMIT 1", table_name),
+                    [],
+                    |row| Ok(row.get::<_, i64>(0)?),
+                )?
+                .into_iter()
+                .next()
+                .ok_or_else(no_rows)?,
+        };
+
+        self.get_reference_row(reference, row_id)
+    }
+
+    /// Row `row_id` of the referenced dataset, as a `REFERENCE` column would see it
+    pub fn get_reference_row(
+        &self,
+        reference: &ReferenceSettings,
+        row_id: i64,
+    ) -> Result<SampledReference, DatasetError> {
+        let table_name = self.find_by_id(reference.dataset_id)?.table_name;
+
+        let data_json = self
+            .db
+            .query(
+                &format!("SELECT data FROM {} WHERE id = ?", table_name),
+                [row_id],
+                |row| Ok(row.get::<_, String>(0)?),
+            )?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::NotFound(format!("Row with id {} not found", row_id)))?;
+        let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+
+        let columns = self.get_columns(reference.dataset_id)?;
+        let mut fields: HashMap<String, String> = columns
+            .iter()
+            .filter_map(|column| {
+                let column_id = column.id?.to_string();
+                let data = row_data.iter().find(|data| data.column_id == column_id)?;
+                Some((column.name.clone(), data.value_or_empty().to_string()))
+            })
+            .collect();
+        fields.entry("id".to_string()).or_insert_with(|| row_id.to_string());
+
+        let value = match reference.column_id {
+            Some(column_id) => row_data
+                .iter()
+                .find(|data| data.column_id == column_id.to_string())
+                .map(|data| data.value_or_empty().to_string())
+                .unwrap_or_default(),
+            None => row_id.to_string(),
+        };
+
+        Ok(SampledReference { value, fields })
+    }
+
+    fn weighted_row_id(&self, table_name: &str, weight_column_id: i64) -> Result<Option<i64>, DatasetError> {
+        let weights = self.db.query(
+            &format!(
+                "SELECT t.id, CAST(json_extract(item.value, '$.value') AS REAL)
+                FROM {} t, json_each(t.data) item
+                WHERE CAST(COALESCE(json_extract(item.value, '$.columnId'), json_extract(item.value, '$.column_id')) AS TEXT) = ?",
+                table_name
+            ),
+            [weight_column_id.to_string()],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<f64>>(1)?.unwrap_or(0.0))),
+        )?;
+
+        let weights: Vec<(i64, f64)> = weights
+            .into_iter()
+            .filter(|(_, weight)| weight.is_finite() && *weight > 0.0)
+            .collect();
+
+        if weights.is_empty() {
+            return Ok(None);
+        }
+
+        let distribution = WeightedIndex::new(weights.iter().map(|(_, weight)| *weight))
+            .map_err(|e| DatasetError::InvalidInput(e.to_string()))?;
+
+        Ok(Some(weights[distribution.sample(&mut rand::thread_rng())].0))
+    }
+
+    pub fn count_rows(&self, table_name: &str) -> Result<i64, DatasetError> {
+        let rows = self
+            .db
+            .query(&format!("SELECT COUNT(*) FROM {}", table_name), [], |row| {
+                Ok(row.get::<_, i64>(0)?)
+            })?;
+
+        rows.into_iter()
+            .next()
+            .ok_or_else(|| DatasetError::DatabaseError("Failed to retrieve row count".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    /// Datasets, columns and rows shared by the test modules below
+    mod fixtures {
+        use super::*;
+
+        pub fn column(
+            metadata: &DatasetMetadata,
+            name: &str,
+            column_type: &str,
+            position: i64,
+            settings: ColumnSettings,
+        ) -> Column {
+            Column {
+                id: None,
+                table_name: metadata.table_name.clone(),
+                dataset_id: metadata.id,
+                name: name.to_string(),
+                column_type: column_type.to_string(),
+                column_type_details: None,
+                rules: format!("A {}", name),
+                position,
+                settings,
+            }
+        }
+
+        /// An empty dataset in a fresh database
+        pub fn dataset(name: &str) -> (DatasetService, DatasetMetadata) {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+            let metadata = dataset.create(name, "test").expect("Failed to create dataset");
+
+            (dataset, metadata)
+        }
+
+        /// Creates a dataset with two `(name, type, settings)` columns and returns their ids
+        pub fn setup(
+            name: &str,
+            columns: [(&str, &str, ColumnSettings); 2],
+        ) -> (DatasetService, DatasetMetadata, i64, i64) {
+            let (dataset, metadata) = dataset(name);
+            let columns = columns
+                .into_iter()
+                .zip(1..)
+                .map(|((name, column_type, settings), position)| {
+                    column(&metadata, name, column_type, position, settings)
+                })
+                .collect::<Vec<_>>();
+            let columns = dataset
+                .add_columns(metadata.id, &columns)
+                .expect("Failed to add columns");
+
+            (dataset, metadata, columns[0].id.unwrap(), columns[1].id.unwrap())
+        }
+
+        /// A `people` dataset with a `name` and an exactly unique `email` column
+        pub fn people() -> (DatasetService, DatasetMetadata, i64, i64) {
+            let email = ColumnSettings {
+                unique: Some(UniqueMode::Exact),
+                ..Default::default()
+            };
+            setup(
+                "people",
+                [("name", "TEXT", ColumnSettings::default()), ("email", "TEXT", email)],
+            )
+        }
+
+        /// Cells are `(column_id, value)`, values are either `&str` or `Option<&str>` for null cells
+        pub fn add_row<'a, V: Into<Option<&'a str>> + Copy>(
+            dataset: &DatasetService,
+            dataset_id: i64,
+            cells: &[(i64, V)],
+        ) -> Row {
+            let data = cells
+                .iter()
+                .map(|&(column_id, value)| {
+                    let value: Option<&str> = value.into();
+                    RowData {
+                        column_id: column_id.to_string(),
+                        value: value.map(str::to_string),
+                    }
+                })
+                .collect();
+            dataset.add_row(dataset_id, &data).expect("Failed to add row")
+        }
+    }
+
+    mod creation {
+        use crate::services::ModelService;
+
+        use super::*;
+
+        #[test]
+        fn test_create_dataset_metadata_default_table() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute("DROP TABLE IF EXISTS models", [])
+                    .expect("Failed to delete models table");
+                conn.execute("DROP TABLE IF EXISTS columns", [])
+                    .expect("Failed to delete columns table");
+                conn.execute("DROP TABLE IF EXISTS datasets_metadata", [])
+                    .expect("Failed to delete datasets_metadata table");
+            }
+
+            dataset
+                .create_dataset_metadata_default_table()
+                .expect("Failed to create datasets_metadata table");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut datasets_metadata_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='datasets_metadata'")
+                .expect("Failed to prepare query");
+
+            let mut datasets_metadata_index_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='index' AND name='idx_datasets_metadata_name'")
+                .expect("Failed to prepare query");
+            let datasets_metadata_exists: bool = datasets_metadata_stmt
+                .exists([])
+                .expect("Failed to check if table exists");
+            let datasets_metadata_index_exists: bool = datasets_metadata_index_stmt
+                .exists([])
+                .expect("Failed to check if index exists");
+
+            assert!(
+                datasets_metadata_index_exists,
+                "datasets_metadata index was not created"
+            );
+            assert!(datasets_metadata_exists, "datasets_metadata table was not created");
+        }
+
+        #[test]
+        fn test_create_columns_default_table() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute("DROP TABLE IF EXISTS models", [])
+                    .expect("Failed to delete models table");
+                conn.execute("DROP TABLE IF EXISTS columns", [])
+                    .expect("Failed to delete columns table");
+                conn.execute("DROP TABLE IF EXISTS datasets_metadata", [])
+                    .expect("Failed to delete datasets_metadata table");
+            }
+
+            dataset
+                .create_columns_default_table()
+                .expect("Failed to create columns table");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut columns_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='columns'")
+                .expect("Failed to prepare query");
+            let mut columns_index_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='index' AND name='idx_column_position'")
+                .expect("Failed to prepare query");
+
+            let columns_exists: bool = columns_stmt.exists([]).expect("Failed to check if table exists");
+            let columns_index_exists: bool = columns_index_stmt.exists([]).expect("Failed to check if index exists");
+
+            assert!(columns_index_exists, "columns index was not created");
+            assert!(columns_exists, "columns table was not created");
+        }
+
+        #[test]
+        fn test_new_dataset_connection_to_database() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+            let _ = ModelService::new(None, dataset.db.clone()).expect("Failed to create model service");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut datasets_metadata_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='datasets_metadata'")
+                .expect("Failed to prepare query");
+            let datasets_metadata_exists: bool = datasets_metadata_stmt
+                .exists([])
+                .expect("Failed to check if table exists");
+            assert!(datasets_metadata_exists, "datasets_metadata table was not created");
+
+            let mut columns_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='columns'")
+                .expect("Failed to prepare query");
+            let columns_exists: bool = columns_stmt.exists([]).expect("Failed to check if table exists");
+            assert!(columns_exists, "columns table was not created");
+
+            let mut models_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='models'")
+                .expect("Failed to prepare query");
+            let models_exists: bool = models_stmt.exists([]).expect("Failed to check if table exists");
+            assert!(models_exists, "models table was not created");
+        }
+
+        #[test]
+        fn test_create_dataset() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            let dataset_result = dataset.create("test", "test").expect("Failed to create dataset");
+            assert!(dataset_result.name == "test", "Failed to create dataset");
+            assert!(dataset_result.description == "test", "Failed to create dataset");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut datasets_metadata_stmt = conn
+                .prepare(&format!(
+                    "SELECT name FROM datasets_metadata WHERE id = {}",
+                    dataset_result.id
+                ))
+                .expect("Failed to prepare query");
+
+            let datasets_metadata_exists: bool = datasets_metadata_stmt
+                .exists([])
+                .expect("Failed to check if dataset exists");
+
+            assert!(datasets_metadata_exists, "dataset was not created");
+        }
+    }
+
+    mod queries {
+        use super::*;
+
+        #[test]
+        fn test_find_by_id_dataset() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset_test", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let dataset_result = dataset.find_by_id(1).expect("Failed to find dataset");
+
+            assert!(dataset_result.name == "test", "Failed to find dataset");
+        }
+
+        #[test]
+        fn test_find_all_dataset() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset_test", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset_test", "test2", "test2"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let dataset_results = dataset.find_all().expect("Failed to find dataset");
+
+            assert!(dataset_results.len() == 2, "Failed to find dataset");
+            assert!(dataset_results[0].name == "test", "Failed to find dataset");
+            assert!(dataset_results[1].name == "test2", "Failed to find dataset");
+        }
+
+        #[test]
+        fn test_get_all_rows() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset_test (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO dataset_test (data) VALUES (?)",
+                    [r#"[{"column_id":"1","value":"test"}]"#],
+                )
+                .expect("Failed to insert row 1");
+
+                conn.execute(
+                    "INSERT INTO dataset_test (data) VALUES (?)",
+                    [r#"[{"column_id":"1","value":"hello"},{"column_id":"2","value":"world"}]"#],
+                )
+                .expect("Failed to insert row 2");
+
+                conn.execute(
+                    "INSERT INTO dataset_test (data) VALUES (?)",
+                    [r#"[{"column_id":"1","value":"test with \"quotes\""},{"column_id":"2","value":"123"},{"column_id":"3","value":"special: !@#$%"}]"#],
+                )
+                .expect("Failed to insert row 3");
+
+                conn.execute(
+                    "INSERT INTO dataset_test (data) VALUES (?)",
+                    [r#"[{"column_id":"1","value":""}]"#],
+                )
+                .expect("Failed to insert row 4");
+
+                let large_text = "a".repeat(1000);
+                let large_row_data = format!(r#"[{{"column_id":"1","value":"{}"}}]"#, large_text);
+                conn.execute("INSERT INTO dataset_test (data) VALUES (?)", [large_row_data.as_str()])
+                    .expect("Failed to insert row 5");
+            }
+
+            let rows = dataset.get_all_rows("dataset_test").expect("Failed to get all rows");
+            assert_eq!(rows.len(), 5, "Should return exactly 5 rows");
+
+            for i in 0..rows.len() {
+                assert_eq!(rows[i].id, (i + 1) as i64, "Rows should be ordered by id ascending");
+            }
+
+            assert_eq!(rows[0].data.len(), 1, "First row should have 1 column");
+            assert_eq!(rows[0].data[0].column_id, "1", "First row column_id should be '1'");
+            assert_eq!(
+                rows[0].data[0].value.as_deref(),
+                Some("test"),
+                "First row value should be 'test'"
+            );
+
+            assert_eq!(rows[1].data.len(), 2, "Second row should have 2 columns");
+            assert_eq!(
+                rows[1].data[0].column_id, "1",
+                "Second row first column_id should be '1'"
+            );
+            assert_eq!(
+                rows[1].data[0].value.as_deref(),
+                Some("hello"),
+                "Second row first value should be 'hello'"
+            );
+            assert_eq!(
+                rows[1].data[1].column_id, "2",
+                "Second row second column_id should be '2'"
+            );
+            assert_eq!(
+                rows[1].data[1].value.as_deref(),
+                Some("world"),
+                "Second row second value should be 'world'"
+            );
+
+            assert_eq!(rows[2].data.len(), 3, "Third row should have 3 columns");
+            assert_eq!(
+                rows[2].data[0].value.as_deref(),
+                Some("test with \"quotes\""),
+                "Should handle escaped quotes"
+            );
+            assert_eq!(
+                rows[2].data[1].value.as_deref(),
+                Some("123"),
+                "Should handle numeric strings"
+            );
+            assert_eq!(
+                rows[2].data[2].value.as_deref(),
+                Some("special: !@#$%"),
+                "Should handle special characters"
+            );
+
+            assert_eq!(rows[3].data.len(), 1, "Fourth row should have 1 column");
+            assert_eq!(
+                rows[3].data[0].value.as_deref(),
+                Some(""),
+                "Should handle empty string values"
+            );
+
+            assert_eq!(rows[4].data.len(), 1, "Fifth row should have 1 column");
+            assert_eq!(
+                rows[4].data[0].value_or_empty().len(),
+                1000,
+                "Should handle large text values"
+            );
+            assert!(
+                rows[4].data[0].value_or_empty().chars().all(|c| c == 'a'),
+                "Large text should be all 'a's"
+            );
+
+            for (idx, row) in rows.iter().enumerate() {
+                assert!(
+                    !row.created_at.is_empty(),
+                    "Row {} should have created_at timestamp",
+                    idx
+                );
+                assert!(
+                    !row.updated_at.is_empty(),
+                    "Row {} should have updated_at timestamp",
+                    idx
+                );
+            }
+
+            let mut ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), 5, "All rows should have unique ids");
+        }
+    }
+
+    mod updates {
+        use super::*;
+
+        #[test]
+        fn test_update_dataset() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset_test", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let updated_dataset = dataset
+                .update(1, Some("testUpdated0"), Some("testUpdated"))
+                .expect("Failed to update dataset");
+            assert!(updated_dataset.name == "testUpdated0", "Failed to update dataset");
+            assert!(updated_dataset.description == "testUpdated", "Failed to update dataset");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let result = conn
+                .query_row("SELECT * FROM datasets_metadata WHERE id = 1", [], |row| {
+                    Ok(DatasetMetadata {
+                        id: row.get(0)?,
+                        table_name: row.get(1)?,
+                        name: row.get(2)?,
+                        description: row.get(3)?,
+                        created_at: row.get(4)?,
+                        updated_at: row.get(5)?,
+                        row_count: 0,
+                        settings: DatasetSettings::default(),
+                    })
+                })
+                .expect("Failed to query dataset");
+
+            assert_eq!(result.name, "testUpdated0", "Failed to update dataset");
+            assert_eq!(result.description, "testUpdated", "Failed to update dataset");
+        }
+
+        #[test]
+        fn test_update_dataset_settings() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            let created = dataset.create("test", "test").expect("Failed to create dataset");
+            assert_eq!(created.settings, DatasetSettings::default());
+            assert_eq!(created.settings.row_strategy, RowStrategy::PerCell);
+
+            let settings = DatasetSettings {
+                row_strategy: RowStrategy::JsonObject,
+                row_group_size: Some(4),
+                include_context: true,
+                locale: Locale::Fr,
+                pii_policy: PiiPolicy::Flag,
+                record_provenance: true,
+            };
+
+            let updated = dataset
+                .update_settings(created.id, &settings)
+                .expect("Failed to update settings");
+            assert_eq!(updated.settings, settings);
+
+            let found = dataset.find_by_id(created.id).expect("Failed to find dataset");
+            assert_eq!(found.settings, settings);
+
+            let invalid = dataset.update_settings(
+                created.id,
+                &DatasetSettings {
+                    row_group_size: Some(0),
+                    ..Default::default()
+                },
+            );
+            assert!(invalid.is_err(), "Group size of zero should be rejected");
+        }
+
+        #[test]
+        fn test_settings_group_size() {
+            assert_eq!(DatasetSettings::default().group_size(6), 1);
+
+            let json_rows = DatasetSettings {
+                row_strategy: RowStrategy::JsonObject,
+                ..Default::default()
+            };
+            assert_eq!(json_rows.group_size(6), 6);
+            assert_eq!(json_rows.group_size(0), 1);
+
+            let grouped = DatasetSettings {
+                row_group_size: Some(4),
+                ..json_rows
+            };
+            assert_eq!(grouped.group_size(6), 4);
+        }
+
+        #[test]
+        fn test_prompt_template() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            let created = dataset.create("test", "test").expect("Failed to create dataset");
+            assert_eq!(
+                dataset.get_prompt_template(created.id).expect("Failed to get template"),
+                PromptTemplate::default()
+            );
+
+            let template = PromptTemplate {
+                system_prompt: Some("Tu es un générateur de données.".to_string()),
+                cell_template: Some("{dataset_description}\n{column_name}: {column_rule} ({format})".to_string()),
+            };
+
+            let updated = dataset
+                .update_prompt_template(created.id, &template)
+                .expect("Failed to update template");
+            assert_eq!(updated, template);
+
+            let reset = dataset
+                .update_prompt_template(
+                    created.id,
+                    &PromptTemplate {
+                        system_prompt: Some("  ".to_string()),
+                        cell_template: None,
+                    },
+                )
+                .expect("Failed to reset template");
+            assert_eq!(
+                reset,
+                PromptTemplate::default(),
+                "Blank values should fall back to defaults"
+            );
+        }
+
+        #[test]
+        fn test_prompt_template_rejects_invalid_template() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            let created = dataset.create("test", "test").expect("Failed to create dataset");
+
+            let result = dataset.update_prompt_template(
+                created.id,
+                &PromptTemplate {
+                    system_prompt: None,
+                    cell_template: Some("Generate {column_name}".to_string()),
+                },
+            );
+            assert!(
+                result.is_err(),
+                "Template without required placeholders should be rejected"
+            );
+
+            let result = dataset.update_prompt_template(42, &PromptTemplate::default());
+            assert!(result.is_err(), "Unknown dataset should be rejected");
+        }
+
+        #[test]
+        fn test_delete_dataset() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset_test", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let deleted_dataset = dataset.delete(1);
+            assert!(deleted_dataset.is_ok(), "Failed to delete dataset");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut datasets_metadata_stmt = conn
+                .prepare("SELECT * FROM datasets_metadata WHERE id = 1")
+                .expect("Failed to prepare query");
+            let datasets_metadata_exists: bool = datasets_metadata_stmt
+                .exists([])
+                .expect("Failed to check if dataset exists");
+
+            assert!(!datasets_metadata_exists, "dataset was not deleted");
+        }
+    }
+
+    mod columns {
+        use super::*;
+
+        #[test]
+        fn test_dataset_add_columns() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            let columns = vec![
+                Column {
+                    id: None,
+                    table_name: "dataset001".to_string(),
+                    dataset_id: 1,
+                    name: "test".to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: "test".to_string(),
+                    position: 1,
+                    settings: ColumnSettings::default(),
+                },
+                Column {
+                    id: None,
+                    table_name: "dataset001".to_string(),
+                    dataset_id: 1,
+                    name: "test2".to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: "test2".to_string(),
+                    position: 2,
+                    settings: ColumnSettings::default(),
+                },
+            ];
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let added_columns = dataset.add_columns(1, &columns);
+            assert!(added_columns.is_ok(), "Failed to add columns");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut columns_stmt = conn
+                .prepare("SELECT * FROM columns WHERE dataset_id = 1")
+                .expect("Failed to prepare query");
+
+            let columns_map = columns_stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, String>(5)?,
+                        row.get::<_, i64>(6)?,
+                    ))
+                })
+                .expect("Failed to query columns");
+
+            let columns_length = columns_map.count();
+            assert!(columns_length > 1, "columns were not added");
+
+            let mut dataset_001_stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='dataset001'")
+                .expect("Failed to prepare query");
+            let dataset_001_exists: bool = dataset_001_stmt
+                .exists([])
+                .expect("Failed to check if dataset_001 exists");
+            assert!(dataset_001_exists, "dataset001 was not created");
+        }
+
+        #[test]
+        fn test_dataset_add_columns_with_existing_table() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            let columns = vec![
+                Column {
+                    id: None,
+                    table_name: "dataset001".to_string(),
+                    dataset_id: 1,
+                    name: "test".to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: "test".to_string(),
+                    position: 1,
+                    settings: ColumnSettings::default(),
+                },
+                Column {
+                    id: None,
+                    table_name: "dataset001".to_string(),
+                    dataset_id: 1,
+                    name: "test2".to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: "test2".to_string(),
+                    position: 2,
+                    settings: ColumnSettings::default(),
+                },
+            ];
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                            id INTEGER PRIMARY KEY AUTOINCREMENT,
+                            data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                        )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    ["[{ \"column_id\": \"1\", \"value\": \"test\" }]"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let added_columns = dataset.add_columns(1, &columns);
+            assert!(added_columns.is_ok(), "Failed to add columns");
+
+            let conn = dataset.db.conn.lock().unwrap();
+            let mut dataset_001_stmt = conn
+                .prepare("SELECT * FROM dataset001")
+                .expect("Failed to prepare query");
+
+            let values: Result<Vec<_>, _> = dataset_001_stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                })
+                .expect("Failed to query dataset")
+                .collect();
+
+            let values = values
+                .expect("Failed to collect values")
+                .into_iter()
+                .map(|(_, data_json, _, _)| data_json);
+
+            let expected_column_ids = ["2", "3"];
+
+            for data_json in values {
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json).expect("Failed to parse JSON data");
+
+                let found_column_ids: std::collections::HashSet<&str> =
+                    row_data.iter().map(|item| item.column_id.as_str()).collect();
+
+                for expected_id in expected_column_ids {
+                    assert!(
+                        found_column_ids.contains(expected_id),
+                        "Row data missing expected column ID: '{}'. Found columns: {:?}",
+                        expected_id,
+                        found_column_ids
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn test_dataset_get_columns() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let columns = dataset.get_columns(1);
+            assert!(columns.is_ok(), "Failed to get columns");
+            assert!(columns.unwrap().len() == 2, "Failed to get columns");
+        }
+
+        #[test]
+        fn test_dataset_update_column() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+            }
+
+            let updated_column = dataset.update_column(
+                1,
+                UpdatableColumnFields {
+                    name: Some("test1".to_string()),
+                    rules: Some("test".to_string()),
+                    column_type: None,
+                    column_type_details: None,
+                    position: Some("1".to_string()),
+                    settings: None,
+                },
+            );
+            assert!(updated_column.is_ok(), "Failed to update column");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let column = conn
+                .query_row(
+                    "SELECT id, table_name, dataset_id, name, column_type, column_type_details, rules, position FROM columns WHERE id = 1",
+                    [],
+                    |row| {
+                        Ok(Column {
+                            id: Some(row.get::<_, i64>(0)?),
+                            table_name: row.get::<_, String>(1)?,
+                            dataset_id: row.get::<_, i64>(2)?,
+                            name: row.get::<_, String>(3)?,
+                            column_type: row.get::<_, String>(4)?,
+                            column_type_details: Some(row.get::<_, String>(5)?),
+                            rules: row.get::<_, String>(6)?,
+                            position: row.get::<_, i64>(7)?,
+                            settings: ColumnSettings::default(),
+                        })
+                    },
+                )
+                .expect("Failed to query column");
+
+            assert_eq!(column.name, "test1", "Failed to update column");
+            assert_eq!(column.rules, "test", "Failed to update column");
+            assert_eq!(column.position, 1, "Failed to update column");
+        }
+
+        #[test]
+        fn test_dataset_delete_column() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
+                )
+                .expect("Failed to insert data row 1");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "Jane"}, {"column_id": "2", "value": "25"}]"#],
+                )
+                .expect("Failed to insert data row 2");
+            }
+
+            let deleted_column = dataset.delete_column(1);
+            assert!(
+                deleted_column.is_ok(),
+                "Failed to delete column: {:?}",
+                deleted_column.err()
+            );
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let mut column_stmt = conn
+                .prepare("SELECT * FROM columns WHERE id = 1")
+                .expect("Failed to check if column exists");
+            let exists: bool = column_stmt.exists([]).expect("Failed to check if table exists");
+            assert!(!exists, "Failed to delete column from columns table");
+
+            let mut data_stmt = conn
+                .prepare("SELECT data FROM dataset001")
+                .expect("Failed to prepare data query");
+            let data_rows: Vec<String> = data_stmt
+                .query_map([], |row| row.get(0))
+                .expect("Failed to query data")
+                .collect::<Result<Vec<_>, _>>()
+                .expect("Failed to collect data");
+
+            for data_json in data_rows {
+                let row_data: Vec<RowData> = serde_json::from_str(&data_json).expect("Failed to parse JSON data");
+
+                for item in &row_data {
+                    assert_ne!(item.column_id, "1", "Column data was not removed from rows");
+                }
+
+                let has_column_2 = row_data.iter().any(|item| item.column_id == "2");
+                assert!(has_column_2, "Other column data was incorrectly removed");
+            }
+        }
+    }
+
+    mod rows {
+        use super::*;
+
+        #[test]
+        fn test_dataset_get_rows() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+                )
+                .expect("Failed to insert dataset");
+
+                for i in 0..11 {
+                    let data = format!(
+                        r#"[{{ "column_id": "1", "value": "John"}}, {{ "column_id": "2", "value": "{}"}}]"#,
+                        i
+                    );
+
+                    let data = data.as_str();
+                    conn.execute("INSERT INTO dataset001 (data) VALUES (?)", [data])
+                        .expect("Failed to insert data row 1");
+                }
+            }
+
+            let next_rows = dataset.get_rows(1, 1, 5);
+            assert!(next_rows.is_ok(), "Failed to get next rows");
+            assert!(next_rows.unwrap().data.len() == 5, "Failed to get next rows");
+        }
+
+        #[test]
+        fn test_dataset_count_rows() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+                )
+                .expect("Failed to insert dataset");
+
+                for i in 0..10 {
+                    let data = format!(
+                        r#"[{{ "column_id": "1", "value": "John"}}, {{ "column_id": "2", "value": "{}"}}]"#,
+                        i
+                    );
+
+                    let data = data.as_str();
+                    conn.execute("INSERT INTO dataset001 (data) VALUES (?)", [data])
+                        .expect("Failed to insert data row 1");
+                }
+            }
+
+            let count = dataset
+                .count_rows("dataset001")
+                .expect("Failed to  call count_rows function");
+            assert!(count == 10, "Failed to count rows");
+        }
+
+        #[test]
+        fn test_dataset_update_row() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
+                )
+                .expect("Failed to insert data row 1");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "Jane"}, {"column_id": "2", "value": "25"}]"#],
+                )
+                .expect("Failed to insert data row 2");
+            }
+
+            let updated_row = dataset.update_row(
+                1,
+                2,
+                &HashMap::from([(2, Some("30".to_string())), (1, Some("Johnny".to_string()))]),
+            );
+            assert!(updated_row.is_ok(), "Failed to update row");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let row = conn
+                .query_row("SELECT * FROM dataset001 WHERE id = 2", [], |row| {
+                    Ok(Row {
+                        id: row.get::<_, i64>(0)?,
+                        data: serde_json::from_str(&row.get::<_, String>(1)?).expect("Failed to parse JSON data"),
+                        created_at: row.get::<_, String>(2)?,
+                        updated_at: row.get::<_, String>(3)?,
+                    })
+                })
+                .expect("Failed to query row");
+
+            assert_eq!(row.data[0].value.as_deref(), Some("Johnny"), "Failed to update row");
+            assert_eq!(row.data[1].value.as_deref(), Some("30"), "Failed to update row");
+        }
+
+        #[test]
+        fn test_add_row() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
+                )
+                .expect("Failed to insert data row 1");
+            }
+
+            let new_row = dataset.add_row(
+                1,
+                &vec![
+                    RowData {
+                        column_id: "1".to_string(),
+                        value: Some("John".to_string()),
+                    },
+                    RowData {
+                        column_id: "2".to_string(),
+                        value: Some("30".to_string()),
+                    },
+                ],
+            );
+            assert!(new_row.is_ok(), "Failed to add row");
+
+            let conn = dataset.db.conn.lock().unwrap();
+
+            let row = conn
+                .query_row("SELECT * FROM dataset001 WHERE id = 1", [], |row| {
+                    Ok(Row {
+                        id: row.get::<_, i64>(0)?,
+                        data: serde_json::from_str(&row.get::<_, String>(1)?).expect("Failed to parse JSON data"),
+                        created_at: row.get::<_, String>(2)?,
+                        updated_at: row.get::<_, String>(3)?,
+                    })
+                })
+                .expect("Failed to query row");
+
+            assert_eq!(row.data[0].value.as_deref(), Some("John"), "Failed to add row");
+            assert_eq!(row.data[1].value.as_deref(), Some("30"), "Failed to add row");
+        }
+
+        #[test]
+        fn test_delete_row() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+
+            {
+                let conn = dataset.db.conn.lock().unwrap();
+
+                conn.execute(
+                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
+                    ["dataset001", "test", "test"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "CREATE TABLE IF NOT EXISTS dataset001 (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
+                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                    )",
+                    [],
+                )
+                .expect("failed to create database");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
+                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+                )
+                .expect("Failed to insert dataset");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
+                )
+                .expect("Failed to insert data row 1");
+
+                conn.execute(
+                    "INSERT INTO dataset001 (data) VALUES (?)",
+                    [r#"[{"column_id": "1", "value": "Jane"}, {"column_id": "2", "value": "25"}]"#],
+                )
+                .expect("Failed to insert data row 2");
+            }
+
+            let deleted_row = dataset.delete_row(1, 2);
+            assert!(deleted_row.is_ok(), "Failed to delete row");
+        }
+
+        fn setup_examples_dataset() -> DatasetService {
+            let (dataset, metadata) = fixtures::dataset("test");
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[fixtures::column(
+                        &metadata,
+                        "test1",
+                        "TEXT",
+                        1,
+                        ColumnSettings::default(),
+                    )],
+                )
+                .expect("Failed to add columns");
+            let column_id = columns[0].id.unwrap();
+
+            for name in ["John", "Jane", "", "Jack", "Jill"] {
+                fixtures::add_row(&dataset, metadata.id, &[(column_id, name)]);
+            }
+
+            dataset
+        }
+
+        #[test]
+        fn test_get_example_values_most_recent() {
+            let dataset = setup_examples_dataset();
+
+            let examples = dataset
+                .get_example_values(
+                    1,
+                    1,
+                    &FewShotSettings {
+                        strategy: FewShotStrategy::MostRecent,
+                        count: 3,
+                    },
+                )
+                .expect("Failed to get example values");
+
+            assert_eq!(examples, vec!["Jill", "Jack", "Jane"], "Empty values should be skipped");
+        }
+
+        #[test]
+        fn test_get_example_values_random() {
+            let dataset = setup_examples_dataset();
+
+            let examples = dataset
+                .get_example_values(
+                    1,
+                    1,
+                    &FewShotSettings {
+                        strategy: FewShotStrategy::Random,
+                        count: 10,
+                    },
+                )
+                .expect("Failed to get example values");
+
+            assert_eq!(examples.len(), 4, "Should return every non-empty value");
+        }
+
+        #[test]
+        fn test_get_example_values_reads_added_rows() {
+            let dataset = setup_examples_dataset();
+
+            dataset
+                .add_row(
+                    1,
+                    &vec![RowData {
+                        column_id: "1".to_string(),
+                        value: Some("Joan".to_string()),
+                    }],
+                )
+                .expect("Failed to add row");
+
+            let examples = dataset
+                .get_example_values(
+                    1,
+                    1,
+                    &FewShotSettings {
+                        strategy: FewShotStrategy::MostRecent,
+                        count: 1,
+                    },
+                )
+                .expect("Failed to get example values");
+
+            assert_eq!(examples, vec!["Joan"], "Rows stored with camelCase keys should be read");
+        }
+
+        #[test]
+        fn test_get_example_values_pinned() {
+            let dataset = setup_examples_dataset();
+
+            dataset.pin_row(1, 2).expect("Failed to pin row");
+            dataset.pin_row(1, 4).expect("Failed to pin row");
+            dataset.pin_row(1, 4).expect("Pinning twice should be a no-op");
+
+            assert_eq!(
+                dataset.get_pinned_row_ids(1).expect("Failed to get pinned rows"),
+                vec![2, 4]
+            );
+
+            let examples = dataset
+                .get_example_values(
+                    1,
+                    1,
+                    &FewShotSettings {
+                        strategy: FewShotStrategy::Pinned,
+                        count: 5,
+                    },
+                )
+                .expect("Failed to get example values");
 
-                Ok(Row {
-                    id: row.get::<_, i64>(0)?,
-                    data: row_data.into_boxed_slice(),
-                    created_at: row.get::<_, String>(2)?,
-                    updated_at: row.get::<_, String>(3)?,
-                })
-            },
-        )?;
+            assert_eq!(examples, vec!["Jack", "Jane"]);
+        }
 
-        Ok(rows)
-    }
+        #[test]
+        fn test_pin_row_not_found() {
+            let dataset = setup_examples_dataset();
 
-    pub fn get_rows(&self, dataset_id: i64, page: i64, page_size: i64) -> Result<PaginatedResponse, DatasetError> {
-        if page <= 0 {
-            return Err(DatasetError::InvalidInput(
-                "Page number must be a positive integer".to_string(),
-            ));
+            let result = dataset.pin_row(1, 42);
+            assert!(result.is_err(), "Pinning an unknown row should fail");
         }
 
-        if page_size <= 0 {
-            return Err(DatasetError::InvalidInput(
-                "Page size must be a positive integer".to_string(),
-            ));
+        #[test]
+        fn test_delete_row_unpins_row() {
+            let dataset = setup_examples_dataset();
+
+            dataset.pin_row(1, 2).expect("Failed to pin row");
+            dataset.delete_row(1, 2).expect("Failed to delete row");
+
+            assert!(
+                dataset
+                    .get_pinned_row_ids(1)
+                    .expect("Failed to get pinned rows")
+                    .is_empty(),
+                "Deleted rows should be unpinned"
+            );
         }
+    }
 
-        let dataset_metadata = self.find_by_id(dataset_id)?;
-        let table_name = dataset_metadata.table_name;
+    mod uniqueness {
+        use super::*;
 
-        let total_rows = self.count_rows(&table_name)?;
-        let total_pages = ((total_rows as f64) / (page_size as f64)).ceil() as i64;
+        fn setup_unique_dataset(unique: Option<UniqueMode>, avoid_recent: Option<usize>) -> (DatasetService, i64) {
+            let (dataset, metadata) = fixtures::dataset("people");
+            let settings = ColumnSettings {
+                unique,
+                avoid_recent,
+                ..Default::default()
+            };
+
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[fixtures::column(&metadata, "email", "TEXT", 1, settings)],
+                )
+                .expect("Failed to add columns");
 
-        if total_pages > 0 && page > total_pages {
-            return Err(DatasetError::InvalidInput(format!(
-                "Page {} exceeds total pages {}",
-                page, total_pages
-            )));
+            (dataset, columns[0].id.expect("Column should have an ID"))
         }
 
-        let offset = (page - 1) * page_size;
+        fn row(column_id: i64, value: &str) -> Vec<RowData> {
+            vec![RowData {
+                column_id: column_id.to_string(),
+                value: Some(value.to_string()),
+            }]
+        }
 
-        let table_exists = self.db.table_exists(&table_name)?;
-        if !table_exists {
-            return Ok(PaginatedResponse {
-                data: Vec::new(),
-                page,
-                page_size,
-                total_rows: 0,
-                total_pages: 0,
-                has_next: false,
-                has_previous: false,
-            });
+        #[test]
+        fn test_column_settings_validate() {
+            assert!(ColumnSettings::default().validate().is_ok());
+
+            let invalid_tokens = ColumnSettings {
+                stop: Some(StopSettings {
+                    min_tokens: Some(50),
+                    max_tokens: Some(10),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert!(invalid_tokens.validate().is_err());
+
+            let invalid_chars = ColumnSettings {
+                stop: Some(StopSettings {
+                    max_chars: Some(0),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert!(invalid_chars.validate().is_err());
+
+            let invalid_null_probability = ColumnSettings {
+                null_probability: Some(1.5),
+                ..Default::default()
+            };
+            assert!(invalid_null_probability.validate().is_err());
+
+            let invalid_condition = ColumnSettings {
+                conditions: vec![ConditionalRule {
+                    column: " ".to_string(),
+                    operator: ConditionOperator::IsEmpty,
+                    value: String::new(),
+                    rules: String::new(),
+                    null_probability: None,
+                }],
+                ..Default::default()
+            };
+            assert!(invalid_condition.validate().is_err());
+
+            let condition = ColumnSettings {
+                conditions: vec![ConditionalRule {
+                    column: "country".to_string(),
+                    operator: ConditionOperator::Equals,
+                    value: "US".to_string(),
+                    rules: "A ZIP code".to_string(),
+                    null_probability: Some(0.0),
+                }],
+                ..Default::default()
+            };
+            assert!(condition.validate().is_ok());
+            let json = serde_json::to_value(&condition).unwrap();
+            assert_eq!(json["conditions"][0]["operator"], "equals");
+            assert_eq!(serde_json::from_value::<ColumnSettings>(json).unwrap(), condition);
         }
 
-        let column_info = self
-            .db
-            .query(&format!("PRAGMA table_info({})", table_name), [], |row| {
-                Ok(row.get::<_, String>(1)?)
-            })?;
+        #[test]
+        fn test_normalize_value() {
+            assert_eq!(normalize_value("  John   Smith "), "john smith");
+            assert_eq!(normalize_value("john-smith."), "john smith");
+            assert_eq!(normalize_value("?!"), "");
+        }
 
-        let rows = self.db.query(
-            &format!("SELECT * FROM {} ORDER BY id ASC LIMIT ? OFFSET ?", table_name),
-            [page_size, offset],
-            |row| {
-                let mut map = HashMap::new();
-                for (i, column_name) in column_info.iter().enumerate() {
-                    let value: serde_json::Value = if column_name == "data" {
-                        if let Ok(text_val) = row.get::<_, String>(i) {
-                            serde_json::from_str(&text_val).unwrap_or(serde_json::Value::String(text_val))
-                        } else {
-                            serde_json::Value::Null
-                        }
-                    } else if let Ok(int_val) = row.get::<_, i64>(i) {
-                        serde_json::Value::String(int_val.to_string())
-                    } else if let Ok(text_val) = row.get::<_, String>(i) {
-                        serde_json::Value::String(text_val)
-                    } else {
-                        serde_json::Value::String("".to_string())
-                    };
-                    map.insert(column_name.clone(), value);
-                }
-                Ok(map)
-            },
-        )?;
+        #[test]
+        fn test_add_row_rejects_exact_duplicate() {
+            let (dataset, column_id) = setup_unique_dataset(Some(UniqueMode::Exact), None);
 
-        Ok(PaginatedResponse {
-            data: rows,
-            page,
-            page_size,
-            total_rows,
-            total_pages,
-            has_next: page < total_pages,
-            has_previous: page > 1,
-        })
-    }
+            dataset
+                .add_row(1, &row(column_id, "john@mail.com"))
+                .expect("Failed to add row");
 
-    pub fn add_row(&self, dataset_id: i64, data: &Vec<RowData>) -> Result<Row, DatasetError> {
-        let dataset_metadata = self.find_by_id(dataset_id)?;
-        let table_name = dataset_metadata.table_name;
+            let result = dataset.add_row(1, &row(column_id, "john@mail.com"));
+            assert!(result.is_err(), "Duplicate value should be rejected");
 
-        let columns = self.get_columns(dataset_id)?;
+            dataset
+                .add_row(1, &row(column_id, "John@mail.com"))
+                .expect("Exact mode should be case sensitive");
+        }
 
-        let mut row_data = Vec::new();
-        for column in columns {
-            let column_id = column
-                .id
-                .expect("Column should have an ID when retrieved from database");
+        #[test]
+        fn test_add_row_rejects_normalized_duplicate() {
+            let (dataset, column_id) = setup_unique_dataset(Some(UniqueMode::Normalized), None);
 
-            let value = data
-                .iter()
-                .find(|r| r.column_id == column_id.to_string())
-                .map(|r| r.value.clone())
-                .ok_or_else(|| DatasetError::NotFound(format!("Column with id {} not found", column_id)))?;
+            dataset
+                .add_row(1, &row(column_id, "John Smith"))
+                .expect("Failed to add row");
 
-            row_data.push(RowData {
-                column_id: column_id.to_string(),
-                value,
-            });
+            let result = dataset.add_row(1, &row(column_id, "john-smith"));
+            assert!(result.is_err(), "Normalized duplicate should be rejected");
         }
 
-        let json_data = serde_json::to_string(&row_data)?;
+        #[test]
+        fn test_update_row_ignores_own_value() {
+            let (dataset, column_id) = setup_unique_dataset(Some(UniqueMode::Exact), None);
 
-        self.db.execute_transaction(&[
-            (&format!("INSERT INTO {} (data) VALUES (?)", table_name), &[&json_data]),
-            (
-                "UPDATE datasets_metadata SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-                &[&dataset_id.to_string()],
-            ),
-        ])?;
+            let first = dataset
+                .add_row(1, &row(column_id, "a@mail.com"))
+                .expect("Failed to add row");
+            dataset
+                .add_row(1, &row(column_id, "b@mail.com"))
+                .expect("Failed to add row");
 
-        let row = self.db.query(
-            &format!(
-                "SELECT id, data, created_at, updated_at FROM {} ORDER BY id DESC LIMIT 1",
-                table_name
-            ),
-            [],
-            |row| {
-                let data_json: String = row.get(1)?;
-                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
-                Ok(Row {
-                    id: row.get::<_, i64>(0)?,
-                    data: row_data.into_boxed_slice(),
-                    created_at: row.get::<_, String>(2)?,
-                    updated_at: row.get::<_, String>(3)?,
-                })
-            },
-        )?;
+            dataset
+                .update_row(
+                    1,
+                    first.id,
+                    &HashMap::from([(column_id, Some("a@mail.com".to_string()))]),
+                )
+                .expect("Keeping the same value should be allowed");
 
-        Ok(row
-            .into_iter()
-            .next()
-            .ok_or_else(|| DatasetError::NotFound(format!("Dataset not found")))?)
-    }
+            let result = dataset.update_row(
+                1,
+                first.id,
+                &HashMap::from([(column_id, Some("b@mail.com".to_string()))]),
+            );
+            assert!(result.is_err(), "Updating to another row's value should be rejected");
+        }
 
-    pub fn update_row(
-        &self,
-        dataset_id: i64,
-        row_id: i64,
-        updates: &HashMap<i64, String>,
-    ) -> Result<Row, DatasetError> {
-        let dataset_metadata = self.find_by_id(dataset_id)?;
-        let table_name = dataset_metadata.table_name;
+        #[test]
+        fn test_delete_row_frees_value() {
+            let (dataset, column_id) = setup_unique_dataset(Some(UniqueMode::Exact), None);
 
-        let rows = self.db.query(
-            &format!("SELECT data FROM {} WHERE id = ?", table_name),
-            [row_id],
-            |row| {
-                row.get::<_, String>(0)
-                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
-            },
-        )?;
+            let first = dataset
+                .add_row(1, &row(column_id, "a@mail.com"))
+                .expect("Failed to add row");
+            dataset.delete_row(1, first.id).expect("Failed to delete row");
 
-        if rows.is_empty() {
-            return Err(DatasetError::NotFound(format!("Row with id {} not found", row_id)));
+            dataset
+                .add_row(1, &row(column_id, "a@mail.com"))
+                .expect("Value should be available again after delete");
         }
 
-        let mut row_data: Vec<RowData> = serde_json::from_str(&rows[0])?;
+        #[test]
+        fn test_get_recent_values() {
+            let (dataset, column_id) = setup_unique_dataset(None, Some(2));
 
-        for data_item in &mut row_data {
-            if let Ok(column_id_i64) = data_item.column_id.parse::<i64>() {
-                if let Some(new_value) = updates.get(&column_id_i64) {
-                    data_item.value = new_value.clone();
-                }
+            for value in ["a", "b", "b", "c"] {
+                dataset.add_row(1, &row(column_id, value)).expect("Failed to add row");
             }
+
+            let values = dataset
+                .get_recent_values(1, column_id, 2)
+                .expect("Failed to get recent values");
+            assert_eq!(values, vec!["c", "b"]);
         }
 
-        let json_data = serde_json::to_string(&row_data)?;
+        #[test]
+        fn test_update_column_rebuilds_value_index() {
+            let (dataset, column_id) = setup_unique_dataset(None, None);
 
-        self.db.execute_transaction(&[
-            (
-                &format!(
-                    "UPDATE {} SET data = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-                    table_name
-                ),
-                &[&json_data as &dyn rusqlite::ToSql, &row_id],
-            ),
-            (
-                "UPDATE datasets_metadata SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-                &[&dataset_id as &dyn rusqlite::ToSql],
-            ),
-        ])?;
+            dataset
+                .add_row(1, &row(column_id, "a@mail.com"))
+                .expect("Failed to add row");
+            assert!(!dataset
+                .value_exists(1, column_id, "a@mail.com", UniqueMode::Exact, None)
+                .expect("Failed to check value"));
 
-        let row = self.db.query(
-            &format!(
-                "SELECT id, data, created_at, updated_at FROM {} WHERE id = ?",
-                table_name
-            ),
-            [row_id],
-            |row| {
-                let data_json: String = row.get(1)?;
-                let row_data: Vec<RowData> = serde_json::from_str(&data_json)?;
+            dataset
+                .update_column(
+                    column_id,
+                    UpdatableColumnFields {
+                        settings: Some(ColumnSettings {
+                            unique: Some(UniqueMode::Exact),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                )
+                .expect("Failed to update column");
 
-                Ok(Row {
-                    id: row.get::<_, i64>(0)?,
-                    data: row_data.into_boxed_slice(),
-                    created_at: row.get::<_, String>(2)?,
-                    updated_at: row.get::<_, String>(3)?,
+            assert!(dataset
+                .value_exists(1, column_id, "a@mail.com", UniqueMode::Exact, None)
+                .expect("Failed to check value"));
+        }
+    }
+
+    mod references {
+        use super::fixtures::{add_row, column};
+        use super::*;
+
+        /// customers(name, weight) with Alice (weight 0) and Bob (weight 5), plus an empty orders dataset
+        fn setup_reference_datasets() -> (DatasetService, DatasetMetadata, DatasetMetadata, Vec<Column>) {
+            let (dataset, customers) = fixtures::dataset("customers");
+            let orders = dataset.create("orders", "test").expect("Failed to create dataset");
+
+            let customer_columns = dataset
+                .add_columns(
+                    customers.id,
+                    &[
+                        column(&customers, "name", "TEXT", 1, ColumnSettings::default()),
+                        column(&customers, "weight", "INT", 2, ColumnSettings::default()),
+                    ],
+                )
+                .expect("Failed to add columns");
+            let (name_id, weight_id) = (customer_columns[0].id.unwrap(), customer_columns[1].id.unwrap());
+
+            for (name, weight) in [("Alice", "0"), ("Bob", "5")] {
+                add_row(&dataset, customers.id, &[(name_id, name), (weight_id, weight)]);
+            }
+
+            (dataset, customers, orders, customer_columns)
+        }
+
+        #[test]
+        fn test_sample_reference_exposes_fields() {
+            let (dataset, customers, _, columns) = setup_reference_datasets();
+
+            let sampled = dataset
+                .sample_reference(&ReferenceSettings {
+                    dataset_id: customers.id,
+                    column_id: None,
+                    weight_column_id: None,
                 })
-            },
-        )?;
+                .expect("Failed to sample reference");
 
-        row.into_iter()
-            .next()
-            .ok_or_else(|| DatasetError::NotFound(format!("Row with id {} not found", row_id)))
-    }
+            let name = sampled.fields.get("name").expect("Name should be exposed");
+            assert!(name == "Alice" || name == "Bob");
+            assert_eq!(
+                sampled.fields.get("id"),
+                Some(&sampled.value),
+                "Value defaults to the row id"
+            );
 
-    pub fn delete_row(&self, dataset_id: i64, row_id: i64) -> Result<(), DatasetError> {
-        let dataset_metadata = self.find_by_id(dataset_id)?;
-        let table_name = dataset_metadata.table_name;
+            let sampled = dataset
+                .sample_reference(&ReferenceSettings {
+                    dataset_id: customers.id,
+                    column_id: columns[0].id,
+                    weight_column_id: None,
+                })
+                .expect("Failed to sample reference");
+            assert_eq!(sampled.fields.get("name"), Some(&sampled.value));
+        }
 
-        let rows = self.db.query(
-            &format!("SELECT id FROM {} WHERE id = ?", table_name),
-            [row_id],
-            |row| {
-                row.get::<_, i64>(0)
-                    .map_err(|e| DatabaseError::SqliteError(e.to_string()))
-            },
-        )?;
+        #[test]
+        fn test_sample_reference_uses_weights() {
+            let (dataset, customers, _, columns) = setup_reference_datasets();
+
+            for _ in 0..20 {
+                let sampled = dataset
+                    .sample_reference(&ReferenceSettings {
+                        dataset_id: customers.id,
+                        column_id: columns[0].id,
+                        weight_column_id: columns[1].id,
+                    })
+                    .expect("Failed to sample reference");
+                assert_eq!(sampled.value, "Bob", "Rows with a zero weight should never be picked");
+            }
+        }
+
+        #[test]
+        fn test_sample_reference_without_rows() {
+            let (dataset, _, orders, _) = setup_reference_datasets();
+
+            let result = dataset.sample_reference(&ReferenceSettings {
+                dataset_id: orders.id,
+                column_id: None,
+                weight_column_id: None,
+            });
+            assert!(matches!(result, Err(DatasetError::NotFound(_))));
+        }
+
+        #[test]
+        fn test_validate_reference_columns() {
+            let (dataset, customers, orders, columns) = setup_reference_datasets();
+            let reference = |dataset_id: i64, column_id: Option<i64>| ColumnSettings {
+                reference: Some(ReferenceSettings {
+                    dataset_id,
+                    column_id,
+                    weight_column_id: None,
+                }),
+                ..Default::default()
+            };
+
+            let invalid = [
+                column(&orders, "customer", "REFERENCE", 1, ColumnSettings::default()),
+                column(&orders, "customer", "TEXT", 1, reference(customers.id, None)),
+                column(&orders, "customer", "REFERENCE", 1, reference(orders.id, None)),
+                column(&orders, "customer", "REFERENCE", 1, reference(999, None)),
+                column(&orders, "customer", "REFERENCE", 1, reference(customers.id, Some(999))),
+            ];
+            for column in invalid {
+                assert!(dataset.add_columns(orders.id, &[column]).is_err());
+            }
+
+            let added = dataset
+                .add_columns(
+                    orders.id,
+                    &[column(
+                        &orders,
+                        "customer",
+                        "REFERENCE",
+                        1,
+                        reference(customers.id, columns[0].id),
+                    )],
+                )
+                .expect("Failed to add reference column");
+            let column_id = added[0].id.expect("Column should have an ID");
 
-        if rows.is_empty() {
-            return Err(DatasetError::NotFound(format!("Row with id {} not found", row_id)));
+            let result = dataset.update_column(
+                column_id,
+                UpdatableColumnFields {
+                    column_type: Some("TEXT".to_string()),
+                    ..Default::default()
+                },
+            );
+            assert!(result.is_err(), "Reference settings require the REFERENCE type");
         }
 
-        self.db.execute_transaction(&[
-            (
-                &format!("DELETE FROM {} WHERE id = ?", table_name),
-                &[&row_id.to_string()],
-            ),
-            (
-                "UPDATE datasets_metadata SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-                &[&dataset_id.to_string()],
-            ),
-        ])?;
+        #[test]
+        fn test_delete_referenced_dataset() {
+            let (dataset, customers, orders, columns) = setup_reference_datasets();
+            let settings = ColumnSettings {
+                reference: Some(ReferenceSettings {
+                    dataset_id: customers.id,
+                    column_id: columns[0].id,
+                    weight_column_id: None,
+                }),
+                ..Default::default()
+            };
+            let added = dataset
+                .add_columns(orders.id, &[column(&orders, "customer", "REFERENCE", 1, settings)])
+                .expect("Failed to add reference column");
+
+            assert!(matches!(
+                dataset.delete(customers.id),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert!(dataset.find_by_id(customers.id).is_ok());
 
-        Ok(())
+            dataset
+                .delete_column(added[0].id.unwrap())
+                .expect("Failed to delete column");
+            dataset
+                .delete(customers.id)
+                .expect("Unreferenced datasets can be deleted");
+        }
     }
 
-    pub fn count_rows(&self, table_name: &str) -> Result<i64, DatasetError> {
-        let rows = self
-            .db
-            .query(&format!("SELECT COUNT(*) FROM {}", table_name), [], |row| {
-                Ok(row.get::<_, i64>(0)?)
-            })?;
-
-        rows.into_iter()
-            .next()
-            .ok_or_else(|| DatasetError::DatabaseError("Failed to retrieve row count".to_string()))
-    }
-}
+    mod relations {
+        use super::*;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json;
+        fn relation(parent_dataset_id: i64, child_dataset_id: i64, min: usize, max: usize) -> DatasetRelation {
+            DatasetRelation {
+                parent_dataset_id,
+                child_dataset_id,
+                min_children: min,
+                max_children: max,
+            }
+        }
 
-    mod creation {
-        use crate::services::ModelService;
+        fn setup_relation_datasets() -> (DatasetService, i64, i64, i64) {
+            let (dataset, customers) = fixtures::dataset("customers");
+            let orders = dataset.create("orders", "test").expect("Failed to create dataset");
+            let items = dataset.create("items", "test").expect("Failed to create dataset");
 
-        use super::*;
+            (dataset, customers.id, orders.id, items.id)
+        }
 
         #[test]
-        fn test_create_dataset_metadata_default_table() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
-
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute("DROP TABLE IF EXISTS models", [])
-                    .expect("Failed to delete models table");
-                conn.execute("DROP TABLE IF EXISTS columns", [])
-                    .expect("Failed to delete columns table");
-                conn.execute("DROP TABLE IF EXISTS datasets_metadata", [])
-                    .expect("Failed to delete datasets_metadata table");
-            }
+        fn test_set_and_get_relations() {
+            let (dataset, customers, orders, items) = setup_relation_datasets();
 
             dataset
-                .create_dataset_metadata_default_table()
-                .expect("Failed to create datasets_metadata table");
-
-            let conn = dataset.db.conn.lock().unwrap();
-
-            let mut datasets_metadata_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='datasets_metadata'")
-                .expect("Failed to prepare query");
+                .set_relation(&relation(customers, orders, 1, 5))
+                .expect("Failed to set relation");
+            dataset
+                .set_relation(&relation(orders, items, 1, 10))
+                .expect("Failed to set relation");
 
-            let mut datasets_metadata_index_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='index' AND name='idx_datasets_metadata_name'")
-                .expect("Failed to prepare query");
-            let datasets_metadata_exists: bool = datasets_metadata_stmt
-                .exists([])
-                .expect("Failed to check if table exists");
-            let datasets_metadata_index_exists: bool = datasets_metadata_index_stmt
-                .exists([])
-                .expect("Failed to check if index exists");
+            assert_eq!(
+                dataset.get_child_relations(customers).expect("Failed to get relations"),
+                vec![relation(customers, orders, 1, 5)]
+            );
+            assert_eq!(
+                dataset.get_parent_relation(items).expect("Failed to get relation"),
+                Some(relation(orders, items, 1, 10))
+            );
+            assert_eq!(
+                dataset.get_relations(orders).expect("Failed to get relations"),
+                vec![relation(customers, orders, 1, 5), relation(orders, items, 1, 10)]
+            );
 
-            assert!(
-                datasets_metadata_index_exists,
-                "datasets_metadata index was not created"
+            dataset
+                .set_relation(&relation(customers, orders, 2, 3))
+                .expect("Setting the relation again should update it");
+            assert_eq!(
+                dataset.get_parent_relation(orders).expect("Failed to get relation"),
+                Some(relation(customers, orders, 2, 3))
             );
-            assert!(datasets_metadata_exists, "datasets_metadata table was not created");
+
+            dataset.remove_relation(items).expect("Failed to remove relation");
+            assert!(dataset
+                .get_child_relations(orders)
+                .expect("Failed to get relations")
+                .is_empty());
         }
 
         #[test]
-        fn test_create_columns_default_table() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_set_relation_rejects_invalid_relations() {
+            let (dataset, customers, orders, items) = setup_relation_datasets();
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute("DROP TABLE IF EXISTS models", [])
-                    .expect("Failed to delete models table");
-                conn.execute("DROP TABLE IF EXISTS columns", [])
-                    .expect("Failed to delete columns table");
-                conn.execute("DROP TABLE IF EXISTS datasets_metadata", [])
-                    .expect("Failed to delete datasets_metadata table");
-            }
+            assert!(dataset.set_relation(&relation(customers, orders, 5, 1)).is_err());
+            assert!(dataset.set_relation(&relation(customers, customers, 1, 1)).is_err());
+            assert!(dataset.set_relation(&relation(customers, 999, 1, 1)).is_err());
 
             dataset
-                .create_columns_default_table()
-                .expect("Failed to create columns table");
+                .set_relation(&relation(customers, orders, 1, 5))
+                .expect("Failed to set relation");
+            dataset
+                .set_relation(&relation(orders, items, 1, 10))
+                .expect("Failed to set relation");
 
-            let conn = dataset.db.conn.lock().unwrap();
+            let cycle = dataset.set_relation(&relation(items, customers, 1, 1));
+            assert!(cycle.is_err(), "Relations should not form a cycle");
+        }
 
-            let mut columns_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='columns'")
-                .expect("Failed to prepare query");
-            let mut columns_index_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='index' AND name='idx_column_position'")
-                .expect("Failed to prepare query");
+        #[test]
+        fn test_delete_dataset_removes_relations() {
+            let (dataset, customers, orders, _) = setup_relation_datasets();
 
-            let columns_exists: bool = columns_stmt.exists([]).expect("Failed to check if table exists");
-            let columns_index_exists: bool = columns_index_stmt.exists([]).expect("Failed to check if index exists");
+            dataset
+                .set_relation(&relation(customers, orders, 1, 5))
+                .expect("Failed to set relation");
+            dataset.delete(customers).expect("Failed to delete dataset");
 
-            assert!(columns_index_exists, "columns index was not created");
-            assert!(columns_exists, "columns table was not created");
+            assert_eq!(
+                dataset.get_parent_relation(orders).expect("Failed to get relation"),
+                None
+            );
         }
+    }
 
-        #[test]
-        fn test_new_dataset_connection_to_database() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
-            let _ = ModelService::new(None, dataset.db.clone()).expect("Failed to create model service");
+    mod distributions {
+        use super::*;
 
-            let conn = dataset.db.conn.lock().unwrap();
+        fn numeric_column(metadata: &DatasetMetadata, column_type: &str, distribution: DistributionSettings) -> Column {
+            let settings = ColumnSettings {
+                distribution: Some(distribution),
+                ..Default::default()
+            };
+            fixtures::column(metadata, "age", column_type, 1, settings)
+        }
 
-            let mut datasets_metadata_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='datasets_metadata'")
-                .expect("Failed to prepare query");
-            let datasets_metadata_exists: bool = datasets_metadata_stmt
-                .exists([])
-                .expect("Failed to check if table exists");
-            assert!(datasets_metadata_exists, "datasets_metadata table was not created");
+        fn buckets() -> DistributionSettings {
+            DistributionSettings {
+                min: Some(0.0),
+                max: Some(100.0),
+                buckets: vec![
+                    HistogramBucket {
+                        min: 0.0,
+                        max: 49.0,
+                        share: 1.0,
+                    },
+                    HistogramBucket {
+                        min: 50.0,
+                        max: 100.0,
+                        share: 3.0,
+                    },
+                ],
+                ..Default::default()
+            }
+        }
 
-            let mut columns_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='columns'")
-                .expect("Failed to prepare query");
-            let columns_exists: bool = columns_stmt.exists([]).expect("Failed to check if table exists");
-            assert!(columns_exists, "columns table was not created");
+        #[test]
+        fn test_distribution_validation() {
+            assert!(buckets().validate().is_ok());
+
+            let invalid = [
+                DistributionSettings {
+                    min: Some(10.0),
+                    max: Some(1.0),
+                    ..Default::default()
+                },
+                DistributionSettings {
+                    mean: Some(10.0),
+                    ..Default::default()
+                },
+                DistributionSettings {
+                    mean: Some(10.0),
+                    stddev: Some(-1.0),
+                    ..Default::default()
+                },
+                DistributionSettings {
+                    buckets: vec![HistogramBucket {
+                        min: 0.0,
+                        max: 10.0,
+                        share: 0.0,
+                    }],
+                    ..Default::default()
+                },
+            ];
 
-            let mut models_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='models'")
-                .expect("Failed to prepare query");
-            let models_exists: bool = models_stmt.exists([]).expect("Failed to check if table exists");
-            assert!(models_exists, "models table was not created");
+            for distribution in invalid {
+                assert!(
+                    matches!(distribution.validate(), Err(DatasetError::InvalidInput(_))),
+                    "{:?}",
+                    distribution
+                );
+            }
         }
 
         #[test]
-        fn test_create_dataset() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_distribution_requires_numeric_column() {
+            let (dataset, metadata) = fixtures::dataset("people");
 
-            let dataset_result = dataset.create("test", "test").expect("Failed to create dataset");
-            assert!(dataset_result.name == "test", "Failed to create dataset");
-            assert!(dataset_result.description == "test", "Failed to create dataset");
+            let result = dataset.add_columns(metadata.id, &[numeric_column(&metadata, "TEXT", buckets())]);
+            assert!(matches!(result, Err(DatasetError::InvalidInput(_))));
 
-            let conn = dataset.db.conn.lock().unwrap();
+            let columns = dataset
+                .add_columns(metadata.id, &[numeric_column(&metadata, "INT", buckets())])
+                .expect("Failed to add column");
 
-            let mut datasets_metadata_stmt = conn
-                .prepare(&format!(
-                    "SELECT name FROM datasets_metadata WHERE id = {}",
-                    dataset_result.id
-                ))
-                .expect("Failed to prepare query");
+            let result = dataset.update_column(
+                columns[0].id.unwrap(),
+                UpdatableColumnFields {
+                    column_type: Some("TEXT".to_string()),
+                    ..Default::default()
+                },
+            );
+            assert!(matches!(result, Err(DatasetError::InvalidInput(_))));
+        }
 
-            let datasets_metadata_exists: bool = datasets_metadata_stmt
-                .exists([])
-                .expect("Failed to check if dataset exists");
+        #[test]
+        fn test_distribution_report() {
+            let (dataset, metadata) = fixtures::dataset("people");
+            let columns = dataset
+                .add_columns(metadata.id, &[numeric_column(&metadata, "INT", buckets())])
+                .expect("Failed to add column");
+            let column_id = columns[0].id.unwrap();
+
+            for value in ["10", "60", "70", "120", "not a number"] {
+                fixtures::add_row(&dataset, metadata.id, &[(column_id, value)]);
+            }
 
-            assert!(datasets_metadata_exists, "dataset was not created");
+            let reports = dataset
+                .get_distribution_report(metadata.id)
+                .expect("Failed to build report");
+            assert_eq!(reports.len(), 1);
+
+            let report = &reports[0];
+            assert_eq!(report.column_id, column_id);
+            assert_eq!(report.count, 4);
+            assert_eq!(report.min, Some(10.0));
+            assert_eq!(report.max, Some(120.0));
+            assert_eq!(report.mean, Some(65.0));
+            assert_eq!(report.out_of_range, 1);
+            assert_eq!(report.buckets[0].target_share, 0.25);
+            assert_eq!(report.buckets[0].realized_share, 0.25);
+            assert_eq!(report.buckets[1].target_share, 0.75);
+            assert_eq!(report.buckets[1].realized_share, 0.5);
         }
     }
 
-    mod queries {
+    mod pii {
+        use super::fixtures::{add_row, column};
         use super::*;
 
-        #[test]
-        fn test_find_by_id_dataset() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn setup_pii_dataset(pii_policy: PiiPolicy) -> (DatasetService, DatasetMetadata, i64) {
+            let (dataset, metadata) = fixtures::dataset("contacts");
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset_test", "test", "test"],
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "contact", "TEXT", 1, ColumnSettings::default())],
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to add column");
+
+            dataset
+                .update_settings(
+                    metadata.id,
+                    &DatasetSettings {
+                        pii_policy,
+                        ..Default::default()
+                    },
+                )
+                .expect("Failed to update settings");
+
+            (dataset, metadata, columns[0].id.unwrap())
+        }
+
+        #[test]
+        fn test_flag_pii_builds_report() {
+            let (dataset, metadata, column_id) = setup_pii_dataset(PiiPolicy::Flag);
+
+            for value in [
+                "Reach me at john.doe@gmail.com or 123-45-6789",
+                "jane@example.com",
+                "4539 1488 0343 6467",
+            ] {
+                let row = add_row(&dataset, metadata.id, &[(column_id, value)]);
+                dataset.flag_pii(metadata.id, &row).expect("Failed to flag row");
             }
 
-            let dataset_result = dataset.find_by_id(1).expect("Failed to find dataset");
+            let report = dataset.get_pii_report(metadata.id).expect("Failed to build report");
+            assert_eq!(report.total_flags, 3);
+            assert_eq!(report.flagged_rows, 2);
+            assert_eq!(report.by_kind.get(&PiiKind::Email), Some(&1));
+            assert_eq!(report.by_kind.get(&PiiKind::Ssn), Some(&1));
+            assert_eq!(report.by_kind.get(&PiiKind::CreditCard), Some(&1));
+            assert_eq!(report.flags[0].value, "john.doe@gmail.com");
+        }
 
-            assert!(dataset_result.name == "test", "Failed to find dataset");
+        #[test]
+        fn test_flag_pii_skipped_when_off() {
+            let (dataset, metadata, column_id) = setup_pii_dataset(PiiPolicy::Off);
+
+            let row = add_row(&dataset, metadata.id, &[(column_id, "john.doe@gmail.com")]);
+            assert_eq!(dataset.flag_pii(metadata.id, &row).unwrap(), 0);
+            assert_eq!(dataset.get_pii_report(metadata.id).unwrap().total_flags, 0);
         }
 
         #[test]
-        fn test_find_all_dataset() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_row_changes_clear_flags() {
+            let (dataset, metadata, column_id) = setup_pii_dataset(PiiPolicy::Regenerate);
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset_test", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset_test", "test2", "test2"],
-                )
-                .expect("Failed to insert dataset");
+            let edited = add_row(&dataset, metadata.id, &[(column_id, "john.doe@gmail.com")]);
+            let deleted = add_row(&dataset, metadata.id, &[(column_id, "123-45-6789")]);
+            dataset.flag_pii(metadata.id, &edited).unwrap();
+            dataset.flag_pii(metadata.id, &deleted).unwrap();
+            assert_eq!(dataset.get_pii_report(metadata.id).unwrap().total_flags, 2);
+
+            let updates = HashMap::from([(column_id, Some("john.doe@example.com".to_string()))]);
+            dataset
+                .update_row(metadata.id, edited.id, &updates)
+                .expect("Failed to update row");
+            dataset
+                .delete_row(metadata.id, deleted.id)
+                .expect("Failed to delete row");
+
+            assert_eq!(dataset.get_pii_report(metadata.id).unwrap().total_flags, 0);
+        }
+    }
+
+    mod provenance {
+        use super::*;
+
+        fn provenance(model_id: i64, generation_id: &str, rules_hash: &str) -> CellProvenance {
+            CellProvenance {
+                model_id: Some(model_id),
+                generation_id: Some(generation_id.to_string()),
+                prompt_hash: "0123456789abcdef".to_string(),
+                rules_hash: rules_hash.to_string(),
+                attempts: 2,
+                seed: Some(42),
+                created_at: None,
             }
+        }
 
-            let dataset_results = dataset.find_all().expect("Failed to find dataset");
+        /// Dataset with a `name` and an `age` column and one row per `(model_id, generation_id, rules_hash)`
+        fn setup_provenance_dataset(
+            cells: &[(i64, &str, &str)],
+        ) -> (DatasetService, DatasetMetadata, Vec<i64>, Vec<i64>) {
+            let (dataset, metadata, name_id, age_id) = fixtures::setup(
+                "people",
+                [
+                    ("name", "TEXT", ColumnSettings::default()),
+                    ("age", "TEXT", ColumnSettings::default()),
+                ],
+            );
+            let column_ids = vec![name_id, age_id];
+
+            let mut row_ids = Vec::new();
+            for (model_id, generation_id, rules_hash) in cells {
+                let data: Vec<RowData> = column_ids
+                    .iter()
+                    .map(|column_id| RowData {
+                        column_id: column_id.to_string(),
+                        value: Some("value".to_string()),
+                    })
+                    .collect();
+                let cells = HashMap::from([(
+                    column_ids[0].to_string(),
+                    provenance(*model_id, generation_id, rules_hash),
+                )]);
+                let row = dataset
+                    .add_row_with_provenance(metadata.id, &data, &cells)
+                    .expect("Failed to add row");
+                row_ids.push(row.id);
+            }
 
-            assert!(dataset_results.len() == 2, "Failed to find dataset");
-            assert!(dataset_results[0].name == "test", "Failed to find dataset");
-            assert!(dataset_results[1].name == "test2", "Failed to find dataset");
+            (dataset, metadata, column_ids, row_ids)
         }
 
         #[test]
-        fn test_get_all_rows() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
-
-            {
-                let conn = dataset.db.conn.lock().unwrap();
+        fn test_record_and_get_cell_provenance() {
+            let (dataset, metadata, column_ids, row_ids) = setup_provenance_dataset(&[(3, "gen_1", "aaaa")]);
+
+            let cell = dataset
+                .get_cell_provenance(metadata.id, row_ids[0], column_ids[0])
+                .expect("Failed to get provenance")
+                .expect("Cell should have provenance");
+            assert_eq!(cell.model_id, Some(3));
+            assert_eq!(cell.generation_id.as_deref(), Some("gen_1"));
+            assert_eq!(cell.attempts, 2);
+            assert_eq!(cell.seed, Some(42));
+            assert!(cell.created_at.is_some());
+
+            let missing = dataset
+                .get_cell_provenance(metadata.id, row_ids[0], column_ids[1])
+                .expect("Failed to get provenance");
+            assert_eq!(missing, None);
+        }
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset_test (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )
-                .expect("failed to create database");
+        #[test]
+        fn test_find_rows_by_provenance() {
+            let (dataset, metadata, column_ids, row_ids) =
+                setup_provenance_dataset(&[(1, "gen_1", "aaaa"), (2, "gen_2", "aaaa"), (2, "gen_2", "bbbb")]);
+
+            let by_model = ProvenanceFilter {
+                model_id: Some(2),
+                ..Default::default()
+            };
+            assert_eq!(
+                dataset.find_rows_by_provenance(metadata.id, &by_model).unwrap(),
+                vec![row_ids[1], row_ids[2]]
+            );
 
-                conn.execute(
-                    "INSERT INTO dataset_test (data) VALUES (?)",
-                    [r#"[{"column_id":"1","value":"test"}]"#],
-                )
-                .expect("Failed to insert row 1");
+            let by_rules = ProvenanceFilter {
+                column_id: Some(column_ids[0]),
+                rules_hash: Some("aaaa".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                dataset.find_rows_by_provenance(metadata.id, &by_rules).unwrap(),
+                vec![row_ids[0], row_ids[1]]
+            );
 
-                conn.execute(
-                    "INSERT INTO dataset_test (data) VALUES (?)",
-                    [r#"[{"column_id":"1","value":"hello"},{"column_id":"2","value":"world"}]"#],
-                )
-                .expect("Failed to insert row 2");
+            let other_column = ProvenanceFilter {
+                column_id: Some(column_ids[1]),
+                ..Default::default()
+            };
+            assert!(dataset
+                .find_rows_by_provenance(metadata.id, &other_column)
+                .unwrap()
+                .is_empty());
+        }
 
-                conn.execute(
-                    "INSERT INTO dataset_test (data) VALUES (?)",
-                    [r#"[{"column_id":"1","value":"test with \"quotes\""},{"column_id":"2","value":"123"},{"column_id":"3","value":"special: !@#$%"}]"#],
-                )
-                .expect("Failed to insert row 3");
+        #[test]
+        fn test_row_changes_clear_provenance() {
+            let (dataset, metadata, column_ids, row_ids) =
+                setup_provenance_dataset(&[(1, "gen_1", "aaaa"), (1, "gen_1", "aaaa")]);
 
-                conn.execute(
-                    "INSERT INTO dataset_test (data) VALUES (?)",
-                    [r#"[{"column_id":"1","value":""}]"#],
-                )
-                .expect("Failed to insert row 4");
+            let untouched_column = HashMap::from([(column_ids[1], Some("edited".to_string()))]);
+            dataset
+                .update_row(metadata.id, row_ids[0], &untouched_column)
+                .expect("Failed to update row");
+            assert!(dataset
+                .get_cell_provenance(metadata.id, row_ids[0], column_ids[0])
+                .unwrap()
+                .is_some());
+
+            let edited_column = HashMap::from([(column_ids[0], Some("edited".to_string()))]);
+            dataset
+                .update_row(metadata.id, row_ids[0], &edited_column)
+                .expect("Failed to update row");
+            assert!(dataset
+                .get_cell_provenance(metadata.id, row_ids[0], column_ids[0])
+                .unwrap()
+                .is_none());
 
-                let large_text = "a".repeat(1000);
-                let large_row_data = format!(r#"[{{"column_id":"1","value":"{}"}}]"#, large_text);
-                conn.execute("INSERT INTO dataset_test (data) VALUES (?)", [large_row_data.as_str()])
-                    .expect("Failed to insert row 5");
-            }
+            dataset
+                .delete_row(metadata.id, row_ids[1])
+                .expect("Failed to delete row");
+            assert!(dataset
+                .find_rows_by_provenance(metadata.id, &ProvenanceFilter::default())
+                .unwrap()
+                .is_empty());
+        }
+    }
 
-            let rows = dataset.get_all_rows("dataset_test").expect("Failed to get all rows");
-            assert_eq!(rows.len(), 5, "Should return exactly 5 rows");
+    mod row_queries {
+        use super::*;
 
-            for i in 0..rows.len() {
-                assert_eq!(rows[i].id, (i + 1) as i64, "Rows should be ordered by id ascending");
+        fn setup_people_dataset() -> (DatasetService, i64, i64, i64) {
+            let (dataset, metadata, name_id, age_id) = fixtures::setup(
+                "people",
+                [
+                    ("name", "TEXT", ColumnSettings::default()),
+                    ("age", "INT", ColumnSettings::default()),
+                ],
+            );
+
+            for (name, age) in [
+                ("Alice", "34"),
+                ("bob", "9"),
+                ("Carol_1", "100"),
+                ("dave", ""),
+                ("Eve", "27"),
+            ] {
+                fixtures::add_row(&dataset, metadata.id, &[(name_id, name), (age_id, age)]);
             }
 
-            assert_eq!(rows[0].data.len(), 1, "First row should have 1 column");
-            assert_eq!(rows[0].data[0].column_id, "1", "First row column_id should be '1'");
-            assert_eq!(rows[0].data[0].value, "test", "First row value should be 'test'");
+            (dataset, metadata.id, name_id, age_id)
+        }
 
-            assert_eq!(rows[1].data.len(), 2, "Second row should have 2 columns");
+        fn names(response: &PaginatedResponse, name_id: i64) -> Vec<String> {
+            let name_id = name_id.to_string();
+            response
+                .data
+                .iter()
+                .map(|row| {
+                    row["data"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .find(|cell| cell["columnId"] == name_id.as_str())
+                        .map(|cell| cell["value"].as_str().unwrap().to_string())
+                        .unwrap()
+                })
+                .collect()
+        }
+
+        fn filter(column_id: i64, operator: FilterOperator) -> RowFilter {
+            RowFilter {
+                column_id,
+                operator,
+                value: None,
+                min: None,
+                max: None,
+            }
+        }
+
+        #[test]
+        fn test_sort_is_type_aware() {
+            let (dataset, dataset_id, name_id, age_id) = setup_people_dataset();
+
+            let by_age = RowQuery {
+                sort: Some(RowSort {
+                    column_id: age_id,
+                    descending: false,
+                }),
+                ..Default::default()
+            };
+            let response = dataset.query_rows(dataset_id, 1, 10, &by_age).unwrap();
             assert_eq!(
-                rows[1].data[0].column_id, "1",
-                "Second row first column_id should be '1'"
+                names(&response, name_id),
+                vec!["bob", "Eve", "Alice", "Carol_1", "dave"]
             );
+
+            let by_name_desc = RowQuery {
+                sort: Some(RowSort {
+                    column_id: name_id,
+                    descending: true,
+                }),
+                ..Default::default()
+            };
+            let response = dataset.query_rows(dataset_id, 1, 10, &by_name_desc).unwrap();
             assert_eq!(
-                rows[1].data[0].value, "hello",
-                "Second row first value should be 'hello'"
+                names(&response, name_id),
+                vec!["Eve", "dave", "Carol_1", "bob", "Alice"]
             );
+        }
+
+        #[test]
+        fn test_filters_and_total_rows() {
+            let (dataset, dataset_id, name_id, age_id) = setup_people_dataset();
+
+            let query = RowQuery {
+                filters: vec![RowFilter {
+                    min: Some("10".to_string()),
+                    max: Some("50".to_string()),
+                    ..filter(age_id, FilterOperator::Range)
+                }],
+                ..Default::default()
+            };
+            let response = dataset.query_rows(dataset_id, 1, 1, &query).unwrap();
+            assert_eq!(response.total_rows, 2);
+            assert_eq!(response.total_pages, 2);
+            assert!(response.has_next);
+            assert_eq!(names(&response, name_id), vec!["Alice"]);
+
+            let query = RowQuery {
+                filters: vec![RowFilter {
+                    value: Some(" ALICE ".to_string()),
+                    ..filter(name_id, FilterOperator::Equals)
+                }],
+                ..Default::default()
+            };
             assert_eq!(
-                rows[1].data[1].column_id, "2",
-                "Second row second column_id should be '2'"
+                names(&dataset.query_rows(dataset_id, 1, 10, &query).unwrap(), name_id),
+                vec!["Alice"]
             );
+
+            let query = RowQuery {
+                filters: vec![RowFilter {
+                    value: Some("100.0".to_string()),
+                    ..filter(age_id, FilterOperator::Equals)
+                }],
+                ..Default::default()
+            };
             assert_eq!(
-                rows[1].data[1].value, "world",
-                "Second row second value should be 'world'"
+                names(&dataset.query_rows(dataset_id, 1, 10, &query).unwrap(), name_id),
+                vec!["Carol_1"]
             );
 
-            assert_eq!(rows[2].data.len(), 3, "Third row should have 3 columns");
+            let query = RowQuery {
+                filters: vec![filter(age_id, FilterOperator::IsEmpty)],
+                ..Default::default()
+            };
             assert_eq!(
-                rows[2].data[0].value, "test with \"quotes\"",
-                "Should handle escaped quotes"
+                names(&dataset.query_rows(dataset_id, 1, 10, &query).unwrap(), name_id),
+                vec!["dave"]
             );
-            assert_eq!(rows[2].data[1].value, "123", "Should handle numeric strings");
+        }
+
+        #[test]
+        fn test_contains_and_search_escape_wildcards() {
+            let (dataset, dataset_id, name_id, _) = setup_people_dataset();
+
+            let query = RowQuery {
+                filters: vec![RowFilter {
+                    value: Some("_".to_string()),
+                    ..filter(name_id, FilterOperator::Contains)
+                }],
+                ..Default::default()
+            };
             assert_eq!(
-                rows[2].data[2].value, "special: !@#$%",
-                "Should handle special characters"
+                names(&dataset.query_rows(dataset_id, 1, 10, &query).unwrap(), name_id),
+                vec!["Carol_1"]
             );
 
-            assert_eq!(rows[3].data.len(), 1, "Fourth row should have 1 column");
-            assert_eq!(rows[3].data[0].value, "", "Should handle empty string values");
-
-            assert_eq!(rows[4].data.len(), 1, "Fifth row should have 1 column");
-            assert_eq!(rows[4].data[0].value.len(), 1000, "Should handle large text values");
-            assert!(
-                rows[4].data[0].value.chars().all(|c| c == 'a'),
-                "Large text should be all 'a's"
+            let query = RowQuery {
+                search: Some("E".to_string()),
+                ..Default::default()
+            };
+            let response = dataset.query_rows(dataset_id, 1, 10, &query).unwrap();
+            assert_eq!(response.total_rows, 3);
+            assert_eq!(names(&response, name_id), vec!["Alice", "dave", "Eve"]);
+
+            let query = RowQuery {
+                search: Some("10".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                names(&dataset.query_rows(dataset_id, 1, 10, &query).unwrap(), name_id),
+                vec!["Carol_1"]
             );
+        }
 
-            for (idx, row) in rows.iter().enumerate() {
-                assert!(
-                    !row.created_at.is_empty(),
-                    "Row {} should have created_at timestamp",
-                    idx
-                );
-                assert!(
-                    !row.updated_at.is_empty(),
-                    "Row {} should have updated_at timestamp",
-                    idx
-                );
-            }
+        #[test]
+        fn test_invalid_queries_are_rejected() {
+            let (dataset, dataset_id, _, age_id) = setup_people_dataset();
+
+            let unknown_column = RowQuery {
+                filters: vec![filter(999, FilterOperator::IsEmpty)],
+                ..Default::default()
+            };
+            assert!(matches!(
+                dataset.query_rows(dataset_id, 1, 10, &unknown_column),
+                Err(DatasetError::InvalidInput(_))
+            ));
 
-            let mut ids: Vec<i64> = rows.iter().map(|r| r.id).collect();
-            ids.sort();
-            ids.dedup();
-            assert_eq!(ids.len(), 5, "All rows should have unique ids");
+            let not_a_number = RowQuery {
+                filters: vec![RowFilter {
+                    min: Some("old".to_string()),
+                    ..filter(age_id, FilterOperator::Range)
+                }],
+                ..Default::default()
+            };
+            assert!(matches!(
+                dataset.query_rows(dataset_id, 1, 10, &not_a_number),
+                Err(DatasetError::InvalidInput(_))
+            ));
+
+            let open_range = RowQuery {
+                filters: vec![filter(age_id, FilterOperator::Range)],
+                ..Default::default()
+            };
+            assert!(matches!(
+                dataset.query_rows(dataset_id, 1, 10, &open_range),
+                Err(DatasetError::InvalidInput(_))
+            ));
         }
     }
 
-    mod updates {
+    mod search {
+        use super::fixtures::add_row;
         use super::*;
 
-        #[test]
-        fn test_update_dataset() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
-
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset_test", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
-            }
-
-            let updated_dataset = dataset
-                .update(1, Some("testUpdated0"), Some("testUpdated"))
-                .expect("Failed to update dataset");
-            assert!(updated_dataset.name == "testUpdated0", "Failed to update dataset");
-            assert!(updated_dataset.description == "testUpdated", "Failed to update dataset");
+        fn setup_notes_dataset() -> (DatasetService, DatasetMetadata, i64, i64) {
+            fixtures::setup(
+                "notes",
+                [
+                    ("title", "TEXT", ColumnSettings::default()),
+                    ("body", "TEXT", ColumnSettings::default()),
+                ],
+            )
+        }
 
-            let conn = dataset.db.conn.lock().unwrap();
+        fn row_ids(hits: &[RowSearchHit]) -> Vec<i64> {
+            hits.iter().map(|hit| hit.row_id).collect()
+        }
 
-            let result = conn
-                .query_row("SELECT * FROM datasets_metadata WHERE id = 1", [], |row| {
-                    Ok(DatasetMetadata {
-                        id: row.get(0)?,
-                        table_name: row.get(1)?,
-                        name: row.get(2)?,
-                        description: row.get(3)?,
-                        created_at: row.get(4)?,
-                        updated_at: row.get(5)?,
-                        row_count: 0,
-                    })
-                })
-                .expect("Failed to query dataset");
+        #[test]
+        fn test_search_ranks_and_highlights() {
+            let (dataset, metadata, title_id, body_id) = setup_notes_dataset();
 
-            assert_eq!(result.name, "testUpdated0", "Failed to update dataset");
-            assert_eq!(result.description, "testUpdated", "Failed to update dataset");
+            let once = add_row(
+                &dataset,
+                metadata.id,
+                &[(title_id, "Groceries"), (body_id, "Buy apples and bread")],
+            );
+            let twice = add_row(
+                &dataset,
+                metadata.id,
+                &[(title_id, "Apple pie"), (body_id, "Peel the apples first")],
+            );
+            add_row(&dataset, metadata.id, &[(title_id, "Café"), (body_id, "Espresso")]);
+
+            let hits = dataset.search_rows(metadata.id, "apple", 10).unwrap();
+            assert_eq!(row_ids(&hits), vec![twice.id, once.id]);
+            assert!(hits[0].score >= hits[1].score);
+            assert!(hits[0].snippet.contains("<mark>Apple</mark>"), "{}", hits[0].snippet);
+
+            assert_eq!(dataset.search_rows(metadata.id, "apple bread", 10).unwrap().len(), 1);
+            assert_eq!(dataset.search_rows(metadata.id, "cafe", 10).unwrap().len(), 1);
+            assert_eq!(dataset.search_rows(metadata.id, "apple", 1).unwrap().len(), 1);
+            assert!(dataset.search_rows(metadata.id, "  ", 10).unwrap().is_empty());
+            assert!(dataset
+                .search_rows(metadata.id, "\"apple\" OR NEAR(", 10)
+                .unwrap()
+                .is_empty());
         }
 
         #[test]
-        fn test_delete_dataset() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_index_follows_row_changes() {
+            let (dataset, metadata, title_id, body_id) = setup_notes_dataset();
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset_test", "test", "test"],
+            let first = add_row(&dataset, metadata.id, &[(title_id, "Draft"), (body_id, "lorem")]);
+            let second = add_row(&dataset, metadata.id, &[(title_id, "Draft"), (body_id, "ipsum")]);
+
+            dataset
+                .update_row(
+                    metadata.id,
+                    first.id,
+                    &HashMap::from([(body_id, Some("dolor".to_string()))]),
                 )
-                .expect("Failed to insert dataset");
-            }
+                .expect("Failed to update row");
+            assert!(dataset.search_rows(metadata.id, "lorem", 10).unwrap().is_empty());
+            assert_eq!(
+                row_ids(&dataset.search_rows(metadata.id, "dolor", 10).unwrap()),
+                vec![first.id]
+            );
 
-            let deleted_dataset = dataset.delete(1);
-            assert!(deleted_dataset.is_ok(), "Failed to delete dataset");
+            dataset
+                .delete_row(metadata.id, second.id)
+                .expect("Failed to delete row");
+            assert_eq!(
+                row_ids(&dataset.search_rows(metadata.id, "draft", 10).unwrap()),
+                vec![first.id]
+            );
 
-            let conn = dataset.db.conn.lock().unwrap();
+            dataset.delete_column(title_id).expect("Failed to delete column");
+            assert!(dataset.search_rows(metadata.id, "draft", 10).unwrap().is_empty());
+            assert_eq!(dataset.search_rows(metadata.id, "dolor", 10).unwrap().len(), 1);
 
-            let mut datasets_metadata_stmt = conn
-                .prepare("SELECT * FROM datasets_metadata WHERE id = 1")
-                .expect("Failed to prepare query");
-            let datasets_metadata_exists: bool = datasets_metadata_stmt
-                .exists([])
-                .expect("Failed to check if dataset exists");
+            let rows = dataset.get_all_rows(&metadata.table_name).expect("Failed to get rows");
+            assert_eq!(
+                rows[0].data.len(),
+                1,
+                "Deleted column should be removed from the row data"
+            );
+        }
 
-            assert!(!datasets_metadata_exists, "dataset was not deleted");
+        #[test]
+        fn test_existing_rows_are_backfilled() {
+            let (dataset, metadata, title_id, body_id) = setup_notes_dataset();
+            let row = add_row(&dataset, metadata.id, &[(title_id, "Legacy"), (body_id, "row")]);
+
+            dataset
+                .db
+                .execute(&format!("DROP TABLE {}_fts", metadata.table_name), [])
+                .expect("Failed to drop search index");
+
+            assert_eq!(
+                row_ids(&dataset.search_rows(metadata.id, "legacy", 10).unwrap()),
+                vec![row.id]
+            );
         }
     }
 
-    mod columns {
+    mod columnar_storage {
+        use super::fixtures::{add_row, column};
         use super::*;
 
+        fn values(dataset: &DatasetService, table_name: &str) -> Vec<Vec<String>> {
+            dataset
+                .get_all_rows(table_name)
+                .expect("Failed to get rows")
+                .iter()
+                .map(|row| row.data.iter().map(|cell| cell.value_or_empty().to_string()).collect())
+                .collect()
+        }
+
+        fn setup() -> (DatasetService, DatasetMetadata, i64, i64) {
+            fixtures::setup(
+                "people",
+                [
+                    ("name", "TEXT", ColumnSettings::default()),
+                    ("age", "INT", ColumnSettings::default()),
+                ],
+            )
+        }
+
         #[test]
-        fn test_dataset_add_columns() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_convert_keeps_rows() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            let first = add_row(&dataset, metadata.id, &[(name_id, "Alice"), (age_id, "34")]);
+            add_row(&dataset, metadata.id, &[(name_id, "Bob"), (age_id, "")]);
+            let before = values(&dataset, &metadata.table_name);
 
-            let columns = vec![
-                Column {
-                    id: None,
-                    table_name: "dataset001".to_string(),
-                    dataset_id: 1,
-                    name: "test".to_string(),
-                    column_type: "TEXT".to_string(),
-                    column_type_details: None,
-                    rules: "test".to_string(),
-                    position: 1,
-                },
-                Column {
-                    id: None,
-                    table_name: "dataset001".to_string(),
-                    dataset_id: 1,
-                    name: "test2".to_string(),
-                    column_type: "TEXT".to_string(),
-                    column_type_details: None,
-                    rules: "test2".to_string(),
-                    position: 2,
-                },
-            ];
+            assert_eq!(
+                dataset.storage_layout(&metadata.table_name).unwrap(),
+                StorageLayout::Json
+            );
+            assert_eq!(
+                dataset.convert_to_columnar(metadata.id).unwrap(),
+                StorageLayout::Columnar
+            );
+            assert_eq!(
+                dataset.convert_to_columnar(metadata.id).unwrap(),
+                StorageLayout::Columnar
+            );
+            assert_eq!(
+                dataset.storage_layout(&metadata.table_name).unwrap(),
+                StorageLayout::Columnar
+            );
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
+            assert_eq!(values(&dataset, &metadata.table_name), before);
+            assert_eq!(dataset.get_all_rows(&metadata.table_name).unwrap()[0].id, first.id);
+
+            let age_type = dataset
+                .db
+                .query(
+                    &format!(
+                        "SELECT typeof(c_{}) FROM {}_cols WHERE id = ?",
+                        age_id, metadata.table_name
+                    ),
+                    [first.id],
+                    |row| Ok(row.get::<_, String>(0)?),
+                )
+                .unwrap();
+            assert_eq!(age_type, vec!["integer"]);
+            assert_eq!(dataset.search_rows(metadata.id, "alice", 10).unwrap().len(), 1);
+        }
+
+        #[test]
+        fn test_typed_columns_store_numbers() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            let score_id = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "score", "FLOAT", 3, ColumnSettings::default())],
+                )
+                .expect("Failed to add column")[2]
+                .id
+                .unwrap();
+            for (name, age, score) in [
+                ("007", "5", "5"),
+                ("x", "007", "3.10"),
+                ("y", " -0 ", "0.50"),
+                ("z", "", ""),
+            ] {
+                add_row(
+                    &dataset,
+                    metadata.id,
+                    &[(name_id, name), (age_id, age), (score_id, score)],
+                );
+            }
+
+            dataset.convert_to_columnar(metadata.id).unwrap();
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![
+                    vec!["007", "5", "5.0"],
+                    vec!["x", "7", "3.1"],
+                    vec!["y", "0", "0.5"],
+                    vec!["z", "", ""],
+                ],
+                "Only TEXT columns keep their text"
+            );
+
+            let row = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "08"), (age_id, "08"), (score_id, "2.50")],
+            );
+            assert_eq!(
+                row.data.iter().map(|data| data.value_or_empty()).collect::<Vec<_>>(),
+                vec!["08", "8", "2.5"]
+            );
+
+            for (age, score) in [("abc", "1"), ("1.5", "1"), ("1", "1,5"), ("1", "inf")] {
+                let data = vec![
+                    RowData {
+                        column_id: name_id.to_string(),
+                        value: Some("w".to_string()),
+                    },
+                    RowData {
+                        column_id: age_id.to_string(),
+                        value: Some(age.to_string()),
+                    },
+                    RowData {
+                        column_id: score_id.to_string(),
+                        value: Some(score.to_string()),
+                    },
+                ];
+                assert!(
+                    matches!(dataset.add_row(metadata.id, &data), Err(DatasetError::InvalidInput(_))),
+                    "{} / {} should be refused",
+                    age,
+                    score
+                );
+            }
+            assert!(matches!(
+                dataset.update_row(
+                    metadata.id,
+                    row.id,
+                    &HashMap::from([(age_id, Some("eight".to_string()))])
+                ),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert_eq!(dataset.get_all_rows(&metadata.table_name).unwrap().len(), 5);
+        }
+
+        #[test]
+        fn test_bool_columns_store_flags() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            let active_id = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "active", "BOOL", 3, ColumnSettings::default())],
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to add column")[2]
+                .id
+                .unwrap();
+            dataset.convert_to_columnar(metadata.id).unwrap();
+            for (name, active) in [("Alice", "True"), ("Bob", "false"), ("Carol", "1")] {
+                add_row(
+                    &dataset,
+                    metadata.id,
+                    &[(name_id, name), (age_id, "1"), (active_id, active)],
+                );
             }
 
-            let added_columns = dataset.add_columns(1, &columns);
-            assert!(added_columns.is_ok(), "Failed to add columns");
-
-            let conn = dataset.db.conn.lock().unwrap();
+            let stored = dataset
+                .db
+                .query(
+                    &format!("SELECT c_{} FROM {}_cols ORDER BY id", active_id, metadata.table_name),
+                    [],
+                    |row| Ok(row.get::<_, i64>(0)?),
+                )
+                .unwrap();
+            assert_eq!(stored, vec![1, 0, 1]);
+            assert_eq!(
+                values(&dataset, &metadata.table_name)
+                    .iter()
+                    .map(|row| row[2].clone())
+                    .collect::<Vec<_>>(),
+                vec!["true", "false", "true"]
+            );
 
-            let mut columns_stmt = conn
-                .prepare("SELECT * FROM columns WHERE dataset_id = 1")
-                .expect("Failed to prepare query");
+            let query = RowQuery {
+                filters: vec![RowFilter {
+                    column_id: active_id,
+                    operator: FilterOperator::Equals,
+                    value: Some("TRUE".to_string()),
+                    min: None,
+                    max: None,
+                }],
+                ..Default::default()
+            };
+            assert_eq!(dataset.query_rows(metadata.id, 1, 10, &query).unwrap().total_rows, 2);
+            assert!(matches!(
+                dataset.add_row(
+                    metadata.id,
+                    &vec![
+                        RowData {
+                            column_id: name_id.to_string(),
+                            value: Some("Dave".to_string()),
+                        },
+                        RowData {
+                            column_id: age_id.to_string(),
+                            value: Some("1".to_string()),
+                        },
+                        RowData {
+                            column_id: active_id.to_string(),
+                            value: Some("yes".to_string()),
+                        },
+                    ]
+                ),
+                Err(DatasetError::InvalidInput(_))
+            ));
+        }
 
-            let columns_map = columns_stmt
-                .query_map([], |row| {
-                    Ok((
-                        row.get::<_, i64>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, String>(3)?,
-                        row.get::<_, String>(4)?,
-                        row.get::<_, String>(5)?,
-                        row.get::<_, i64>(6)?,
-                    ))
-                })
-                .expect("Failed to query columns");
+        #[test]
+        fn test_column_type_change_rebuilds_storage() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            add_row(&dataset, metadata.id, &[(name_id, "Alice"), (age_id, "34")]);
+            add_row(&dataset, metadata.id, &[(name_id, "Bob"), (age_id, "")]);
+            dataset.convert_to_columnar(metadata.id).unwrap();
+            let age_type = |dataset: &DatasetService| {
+                dataset
+                    .db
+                    .query(
+                        &format!(
+                            "SELECT typeof(c_{}) FROM {}_cols ORDER BY id",
+                            age_id, metadata.table_name
+                        ),
+                        [],
+                        |row| Ok(row.get::<_, String>(0)?),
+                    )
+                    .unwrap()
+            };
+
+            let retype = |column_type: &str| UpdatableColumnFields {
+                column_type: Some(column_type.to_string()),
+                ..Default::default()
+            };
+            dataset.update_column(age_id, retype("TEXT")).unwrap();
+            assert_eq!(age_type(&dataset), vec!["text", "text"]);
+            dataset
+                .update_row(
+                    metadata.id,
+                    dataset.get_all_rows(&metadata.table_name).unwrap()[1].id,
+                    &HashMap::from([(age_id, Some("unknown".to_string()))]),
+                )
+                .unwrap();
 
-            let columns_length = columns_map.count();
-            assert!(columns_length > 1, "columns were not added");
+            assert!(dataset.update_column(age_id, retype("INT")).is_err());
+            assert_eq!(dataset.find_column(age_id).unwrap().column_type, "TEXT");
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![vec!["Alice", "34"], vec!["Bob", "unknown"]]
+            );
 
-            let mut dataset_001_stmt = conn
-                .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name='dataset001'")
-                .expect("Failed to prepare query");
-            let dataset_001_exists: bool = dataset_001_stmt
-                .exists([])
-                .expect("Failed to check if dataset_001 exists");
-            assert!(dataset_001_exists, "dataset001 was not created");
+            dataset.undo(metadata.id).unwrap();
+            dataset.undo(metadata.id).unwrap();
+            assert_eq!(dataset.find_column(age_id).unwrap().column_type, "INT");
+            assert_eq!(age_type(&dataset), vec!["integer", "text"]);
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![vec!["Alice", "34"], vec!["Bob", ""]]
+            );
         }
 
         #[test]
-        fn test_dataset_add_columns_with_existing_table() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
-
-            let columns = vec![
-                Column {
-                    id: None,
-                    table_name: "dataset001".to_string(),
-                    dataset_id: 1,
-                    name: "test".to_string(),
-                    column_type: "TEXT".to_string(),
-                    column_type_details: None,
-                    rules: "test".to_string(),
-                    position: 1,
-                },
-                Column {
-                    id: None,
-                    table_name: "dataset001".to_string(),
-                    dataset_id: 1,
-                    name: "test2".to_string(),
-                    column_type: "TEXT".to_string(),
-                    column_type_details: None,
-                    rules: "test2".to_string(),
-                    position: 2,
-                },
-            ];
-
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
+        fn test_row_writes_after_conversion() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            dataset.convert_to_columnar(metadata.id).unwrap();
+
+            let alice = add_row(&dataset, metadata.id, &[(name_id, "Alice"), (age_id, "34")]);
+            let bob = add_row(&dataset, metadata.id, &[(name_id, "Bob"), (age_id, "9")]);
+            assert_eq!(alice.data[1].value.as_deref(), Some("34"));
+
+            let updated = dataset
+                .update_row(
+                    metadata.id,
+                    alice.id,
+                    &HashMap::from([(age_id, Some("35".to_string()))]),
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to update row");
+            assert_eq!(updated.data[1].value.as_deref(), Some("35"));
+            assert_eq!(updated.data[0].value.as_deref(), Some("Alice"));
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                            id INTEGER PRIMARY KEY AUTOINCREMENT,
-                            data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                        )",
-                    [],
-                )
-                .expect("failed to create database");
+            dataset.delete_row(metadata.id, bob.id).expect("Failed to delete row");
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![vec!["Alice".to_string(), "35".to_string()]]
+            );
+            assert_eq!(dataset.get_numeric_values(metadata.id, age_id).unwrap(), vec![35.0]);
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+        #[test]
+        fn test_queries_on_typed_columns() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            for (name, age) in [("Alice", "34"), ("bob", "9"), ("Carol", "100"), ("dave", "")] {
+                add_row(&dataset, metadata.id, &[(name_id, name), (age_id, age)]);
+            }
+            dataset.convert_to_columnar(metadata.id).unwrap();
+
+            let query = RowQuery {
+                sort: Some(RowSort {
+                    column_id: age_id,
+                    descending: true,
+                }),
+                filters: vec![RowFilter {
+                    column_id: age_id,
+                    operator: FilterOperator::Range,
+                    value: None,
+                    min: Some("5".to_string()),
+                    max: None,
+                }],
+                search: Some("o".to_string()),
+            };
+            let sql = query
+                .to_sql(&dataset.get_columns(metadata.id).unwrap(), StorageLayout::Columnar)
+                .unwrap();
+            assert!(!format!("{} {}", sql.where_clause, sql.order_by).contains("AS REAL"));
+
+            let response = dataset.query_rows(metadata.id, 1, 10, &query).unwrap();
+            assert_eq!(response.total_rows, 2);
+
+            let names = response
+                .data
+                .iter()
+                .map(|row| row["data"][0]["value"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["Carol", "bob"]);
+        }
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    ["[{ \"column_id\": \"1\", \"value\": \"test\" }]"],
+        #[test]
+        fn test_add_and_delete_columns() {
+            let (dataset, metadata, name_id, age_id) = setup();
+            add_row(&dataset, metadata.id, &[(name_id, "Alice"), (age_id, "34")]);
+            dataset.convert_to_columnar(metadata.id).unwrap();
+
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "score", "FLOAT", 3, ColumnSettings::default())],
                 )
-                .expect("Failed to insert dataset");
-            }
+                .expect("Failed to add column");
+            let score_id = columns[2].id.unwrap();
+            assert!(dataset
+                .db
+                .column_exists(&format!("{}_cols", metadata.table_name), &format!("c_{}", score_id))
+                .unwrap());
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![vec!["Alice".to_string(), "34".to_string(), "".to_string()]]
+            );
 
-            let added_columns = dataset.add_columns(1, &columns);
-            assert!(added_columns.is_ok(), "Failed to add columns");
+            dataset.delete_column(age_id).expect("Failed to delete column");
+            assert!(!dataset
+                .db
+                .column_exists(&format!("{}_cols", metadata.table_name), &format!("c_{}", age_id))
+                .unwrap());
 
-            let conn = dataset.db.conn.lock().unwrap();
-            let mut dataset_001_stmt = conn
-                .prepare("SELECT * FROM dataset001")
-                .expect("Failed to prepare query");
+            let row = add_row(&dataset, metadata.id, &[(name_id, "Bob"), (score_id, "1.5")]);
+            assert_eq!(row.data.len(), 2);
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![
+                    vec!["Alice".to_string(), "".to_string()],
+                    vec!["Bob".to_string(), "1.5".to_string()]
+                ]
+            );
+        }
 
-            let values: Result<Vec<_>, _> = dataset_001_stmt
-                .query_map([], |row| {
-                    Ok((
-                        row.get::<_, i64>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, String>(3)?,
-                    ))
-                })
-                .expect("Failed to query dataset")
-                .collect();
+        #[test]
+        fn test_new_dataset_starts_columnar() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+            let metadata = dataset.create("fresh", "test").expect("Failed to create dataset");
 
-            let values = values
-                .expect("Failed to collect values")
-                .into_iter()
-                .map(|(_, data_json, _, _)| data_json);
+            dataset.convert_to_columnar(metadata.id).unwrap();
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "name", "TEXT", 1, ColumnSettings::default())],
+                )
+                .expect("Failed to add column");
+            add_row(&dataset, metadata.id, &[(columns[0].id.unwrap(), "Alice")]);
 
-            let expected_column_ids = ["2", "3"];
+            assert_eq!(
+                dataset.storage_layout(&metadata.table_name).unwrap(),
+                StorageLayout::Columnar
+            );
+            assert_eq!(values(&dataset, &metadata.table_name), vec![vec!["Alice".to_string()]]);
+        }
+    }
 
-            for data_json in values {
-                let row_data: Vec<RowData> = serde_json::from_str(&data_json).expect("Failed to parse JSON data");
+    mod nullable {
+        use super::*;
 
-                let found_column_ids: std::collections::HashSet<&str> =
-                    row_data.iter().map(|item| item.column_id.as_str()).collect();
+        fn settings(nullable: bool) -> ColumnSettings {
+            ColumnSettings {
+                nullable,
+                unique: nullable.then_some(UniqueMode::Exact),
+                ..Default::default()
+            }
+        }
 
-                for expected_id in expected_column_ids {
-                    assert!(
-                        found_column_ids.contains(expected_id),
-                        "Row data missing expected column ID: '{}'. Found columns: {:?}",
-                        expected_id,
-                        found_column_ids
-                    );
-                }
+        fn column(metadata: &DatasetMetadata, name: &str, position: i64, nullable: bool) -> Column {
+            fixtures::column(metadata, name, "TEXT", position, settings(nullable))
+        }
+
+        fn cell(column_id: i64, value: Option<&str>) -> RowData {
+            RowData {
+                column_id: column_id.to_string(),
+                value: value.map(str::to_string),
             }
         }
 
+        fn values(dataset: &DatasetService, table_name: &str) -> Vec<Vec<Option<String>>> {
+            dataset
+                .get_all_rows(table_name)
+                .expect("Failed to get rows")
+                .iter()
+                .map(|row| row.data.iter().map(|cell| cell.value.clone()).collect())
+                .collect()
+        }
+
+        fn setup() -> (DatasetService, DatasetMetadata, i64, i64) {
+            fixtures::setup(
+                "people",
+                [("name", "TEXT", settings(false)), ("nickname", "TEXT", settings(true))],
+            )
+        }
+
         #[test]
-        fn test_dataset_get_columns() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_null_rejected_in_non_nullable_column() {
+            let (dataset, metadata, name_id, nickname_id) = setup();
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
+            let result = dataset.add_row(metadata.id, &vec![cell(name_id, None), cell(nickname_id, Some("Al"))]);
+            assert!(matches!(result, Err(DatasetError::InvalidInput(_))));
+
+            let row = dataset
+                .add_row(
+                    metadata.id,
+                    &vec![cell(name_id, Some("Alice")), cell(nickname_id, None)],
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to add row");
+            let result = dataset.update_row(metadata.id, row.id, &HashMap::from([(name_id, None)]));
+            assert!(matches!(result, Err(DatasetError::InvalidInput(_))));
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+        #[test]
+        fn test_null_and_empty_string_stay_distinct() {
+            let (dataset, metadata, name_id, nickname_id) = setup();
+
+            let row = dataset
+                .add_row(
+                    metadata.id,
+                    &vec![cell(name_id, Some("Alice")), cell(nickname_id, None)],
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to add row");
+            dataset
+                .add_row(metadata.id, &vec![cell(name_id, Some("Bob")), cell(nickname_id, None)])
+                .expect("Nulls should not collide in a unique column");
+            assert_eq!(
+                values(&dataset, &metadata.table_name)[0],
+                vec![Some("Alice".to_string()), None]
+            );
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "1"],
+            dataset
+                .update_row(
+                    metadata.id,
+                    row.id,
+                    &HashMap::from([(nickname_id, Some(String::new()))]),
                 )
-                .expect("Failed to insert dataset");
-            }
+                .expect("Failed to update row");
+            assert_eq!(
+                values(&dataset, &metadata.table_name)[0],
+                vec![Some("Alice".to_string()), Some(String::new())]
+            );
 
-            let columns = dataset.get_columns(1);
-            assert!(columns.is_ok(), "Failed to get columns");
-            assert!(columns.unwrap().len() == 2, "Failed to get columns");
+            dataset
+                .update_row(metadata.id, row.id, &HashMap::from([(nickname_id, None)]))
+                .expect("Failed to update row");
+            assert_eq!(
+                values(&dataset, &metadata.table_name)[0],
+                vec![Some("Alice".to_string()), None]
+            );
         }
 
         #[test]
-        fn test_dataset_update_column() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_new_columns_backfilled_by_nullability() {
+            let (dataset, metadata, name_id, nickname_id) = setup();
+            dataset
+                .add_row(
+                    metadata.id,
+                    &vec![cell(name_id, Some("Alice")), cell(nickname_id, Some("Al"))],
+                )
+                .expect("Failed to add row");
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "email", 3, true), column(&metadata, "city", 4, false)],
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to add columns");
+
+            let row = &dataset.get_all_rows(&metadata.table_name).expect("Failed to get rows")[0];
+            let value = |column: &Column| {
+                row.data
+                    .iter()
+                    .find(|data| data.column_id == column.id.unwrap().to_string())
+                    .map(|data| data.value.clone())
+            };
+            assert_eq!(
+                value(&columns[2]),
+                Some(None),
+                "Nullable columns should be backfilled with null"
+            );
+            assert_eq!(value(&columns[3]), Some(Some(String::new())));
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
+        #[test]
+        fn test_columnar_keeps_nulls() {
+            let (dataset, metadata, name_id, nickname_id) = setup();
+            dataset
+                .add_row(
+                    metadata.id,
+                    &vec![cell(name_id, Some("Alice")), cell(nickname_id, None)],
                 )
-                .expect("Failed to insert dataset");
+                .expect("Failed to add row");
+            dataset
+                .add_row(
+                    metadata.id,
+                    &vec![cell(name_id, Some("Bob")), cell(nickname_id, Some(""))],
+                )
+                .expect("Failed to add row");
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
+
+            let columns = dataset
+                .add_columns(
+                    metadata.id,
+                    &[column(&metadata, "email", 3, true), column(&metadata, "city", 4, false)],
+                )
+                .expect("Failed to add columns");
+            assert_eq!(columns.len(), 4);
+
+            assert_eq!(
+                values(&dataset, &metadata.table_name),
+                vec![
+                    vec![Some("Alice".to_string()), None, None, Some(String::new())],
+                    vec![Some("Bob".to_string()), Some(String::new()), None, Some(String::new())]
+                ]
+            );
+        }
+    }
+
+    mod bulk_operations {
+        use super::*;
+
+        fn setup() -> (DatasetService, DatasetMetadata, i64, i64) {
+            let code = ColumnSettings {
+                unique: Some(UniqueMode::Exact),
+                ..Default::default()
+            };
+            fixtures::setup(
+                "tickets",
+                [("code", "TEXT", code), ("status", "TEXT", ColumnSettings::default())],
+            )
+        }
+
+        fn add_rows(dataset: &DatasetService, metadata: &DatasetMetadata, code_id: i64, status_id: i64) -> Vec<i64> {
+            [("T-1", "open"), ("T-2", "closed"), ("T-3", "open"), ("T-4", "open")]
+                .iter()
+                .map(|&(code, status)| {
+                    fixtures::add_row(dataset, metadata.id, &[(code_id, code), (status_id, status)]).id
+                })
+                .collect()
+        }
+
+        fn column_values(dataset: &DatasetService, table_name: &str, column_id: i64) -> Vec<String> {
+            dataset
+                .get_all_rows(table_name)
+                .expect("Failed to get rows")
+                .iter()
+                .filter_map(|row| row.data.iter().find(|data| data.column_id == column_id.to_string()))
+                .map(|data| data.value_or_empty().to_string())
+                .collect()
+        }
+
+        fn status_is(status_id: i64, value: &str) -> RowSelection {
+            RowSelection::Query(RowQuery {
+                filters: vec![RowFilter {
+                    column_id: status_id,
+                    operator: FilterOperator::Equals,
+                    value: Some(value.to_string()),
+                    min: None,
+                    max: None,
+                }],
+                ..Default::default()
+            })
+        }
+
+        #[test]
+        fn test_delete_rows() {
+            let (dataset, metadata, code_id, status_id) = setup();
+            let row_ids = add_rows(&dataset, &metadata, code_id, status_id);
+            dataset.pin_row(metadata.id, row_ids[0]).expect("Failed to pin row");
+
+            let deleted = dataset
+                .delete_rows(metadata.id, &RowSelection::Ids(vec![row_ids[0], row_ids[1], 999]))
+                .unwrap();
+            assert_eq!(deleted, 2, "Unknown ids should not be counted");
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, code_id),
+                vec!["T-3", "T-4"]
+            );
+            assert!(dataset.get_pinned_row_ids(metadata.id).unwrap().is_empty());
+            assert!(dataset.search_rows(metadata.id, "T-1", 10).unwrap().is_empty());
+
+            assert_eq!(
+                dataset
+                    .delete_rows(metadata.id, &status_is(status_id, "closed"))
+                    .unwrap(),
+                0
+            );
+            assert_eq!(
+                dataset.delete_rows(metadata.id, &status_is(status_id, "OPEN")).unwrap(),
+                2
+            );
+            assert!(dataset.get_all_rows(&metadata.table_name).unwrap().is_empty());
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
-            }
+        #[test]
+        fn test_update_rows() {
+            let (dataset, metadata, code_id, status_id) = setup();
+            let row_ids = add_rows(&dataset, &metadata, code_id, status_id);
 
-            let updated_column = dataset.update_column(
-                1,
-                UpdatableColumnFields {
-                    name: Some("test1".to_string()),
-                    rules: Some("test".to_string()),
-                    column_type: None,
-                    column_type_details: None,
-                    position: Some("1".to_string()),
-                },
+            let selection = RowSelection::Query(RowQuery::default());
+            assert_eq!(
+                dataset
+                    .update_rows(metadata.id, &selection, status_id, Some("open"))
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, status_id),
+                vec!["open", "open", "open", "open"]
             );
-            assert!(updated_column.is_ok(), "Failed to update column");
 
-            let conn = dataset.db.conn.lock().unwrap();
+            let selection = RowSelection::Ids(vec![row_ids[2], row_ids[3]]);
+            assert_eq!(
+                dataset
+                    .update_rows(metadata.id, &selection, status_id, Some("done"))
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, status_id),
+                vec!["open", "open", "done", "done"]
+            );
 
-            let column = conn
-                .query_row(
-                    "SELECT id, table_name, dataset_id, name, column_type, column_type_details, rules, position FROM columns WHERE id = 1",
-                    [],
-                    |row| {
-                        Ok(Column {
-                            id: Some(row.get::<_, i64>(0)?),
-                            table_name: row.get::<_, String>(1)?,
-                            dataset_id: row.get::<_, i64>(2)?,
-                            name: row.get::<_, String>(3)?,
-                            column_type: row.get::<_, String>(4)?,
-                            column_type_details: Some(row.get::<_, String>(5)?),
-                            rules: row.get::<_, String>(6)?,
-                            position: row.get::<_, i64>(7)?
-                        })
-                    },
+            assert!(matches!(
+                dataset.update_rows(metadata.id, &selection, status_id, None),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                dataset.update_rows(metadata.id, &selection, 999, Some("done")),
+                Err(DatasetError::InvalidInput(_))
+            ));
+        }
+
+        #[test]
+        fn test_replace_in_column() {
+            let (dataset, metadata, code_id, status_id) = setup();
+            add_rows(&dataset, &metadata, code_id, status_id);
+
+            let replaced = dataset
+                .replace_in_column(
+                    metadata.id,
+                    &status_is(status_id, "open"),
+                    code_id,
+                    r"^T-(\d+)$",
+                    "OPEN-$1",
                 )
-                .expect("Failed to query column");
+                .unwrap();
+            assert_eq!(replaced, 3);
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, code_id),
+                vec!["OPEN-1", "T-2", "OPEN-3", "OPEN-4"]
+            );
 
-            assert_eq!(column.name, "test1", "Failed to update column");
-            assert_eq!(column.rules, "test", "Failed to update column");
-            assert_eq!(column.position, 1, "Failed to update column");
+            let all = RowSelection::Query(RowQuery::default());
+            assert_eq!(
+                dataset
+                    .replace_in_column(metadata.id, &all, code_id, "^T-", "T-")
+                    .unwrap(),
+                0,
+                "Unchanged values should not be counted"
+            );
+            assert!(matches!(
+                dataset.replace_in_column(metadata.id, &all, code_id, "(", ""),
+                Err(DatasetError::InvalidInput(_))
+            ));
         }
 
         #[test]
-        fn test_dataset_delete_column() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset: DatasetService = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_bulk_edits_keep_unique_columns_unique() {
+            let (dataset, metadata, code_id, status_id) = setup();
+            let row_ids = add_rows(&dataset, &metadata, code_id, status_id);
+            let all = RowSelection::Query(RowQuery::default());
+
+            assert!(matches!(
+                dataset.update_rows(metadata.id, &all, code_id, Some("T-9")),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                dataset.update_rows(metadata.id, &RowSelection::Ids(vec![row_ids[0]]), code_id, Some("T-2")),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                dataset.replace_in_column(metadata.id, &all, code_id, r"\d", "0"),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, code_id),
+                vec!["T-1", "T-2", "T-3", "T-4"],
+                "Rejected edits should leave every row untouched"
+            );
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
+            let shifted = RowSelection::Ids(vec![row_ids[0], row_ids[1]]);
+            assert_eq!(
+                dataset
+                    .replace_in_column(metadata.id, &shifted, code_id, r"^T-(\d)$", "T-${1}0")
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, code_id),
+                vec!["T-10", "T-20", "T-3", "T-4"]
+            );
+        }
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )
-                .expect("failed to create database");
+        #[test]
+        fn test_bulk_operations_on_columnar_storage() {
+            let (dataset, metadata, code_id, status_id) = setup();
+            add_rows(&dataset, &metadata, code_id, status_id);
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+            assert_eq!(
+                dataset
+                    .update_rows(metadata.id, &status_is(status_id, "open"), status_id, Some("triaged"))
+                    .unwrap(),
+                3
+            );
+            assert_eq!(
+                dataset
+                    .delete_rows(metadata.id, &status_is(status_id, "closed"))
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                column_values(&dataset, &metadata.table_name, status_id),
+                vec!["triaged", "triaged", "triaged"]
+            );
+        }
+    }
+    mod history {
+        use super::fixtures::add_row;
+        use super::*;
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
-                )
-                .expect("Failed to insert dataset");
+        fn setup() -> (DatasetService, DatasetMetadata, i64, i64) {
+            let email = ColumnSettings {
+                unique: Some(UniqueMode::Exact),
+                nullable: true,
+                ..Default::default()
+            };
+            fixtures::setup(
+                "people",
+                [("name", "TEXT", ColumnSettings::default()), ("email", "TEXT", email)],
+            )
+        }
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
-                )
-                .expect("Failed to insert data row 1");
+        /// Cells of each row as `(column_id, value)`, sorted by column
+        type RowCells = (i64, Vec<(String, Option<String>)>);
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "Jane"}, {"column_id": "2", "value": "25"}]"#],
-                )
-                .expect("Failed to insert data row 2");
-            }
+        fn cells(dataset: &DatasetService, metadata: &DatasetMetadata) -> Vec<RowCells> {
+            dataset
+                .get_all_rows(&metadata.table_name)
+                .expect("Failed to get rows")
+                .iter()
+                .map(|row| {
+                    let mut data = row
+                        .data
+                        .iter()
+                        .map(|data| (data.column_id.clone(), data.value.clone()))
+                        .collect::<Vec<_>>();
+                    data.sort();
+                    (row.id, data)
+                })
+                .collect()
+        }
 
-            let deleted_column = dataset.delete_column(1);
+        #[test]
+        fn test_undo_and_redo_row_edit() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            let row = add_row(&dataset, metadata.id, &[(name_id, Some("Alice")), (email_id, None)]);
             assert!(
-                deleted_column.is_ok(),
-                "Failed to delete column: {:?}",
-                deleted_column.err()
+                dataset.undo(metadata.id).unwrap().is_none(),
+                "Adding rows is not recorded"
             );
 
-            let conn = dataset.db.conn.lock().unwrap();
-
-            let mut column_stmt = conn
-                .prepare("SELECT * FROM columns WHERE id = 1")
-                .expect("Failed to check if column exists");
-            let exists: bool = column_stmt.exists([]).expect("Failed to check if table exists");
-            assert!(!exists, "Failed to delete column from columns table");
+            let before = cells(&dataset, &metadata);
+            dataset
+                .update_row(
+                    metadata.id,
+                    row.id,
+                    &HashMap::from([(email_id, Some("alice@example.com".to_string()))]),
+                )
+                .expect("Failed to update row");
+            let after = cells(&dataset, &metadata);
+
+            let undone = dataset.undo(metadata.id).unwrap().expect("Edit should be undone");
+            assert!(undone.undone);
+            assert!(matches!(undone.operation, HistoryOperation::RowEdit { .. }));
+            assert_eq!(cells(&dataset, &metadata), before);
+            assert!(dataset.undo(metadata.id).unwrap().is_none());
+            assert!(!dataset
+                .value_exists(metadata.id, email_id, "alice@example.com", UniqueMode::Exact, None)
+                .unwrap());
+
+            assert!(dataset.redo(metadata.id).unwrap().is_some());
+            assert_eq!(cells(&dataset, &metadata), after);
+            assert!(dataset.redo(metadata.id).unwrap().is_none());
+            assert_eq!(
+                dataset.search_rows(metadata.id, "alice@example.com", 10).unwrap().len(),
+                1
+            );
+        }
 
-            let mut data_stmt = conn
-                .prepare("SELECT data FROM dataset001")
-                .expect("Failed to prepare data query");
-            let data_rows: Vec<String> = data_stmt
-                .query_map([], |row| row.get(0))
-                .expect("Failed to query data")
-                .collect::<Result<Vec<_>, _>>()
-                .expect("Failed to collect data");
+        #[test]
+        fn test_undo_row_deletes() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            for (name, email) in [("Alice", Some("alice@example.com")), ("Bob", None), ("Carol", None)] {
+                add_row(&dataset, metadata.id, &[(name_id, Some(name)), (email_id, email)]);
+            }
+            let before = cells(&dataset, &metadata);
+            let row_ids = before.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>();
 
-            for data_json in data_rows {
-                let row_data: Vec<RowData> = serde_json::from_str(&data_json).expect("Failed to parse JSON data");
+            dataset
+                .delete_row(metadata.id, row_ids[0])
+                .expect("Failed to delete row");
+            dataset
+                .delete_rows(metadata.id, &RowSelection::Ids(vec![row_ids[1], row_ids[2]]))
+                .expect("Failed to delete rows");
+            assert!(cells(&dataset, &metadata).is_empty());
 
-                for item in &row_data {
-                    assert_ne!(item.column_id, "1", "Column data was not removed from rows");
-                }
+            dataset.undo(metadata.id).unwrap();
+            assert_eq!(cells(&dataset, &metadata), before[1..].to_vec());
+            dataset.undo(metadata.id).unwrap();
+            assert_eq!(
+                cells(&dataset, &metadata),
+                before,
+                "Rows should come back with their ids"
+            );
+            assert!(dataset
+                .value_exists(metadata.id, email_id, "alice@example.com", UniqueMode::Exact, None)
+                .unwrap());
+            assert_eq!(
+                dataset.search_rows(metadata.id, "Carol", 10).unwrap()[0].row_id,
+                row_ids[2]
+            );
 
-                let has_column_2 = row_data.iter().any(|item| item.column_id == "2");
-                assert!(has_column_2, "Other column data was incorrectly removed");
-            }
+            dataset.redo(metadata.id).unwrap();
+            assert_eq!(cells(&dataset, &metadata), before[1..].to_vec());
         }
-    }
-
-    mod rows {
-        use super::*;
 
         #[test]
-        fn test_dataset_get_rows() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
-
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
+        fn test_undo_column_edit_and_delete() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            add_row(&dataset, metadata.id, &[(name_id, Some("Alice")), (email_id, None)]);
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, Some("Bob")), (email_id, Some("bob@example.com"))],
+            );
+            let before = cells(&dataset, &metadata);
+            let columns = dataset.get_columns(metadata.id).unwrap();
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
+            dataset
+                .update_column(
+                    name_id,
+                    UpdatableColumnFields {
+                        name: Some("full_name".to_string()),
+                        ..Default::default()
+                    },
                 )
-                .expect("failed to create database");
+                .expect("Failed to update column");
+            dataset.delete_column(name_id).expect("Failed to delete column");
+            let late = add_row(&dataset, metadata.id, &[(email_id, Some("late@example.com"))]);
+
+            dataset.undo(metadata.id).unwrap();
+            let restored = dataset.get_columns(metadata.id).unwrap();
+            assert_eq!(restored[0].id, Some(name_id));
+            assert_eq!(restored[0].name, "full_name");
+            assert_eq!(restored[1].position, 2);
+            assert_eq!(
+                cells(&dataset, &metadata),
+                [
+                    before.clone(),
+                    vec![(
+                        late.id,
+                        vec![
+                            (name_id.to_string(), Some(String::new())),
+                            (email_id.to_string(), Some("late@example.com".to_string()))
+                        ]
+                    )]
+                ]
+                .concat(),
+                "Rows added after the delete should be backfilled"
+            );
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+            dataset.undo(metadata.id).unwrap();
+            assert_eq!(dataset.get_columns(metadata.id).unwrap()[0].name, columns[0].name);
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
-                )
-                .expect("Failed to insert dataset");
+            dataset.redo(metadata.id).unwrap();
+            dataset.redo(metadata.id).unwrap();
+            assert_eq!(dataset.get_columns(metadata.id).unwrap().len(), 1);
+            assert!(cells(&dataset, &metadata)
+                .iter()
+                .all(|(_, data)| data.iter().all(|(column_id, _)| *column_id != name_id.to_string())));
+        }
 
-                for i in 0..11 {
-                    let data = format!(
-                        r#"[{{ "column_id": "1", "value": "John"}}, {{ "column_id": "2", "value": "{}"}}]"#,
-                        i
-                    );
+        #[test]
+        fn test_new_edit_discards_redo() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            let row = add_row(&dataset, metadata.id, &[(name_id, Some("Alice")), (email_id, None)]);
+            let rename = |name: &str| {
+                dataset
+                    .update_row(metadata.id, row.id, &HashMap::from([(name_id, Some(name.to_string()))]))
+                    .expect("Failed to update row");
+            };
+
+            rename("Alicia");
+            dataset.undo(metadata.id).unwrap();
+            rename("Ally");
+
+            assert!(dataset.redo(metadata.id).unwrap().is_none());
+            assert_eq!(dataset.get_history(metadata.id).unwrap().len(), 1);
+        }
 
-                    let data = data.as_str();
-                    conn.execute("INSERT INTO dataset001 (data) VALUES (?)", [data])
-                        .expect("Failed to insert data row 1");
-                }
+        #[test]
+        fn test_history_is_bounded() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            let row = add_row(&dataset, metadata.id, &[(name_id, Some("Alice")), (email_id, None)]);
+
+            for i in 0..HISTORY_LIMIT + 5 {
+                dataset
+                    .update_row(metadata.id, row.id, &HashMap::from([(name_id, Some(i.to_string()))]))
+                    .expect("Failed to update row");
             }
 
-            let next_rows = dataset.get_rows(1, 1, 5);
-            assert!(next_rows.is_ok(), "Failed to get next rows");
-            assert!(next_rows.unwrap().data.len() == 5, "Failed to get next rows");
+            let history = dataset.get_history(metadata.id).unwrap();
+            assert_eq!(history.len() as i64, HISTORY_LIMIT);
+            assert!(
+                history.windows(2).all(|pair| pair[0].id > pair[1].id),
+                "Newest entries come first"
+            );
         }
 
         #[test]
-        fn test_dataset_count_rows() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_undo_on_columnar_storage() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            let row = add_row(&dataset, metadata.id, &[(name_id, Some("Alice")), (email_id, None)]);
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, Some("Bob")), (email_id, Some("bob@example.com"))],
+            );
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
+            let before = cells(&dataset, &metadata);
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
+            dataset.delete_column(email_id).expect("Failed to delete column");
+            dataset.delete_row(metadata.id, row.id).expect("Failed to delete row");
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )
-                .expect("failed to create database");
+            dataset.undo(metadata.id).unwrap();
+            dataset.undo(metadata.id).unwrap();
+            assert_eq!(cells(&dataset, &metadata), before);
+            assert_eq!(
+                dataset.storage_layout(&metadata.table_name).unwrap(),
+                StorageLayout::Columnar
+            );
+        }
+
+        #[test]
+        fn test_failed_column_restore_on_columnar_storage_rolls_back() {
+            let (dataset, metadata, name_id, email_id) = setup();
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, Some("Alice")), (email_id, Some("alice@example.com"))],
+            );
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
+            let before = cells(&dataset, &metadata);
+            dataset.delete_column(email_id).expect("Failed to delete column");
+
+            // A stray physical column makes the restore fail after the column row is written
+            let stray = format!(
+                "ALTER TABLE {} ADD COLUMN {} TEXT",
+                columnar_table(&metadata.table_name),
+                physical_column(email_id)
+            );
+            dataset.db.execute(&stray, []).unwrap();
+
+            assert!(dataset.undo(metadata.id).is_err());
+            assert!(dataset.find_column(email_id).is_err(), "Column should not be restored");
+            assert_eq!(dataset.get_all_rows(&metadata.table_name).unwrap().len(), 1);
+
+            let drop = format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                columnar_table(&metadata.table_name),
+                physical_column(email_id)
+            );
+            dataset.db.execute(&drop, []).unwrap();
+
+            dataset.undo(metadata.id).expect("Undo should still be pending");
+            assert_eq!(cells(&dataset, &metadata), before);
+        }
+    }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+    mod snapshots {
+        use super::fixtures::{add_row, people};
+        use super::*;
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+        fn rename(dataset: &DatasetService, dataset_id: i64, row_id: i64, column_id: i64, value: &str) {
+            dataset
+                .update_row(
+                    dataset_id,
+                    row_id,
+                    &HashMap::from([(column_id, Some(value.to_string()))]),
                 )
-                .expect("Failed to insert dataset");
-
-                for i in 0..10 {
-                    let data = format!(
-                        r#"[{{ "column_id": "1", "value": "John"}}, {{ "column_id": "2", "value": "{}"}}]"#,
-                        i
-                    );
+                .expect("Failed to update row");
+        }
 
-                    let data = data.as_str();
-                    conn.execute("INSERT INTO dataset001 (data) VALUES (?)", [data])
-                        .expect("Failed to insert data row 1");
-                }
-            }
+        /// Cells of each row as `(column_id, value)`, sorted by column
+        type RowCells = (i64, Vec<(String, Option<String>)>);
 
-            let count = dataset
-                .count_rows("dataset001")
-                .expect("Failed to  call count_rows function");
-            assert!(count == 10, "Failed to count rows");
+        fn cells(dataset: &DatasetService, metadata: &DatasetMetadata) -> Vec<RowCells> {
+            dataset
+                .get_all_rows(&metadata.table_name)
+                .expect("Failed to get rows")
+                .iter()
+                .map(|row| {
+                    let mut data = row
+                        .data
+                        .iter()
+                        .map(|data| (data.column_id.clone(), data.value.clone()))
+                        .collect::<Vec<_>>();
+                    data.sort();
+                    (row.id, data)
+                })
+                .collect()
         }
 
         #[test]
-        fn test_dataset_update_row() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_create_list_and_delete_snapshots() {
+            let (dataset, metadata, name_id, email_id) = people();
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
+            let first = dataset.create_snapshot(metadata.id, " Before cleanup ").unwrap();
+            assert_eq!(first.name, "Before cleanup");
+            assert_eq!(first.row_count, 1);
+            assert_eq!(first.columns.len(), 2);
 
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Bob"), (email_id, "bob@example.com")],
+            );
+            let second = dataset.create_snapshot(metadata.id, "After import").unwrap();
+            assert_eq!(second.row_count, 2);
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )
-                .expect("failed to create database");
+            let ids = |snapshots: Vec<DatasetSnapshot>| snapshots.iter().map(|s| s.id).collect::<Vec<_>>();
+            assert_eq!(
+                ids(dataset.list_snapshots(metadata.id).unwrap()),
+                vec![second.id, first.id]
+            );
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+            dataset.delete_snapshot(first.id).unwrap();
+            assert_eq!(ids(dataset.list_snapshots(metadata.id).unwrap()), vec![second.id]);
+            assert!(matches!(
+                dataset.find_snapshot(first.id),
+                Err(DatasetError::NotFound(_))
+            ));
+            assert!(matches!(
+                dataset.create_snapshot(metadata.id, "  "),
+                Err(DatasetError::InvalidInput(_))
+            ));
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
-                )
-                .expect("Failed to insert dataset");
+        #[test]
+        fn test_diff_against_current_state() {
+            let (dataset, metadata, name_id, email_id) = people();
+            let alice = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            let bob = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Bob"), (email_id, "bob@example.com")],
+            );
+            let carol = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Carol"), (email_id, "carol@example.com")],
+            );
+            let snapshot = dataset.create_snapshot(metadata.id, "v1").unwrap();
+
+            rename(&dataset, metadata.id, alice.id, name_id, "Alicia");
+            dataset.delete_row(metadata.id, bob.id).unwrap();
+            let dave = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Dave"), (email_id, "dave@example.com")],
+            );
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
-                )
-                .expect("Failed to insert data row 1");
+            let diff = dataset.diff_snapshot(snapshot.id, None).unwrap();
+            assert_eq!(diff.added_rows, vec![dave.id]);
+            assert_eq!(diff.removed_rows, vec![bob.id]);
+            assert_eq!(diff.changed_rows, vec![alice.id]);
+            assert!(diff.added_columns.is_empty() && diff.removed_columns.is_empty());
+            assert_eq!(
+                diff.columns,
+                vec![ColumnDiff {
+                    column_id: name_id,
+                    name: "name".to_string(),
+                    changed_rows: vec![alice.id],
+                }]
+            );
+            assert!(!diff.changed_rows.contains(&carol.id));
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "Jane"}, {"column_id": "2", "value": "25"}]"#],
-                )
-                .expect("Failed to insert data row 2");
-            }
+            dataset.delete_column(email_id).unwrap();
+            let other = dataset.create_snapshot(metadata.id, "v2").unwrap();
+            let diff = dataset.diff_snapshot(snapshot.id, Some(other.id)).unwrap();
+            assert_eq!(diff.removed_columns, vec![email_id]);
+            assert_eq!(
+                diff.changed_rows,
+                vec![alice.id],
+                "Removed columns are not reported per row"
+            );
+            assert_eq!(dataset.diff_snapshot(other.id, None).unwrap(), SnapshotDiff::default());
+        }
 
-            let updated_row =
-                dataset.update_row(1, 2, &HashMap::from([(2, "30".to_string()), (1, "Johnny".to_string())]));
-            assert!(updated_row.is_ok(), "Failed to update row");
+        #[test]
+        fn test_diff_rejects_snapshots_of_other_datasets() {
+            let (dataset, metadata, _, _) = people();
+            let other = dataset.create("other", "test").unwrap();
+            let first = dataset.create_snapshot(metadata.id, "v1").unwrap();
+            let second = dataset.create_snapshot(other.id, "v1").unwrap();
+
+            assert!(matches!(
+                dataset.diff_snapshot(first.id, Some(second.id)),
+                Err(DatasetError::InvalidInput(_))
+            ));
+        }
 
-            let conn = dataset.db.conn.lock().unwrap();
+        #[test]
+        fn test_restore_snapshot() {
+            let (dataset, metadata, name_id, email_id) = people();
+            let alice = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            let bob = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Bob"), (email_id, "bob@example.com")],
+            );
+            let snapshot = dataset.create_snapshot(metadata.id, "v1").unwrap();
+            let before = cells(&dataset, &metadata);
 
-            let row = conn
-                .query_row("SELECT * FROM dataset001 WHERE id = 2", [], |row| {
-                    Ok(Row {
-                        id: row.get::<_, i64>(0)?,
-                        data: serde_json::from_str(&row.get::<_, String>(1)?).expect("Failed to parse JSON data"),
-                        created_at: row.get::<_, String>(2)?,
-                        updated_at: row.get::<_, String>(3)?,
-                    })
-                })
-                .expect("Failed to query row");
+            rename(&dataset, metadata.id, alice.id, name_id, "Alicia");
+            dataset.delete_row(metadata.id, bob.id).unwrap();
+            dataset.delete_column(email_id).unwrap();
+            add_row(&dataset, metadata.id, &[(name_id, "Carol")]);
 
-            assert_eq!(row.data[0].value, "Johnny", "Failed to update row");
-            assert_eq!(row.data[1].value, "30", "Failed to update row");
+            dataset.restore_snapshot(snapshot.id).unwrap();
+            assert_eq!(cells(&dataset, &metadata), before);
+            assert_eq!(
+                dataset
+                    .get_columns(metadata.id)
+                    .unwrap()
+                    .iter()
+                    .map(|column| column.id.unwrap())
+                    .collect::<Vec<_>>(),
+                vec![name_id, email_id]
+            );
+            assert!(dataset.get_history(metadata.id).unwrap().is_empty());
+            assert!(dataset
+                .value_exists(metadata.id, email_id, "bob@example.com", UniqueMode::Exact, None)
+                .unwrap());
+            assert_eq!(
+                dataset.diff_snapshot(snapshot.id, None).unwrap(),
+                SnapshotDiff::default()
+            );
         }
 
         #[test]
-        fn test_add_row() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_restore_snapshot_on_columnar_storage() {
+            let (dataset, metadata, name_id, email_id) = people();
+            let alice = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Bob"), (email_id, "bob@example.com")],
+            );
+            let snapshot = dataset.create_snapshot(metadata.id, "v1").unwrap();
+            let before = cells(&dataset, &metadata);
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
+            dataset.delete_column(email_id).unwrap();
+            rename(&dataset, metadata.id, alice.id, name_id, "Alicia");
 
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
+            dataset.restore_snapshot(snapshot.id).unwrap();
+            assert_eq!(cells(&dataset, &metadata), before);
+            assert_eq!(
+                dataset.storage_layout(&metadata.table_name).unwrap(),
+                StorageLayout::Columnar
+            );
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Carol"), (email_id, "carol@example.com")],
+            );
+            assert_eq!(cells(&dataset, &metadata).len(), 3);
+        }
+    }
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )
-                .expect("failed to create database");
+    mod duplicate {
+        use super::fixtures::{add_row, people};
+        use super::*;
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+        fn column_ids(dataset: &DatasetService, dataset_id: i64) -> Vec<i64> {
+            dataset
+                .get_columns(dataset_id)
+                .unwrap()
+                .iter()
+                .map(|column| column.id.unwrap())
+                .collect()
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
+        #[test]
+        fn test_duplicate_with_rows() {
+            let (dataset, metadata, name_id, email_id) = people();
+            let settings = DatasetSettings {
+                include_context: true,
+                ..Default::default()
+            };
+            dataset.update_settings(metadata.id, &settings).unwrap();
+            dataset
+                .update_prompt_template(
+                    metadata.id,
+                    &PromptTemplate {
+                        system_prompt: Some("You write people".to_string()),
+                        cell_template: None,
+                    },
                 )
-                .expect("Failed to insert dataset");
+                .unwrap();
+            let alice = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            let bob = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Bob"), (email_id, "bob@example.com")],
+            );
+            dataset.pin_row(metadata.id, bob.id).unwrap();
+
+            let copy = dataset.duplicate(metadata.id, None, true).unwrap();
+            assert_eq!(copy.name, "people (copy)");
+            assert_eq!(copy.description, "test");
+            assert_ne!(copy.table_name, metadata.table_name);
+            assert_eq!(copy.settings, settings);
+            assert_eq!(copy.row_count, 2);
+            assert_eq!(
+                dataset.get_prompt_template(copy.id).unwrap().system_prompt.as_deref(),
+                Some("You write people")
+            );
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
-                )
-                .expect("Failed to insert data row 1");
-            }
+            let copied_ids = column_ids(&dataset, copy.id);
+            assert_eq!(copied_ids.len(), 2);
+            assert!(!copied_ids.contains(&name_id) && !copied_ids.contains(&email_id));
+            let copied_columns = dataset.get_columns(copy.id).unwrap();
+            assert_eq!(copied_columns[1].name, "email");
+            assert_eq!(copied_columns[1].settings.unique, Some(UniqueMode::Exact));
 
-            let new_row = dataset.add_row(
-                1,
-                &vec![
-                    RowData {
-                        column_id: "1".to_string(),
-                        value: "John".to_string(),
-                    },
-                    RowData {
-                        column_id: "2".to_string(),
-                        value: "30".to_string(),
-                    },
-                ],
+            let rows = dataset.get_all_rows(&copy.table_name).unwrap();
+            assert_eq!(
+                rows.iter().map(|row| row.id).collect::<Vec<_>>(),
+                vec![alice.id, bob.id]
             );
-            assert!(new_row.is_ok(), "Failed to add row");
-
-            let conn = dataset.db.conn.lock().unwrap();
+            assert_eq!(rows[0].created_at, alice.created_at);
+            assert_eq!(dataset.get_pinned_row_ids(copy.id).unwrap(), vec![bob.id]);
+            for (row, (name, email)) in rows
+                .iter()
+                .zip([("Alice", "alice@example.com"), ("Bob", "bob@example.com")])
+            {
+                let cells = row
+                    .data
+                    .iter()
+                    .map(|data| (data.column_id.clone(), data.value_or_empty().to_string()))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    cells,
+                    vec![
+                        (copied_ids[0].to_string(), name.to_string()),
+                        (copied_ids[1].to_string(), email.to_string()),
+                    ]
+                );
+            }
 
-            let row = conn
-                .query_row("SELECT * FROM dataset001 WHERE id = 1", [], |row| {
-                    Ok(Row {
-                        id: row.get::<_, i64>(0)?,
-                        data: serde_json::from_str(&row.get::<_, String>(1)?).expect("Failed to parse JSON data"),
-                        created_at: row.get::<_, String>(2)?,
-                        updated_at: row.get::<_, String>(3)?,
-                    })
-                })
-                .expect("Failed to query row");
+            assert!(dataset
+                .value_exists(copy.id, copied_ids[1], "bob@example.com", UniqueMode::Exact, None)
+                .unwrap());
+            assert_eq!(dataset.search_rows(copy.id, "alice", 10).unwrap().len(), 1);
 
-            assert_eq!(row.data[0].value, "John", "Failed to add row");
-            assert_eq!(row.data[1].value, "30", "Failed to add row");
+            dataset.delete_row(copy.id, alice.id).unwrap();
+            assert_eq!(dataset.get_all_rows(&metadata.table_name).unwrap().len(), 2);
         }
 
         #[test]
-        fn test_delete_row() {
-            let db = DatabaseService::new(None).expect("Failed to create database");
-            let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+        fn test_duplicate_without_rows() {
+            let (dataset, metadata, name_id, email_id) = people();
+            let alice = add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            dataset.pin_row(metadata.id, alice.id).unwrap();
+
+            let copy = dataset.duplicate(metadata.id, Some(" people v2 "), false).unwrap();
+            assert_eq!(copy.name, "people v2");
+            assert_eq!(copy.row_count, 0);
+            assert!(dataset.get_pinned_row_ids(copy.id).unwrap().is_empty());
+
+            let copied_ids = column_ids(&dataset, copy.id);
+            let row = add_row(
+                &dataset,
+                copy.id,
+                &[(copied_ids[0], "Bob"), (copied_ids[1], "bob@example.com")],
+            );
+            assert_eq!(dataset.get_all_rows(&copy.table_name).unwrap()[0].id, row.id);
 
-            {
-                let conn = dataset.db.conn.lock().unwrap();
+            assert!(matches!(
+                dataset.duplicate(metadata.id, Some("  "), false),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                dataset.duplicate(999, None, false),
+                Err(DatasetError::NotFound(_))
+            ));
+            assert_eq!(dataset.find_all().unwrap().len(), 2);
+        }
 
-                conn.execute(
-                    "INSERT INTO datasets_metadata (table_name, name, description) VALUES (?, ?, ?)",
-                    ["dataset001", "test", "test"],
-                )
-                .expect("Failed to insert dataset");
+        #[test]
+        fn test_duplicate_keeps_columnar_storage() {
+            let (dataset, metadata, name_id, email_id) = people();
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
 
-                conn.execute(
-                    "CREATE TABLE IF NOT EXISTS dataset001 (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        data JSON DEFAULT '{}' CHECK(json_valid(data)),
-                        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-                        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-                    )",
-                    [],
-                )
-                .expect("failed to create database");
+            let copy = dataset.duplicate(metadata.id, None, true).unwrap();
+            assert_eq!(
+                dataset.storage_layout(&copy.table_name).unwrap(),
+                StorageLayout::Columnar
+            );
+            let copied_ids = column_ids(&dataset, copy.id);
+            let rows = dataset.get_all_rows(&copy.table_name).unwrap();
+            assert_eq!(rows.len(), 1);
+            assert!(rows[0]
+                .data
+                .iter()
+                .any(|data| data.column_id == copied_ids[0].to_string() && data.value.as_deref() == Some("Alice")));
+        }
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test1", "TEXT", "test", "1"],
-                )
-                .expect("Failed to insert dataset");
+        #[test]
+        fn test_duplicate_refuses_stale_table() {
+            let (dataset, metadata, name_id, email_id) = people();
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            dataset
+                .db
+                .create_table("dataset2", &["data JSON DEFAULT '{}'"], &[])
+                .unwrap();
+            dataset
+                .db
+                .execute("INSERT INTO dataset2 (data) VALUES ('[]')", [])
+                .unwrap();
 
-                conn.execute(
-                    "INSERT INTO columns (dataset_id, table_name, name, column_type, rules, position) VALUES (?, ?, ?, ?, ?, ?)",
-                    ["1", "dataset001", "test2", "NUMBER", "test", "2"],
-                )
-                .expect("Failed to insert dataset");
+            assert!(matches!(
+                dataset.duplicate(metadata.id, None, true),
+                Err(DatasetError::InvalidInput(_))
+            ));
+            assert_eq!(dataset.find_all().unwrap().len(), 1);
+            assert!(!dataset.db.table_exists("dataset2").unwrap());
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "John"}, {"column_id": "2", "value": "30"}]"#],
-                )
-                .expect("Failed to insert data row 1");
+            let copy = dataset.duplicate(metadata.id, None, true).unwrap();
+            assert_eq!(copy.table_name, "dataset2");
+            assert_eq!(copy.row_count, 1);
+        }
 
-                conn.execute(
-                    "INSERT INTO dataset001 (data) VALUES (?)",
-                    [r#"[{"column_id": "1", "value": "Jane"}, {"column_id": "2", "value": "25"}]"#],
-                )
-                .expect("Failed to insert data row 2");
+        #[test]
+        fn test_discard_drops_rows_tables() {
+            let (dataset, metadata, name_id, email_id) = people();
+            add_row(
+                &dataset,
+                metadata.id,
+                &[(name_id, "Alice"), (email_id, "alice@example.com")],
+            );
+            dataset.convert_to_columnar(metadata.id).expect("Failed to convert");
+
+            dataset.discard(&metadata).unwrap();
+            assert!(matches!(
+                dataset.find_by_id(metadata.id),
+                Err(DatasetError::NotFound(_))
+            ));
+            for table in ["dataset1", "dataset1_cols", "dataset1_fts"] {
+                assert!(!dataset.db.table_exists(table).unwrap(), "{} was left behind", table);
             }
+            let triggers = dataset
+                .db
+                .query("SELECT name FROM sqlite_master WHERE type = 'trigger'", [], |row| {
+                    Ok(row.get::<_, String>(0)?)
+                })
+                .unwrap();
+            assert!(triggers.is_empty());
 
-            let deleted_row = dataset.delete_row(1, 2);
-            assert!(deleted_row.is_ok(), "Failed to delete row");
+            let next = dataset.create("people", "test").unwrap();
+            assert_eq!(next.table_name, "dataset1");
+            assert_eq!(next.row_count, 0);
         }
     }
 }
@@ -105,7 +105,7 @@ impl ExportService {
         for row in rows {
             let mut row_values = Vec::new();
 
-            let value_map: HashMap<String, String> = row
+            let value_map: HashMap<String, Option<String>> = row
                 .data
                 .iter()
                 .map(|rd| (rd.column_id.clone(), rd.value.clone()))
@@ -113,8 +113,13 @@ impl ExportService {
 
             for column in columns {
                 let column_id = column.id.expect("Column should have an ID").to_string();
-                let value = value_map.get(&column_id).cloned().unwrap_or_else(|| "".to_string());
-                row_values.push(self.escape_csv_field(&value));
+                let value = value_map.get(&column_id).cloned().flatten();
+                row_values.push(match value {
+                    // Null is an empty field, so a nullable column quotes its empty strings to tell them apart
+                    Some(value) if value.is_empty() && column.settings.nullable => "\"\"".to_string(),
+                    Some(value) => self.escape_csv_field(&value),
+                    None => String::new(),
+                });
             }
 
             csv_content.push_str(&row_values.join(","));
@@ -165,7 +170,7 @@ mod tests {
 
     mod csv_processing {
         use super::*;
-        use crate::services::dataset::RowData;
+        use crate::services::dataset::{ColumnSettings, RowData};
 
         #[test]
         fn test_create_csv_content() {
@@ -183,6 +188,7 @@ mod tests {
                     column_type_details: None,
                     rules: "Name column".to_string(),
                     position: 1,
+                    settings: ColumnSettings::default(),
                 },
                 Column {
                     id: Some(2),
@@ -193,6 +199,7 @@ mod tests {
                     column_type_details: None,
                     rules: "Age column".to_string(),
                     position: 2,
+                    settings: ColumnSettings::default(),
                 },
             ];
 
@@ -202,11 +209,11 @@ mod tests {
                     data: vec![
                         RowData {
                             column_id: "1".to_string(),
-                            value: "John".to_string(),
+                            value: Some("John".to_string()),
                         },
                         RowData {
                             column_id: "2".to_string(),
-                            value: "25".to_string(),
+                            value: Some("25".to_string()),
                         },
                     ]
                     .into_boxed_slice(),
@@ -218,11 +225,11 @@ mod tests {
                     data: vec![
                         RowData {
                             column_id: "1".to_string(),
-                            value: "Jane".to_string(),
+                            value: Some("Jane".to_string()),
                         },
                         RowData {
                             column_id: "2".to_string(),
-                            value: "30".to_string(),
+                            value: Some("30".to_string()),
                         },
                     ]
                     .into_boxed_slice(),
@@ -256,6 +263,7 @@ mod tests {
                 column_type_details: None,
                 rules: "Name column".to_string(),
                 position: 1,
+                settings: ColumnSettings::default(),
             }];
 
             let rows = vec![];
@@ -269,6 +277,64 @@ mod tests {
             assert_eq!(lines[0], "name");
         }
 
+        #[test]
+        fn test_create_csv_content_null_values() {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            let dataset_service = DatasetService::new(db.clone()).expect("Failed to create dataset service");
+            let export = ExportService::new(db, dataset_service);
+
+            let columns: Vec<Column> = [("nickname", true), ("city", false)]
+                .into_iter()
+                .enumerate()
+                .map(|(i, (name, nullable))| Column {
+                    id: Some(i as i64 + 1),
+                    dataset_id: 1,
+                    table_name: "test_table".to_string(),
+                    name: name.to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: String::new(),
+                    position: i as i64 + 1,
+                    settings: ColumnSettings {
+                        nullable,
+                        ..Default::default()
+                    },
+                })
+                .collect();
+
+            let rows: Vec<Row> = [(None, Some("")), (Some(""), Some("Paris"))]
+                .into_iter()
+                .enumerate()
+                .map(|(i, (nickname, city))| Row {
+                    id: i as i64 + 1,
+                    data: vec![
+                        RowData {
+                            column_id: "1".to_string(),
+                            value: nickname.map(str::to_string),
+                        },
+                        RowData {
+                            column_id: "2".to_string(),
+                            value: city.map(str::to_string),
+                        },
+                    ]
+                    .into_boxed_slice(),
+                    created_at: "2023-01-01".to_string(),
+                    updated_at: "2023-01-01".to_string(),
+                })
+                .collect();
+
+            let csv_content = export
+                .create_csv_content(&columns, &rows)
+                .expect("Should create CSV content");
+            let lines: Vec<&str> = csv_content.lines().collect();
+
+            assert_eq!(
+                lines[1], ",",
+                "Null and non-nullable empty values are bare empty fields"
+            );
+            assert_eq!(lines[2], "\"\",Paris", "Empty strings are quoted in nullable columns");
+        }
+
         #[test]
         fn test_escape_csv_field_normal() {
             let db = DatabaseService::new(None).expect("Failed to create database");
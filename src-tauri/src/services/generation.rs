@@ -1,12 +1,18 @@
 use crate::error::AppError;
 use crate::services::database::{DatabaseError, DatabaseService};
-use crate::services::dataset::{Column, Row, RowData};
+use crate::services::dataset::{
+    CellProvenance, Column, ColumnSettings, DistributionSettings, PiiPolicy, PromptTemplate, ReferenceSettings, Row,
+    RowData, RowStrategy, StopSettings,
+};
+use crate::services::model::ModelBenchmark;
 use crate::services::{DatasetService, ModelService};
 use serde_json::Value;
+use std::cell::Cell;
 use std::fmt;
 use std::num::NonZeroU32;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
 use llama_cpp_2::context::params::LlamaContextParams;
@@ -24,9 +30,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::cmp::Ordering;
 use std::sync::OnceLock;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use crate::utils::CELL_PROMPT_TEMPLATE;
+use crate::utils::{fold_full_width, render_chat_prompt, resident_memory_bytes, scan_pii, Locale, LocaleStrings};
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +45,129 @@ pub struct InferenceConfig {
     pub batch_size: usize,
     pub context_size: u32,
     pub add_bos: bool,
+    #[serde(default)]
+    pub stop: StopCriteria,
+    /// Seed of the token sampler, a random one is drawn per completion when unset
+    #[serde(default)]
+    pub seed: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StopCriteria {
+    pub stop_sequences: Vec<String>,
+    pub min_tokens: usize,
+    pub min_chars: usize,
+    pub max_chars: Option<usize>,
+    pub multiline: bool,
+    /// Stop on `.`, `!` or `?` once this many tokens were generated
+    pub sentence_end_after: Option<usize>,
+    /// Stop once the first JSON object is closed
+    pub json_object: bool,
+}
+
+impl Default for StopCriteria {
+    fn default() -> Self {
+        Self {
+            stop_sequences: vec!["```".to_string()],
+            min_tokens: 0,
+            min_chars: 0,
+            max_chars: Some(200),
+            multiline: false,
+            sentence_end_after: Some(11),
+            json_object: false,
+        }
+    }
+}
+
+impl StopCriteria {
+    pub fn json_object() -> Self {
+        Self {
+            stop_sequences: Vec::new(),
+            max_chars: None,
+            multiline: true,
+            sentence_end_after: None,
+            json_object: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_settings(settings: &StopSettings) -> Self {
+        let defaults = Self::default();
+
+        let mut stop_sequences = defaults.stop_sequences;
+        stop_sequences.extend(settings.stop_sequences.iter().filter(|s| !s.is_empty()).cloned());
+
+        let default_max_chars = if settings.multiline { None } else { defaults.max_chars };
+        let sentence_end_after = match settings.stop_at_sentence_end.unwrap_or(!settings.multiline) {
+            true => defaults.sentence_end_after,
+            false => None,
+        };
+
+        Self {
+            stop_sequences,
+            min_tokens: settings.min_tokens.unwrap_or(defaults.min_tokens),
+            min_chars: settings.min_chars.unwrap_or(defaults.min_chars),
+            max_chars: settings.max_chars.or(default_max_chars),
+            multiline: settings.multiline,
+            sentence_end_after,
+            json_object: false,
+        }
+    }
+
+    /// Whether the minimum length is reached, so EOS or any other stop condition may end generation
+    pub fn can_stop(&self, response: &str, tokens_generated: usize) -> bool {
+        tokens_generated >= self.min_tokens && response.trim().chars().count() >= self.min_chars
+    }
+
+    pub fn should_stop(&self, response: &str, tokens_generated: usize) -> bool {
+        if self.max_chars.is_some_and(|max| response.chars().count() >= max) {
+            return true;
+        }
+
+        if !self.can_stop(response, tokens_generated) {
+            return false;
+        }
+
+        if self.json_object {
+            return GenerationService::is_json_object_complete(response);
+        }
+
+        let trimmed = response.trim();
+
+        if self.stop_sequence_position(trimmed).is_some() {
+            return true;
+        }
+
+        if !self.multiline && trimmed.contains('\n') {
+            return true;
+        }
+
+        self.sentence_end_after.is_some_and(|after| tokens_generated >= after)
+            && (trimmed.ends_with('.') || trimmed.ends_with('!') || trimmed.ends_with('?'))
+    }
+
+    /// Cuts `response` at the first stop sequence that follows some content, then to `max_chars` characters
+    pub fn truncate(&self, response: &str) -> String {
+        let mut text = response.trim_start();
+
+        if let Some(position) = self.stop_sequence_position(text) {
+            text = &text[..position];
+        }
+        if let Some((end, _)) = self.max_chars.and_then(|max| text.char_indices().nth(max)) {
+            text = &text[..end];
+        }
+
+        text.to_string()
+    }
+
+    fn stop_sequence_position(&self, text: &str) -> Option<usize> {
+        self.stop_sequences
+            .iter()
+            .filter(|sequence| !sequence.is_empty())
+            .filter_map(|sequence| text.find(sequence.as_str()).filter(|&position| position > 0))
+            .min()
+    }
 }
 
 impl Default for InferenceConfig {
@@ -50,6 +180,22 @@ impl Default for InferenceConfig {
             batch_size: 512,
             context_size: 2048,
             add_bos: true,
+            stop: StopCriteria::default(),
+            seed: None,
+        }
+    }
+}
+
+impl InferenceConfig {
+    pub fn for_column(&self, column: &Column) -> Self {
+        let Some(stop) = &column.settings.stop else {
+            return self.clone();
+        };
+
+        Self {
+            max_tokens: stop.max_tokens.unwrap_or(self.max_tokens),
+            stop: StopCriteria::from_settings(stop),
+            ..self.clone()
         }
     }
 }
@@ -167,7 +313,7 @@ static RANDOM_INT_SINGLE_REGEX: OnceLock<Regex> = OnceLock::new();
 static RANDOM_INT_RANGE_REGEX: OnceLock<Regex> = OnceLock::new();
 
 fn get_column_ref_regex() -> &'static Regex {
-    COLUMN_REF_REGEX.get_or_init(|| Regex::new(r"@(\w+)").expect("Invalid regex pattern"))
+    COLUMN_REF_REGEX.get_or_init(|| Regex::new(r"@(\w+)(?:\.(\w+))?").expect("Invalid regex pattern"))
 }
 
 fn get_random_int_single_regex() -> &'static Regex {
@@ -198,6 +344,197 @@ pub struct DraftRow {
     pub data: Vec<RowData>,
 }
 
+/// Generation run recorded in the provenance of the cells it produces
+#[derive(Debug, Clone)]
+pub struct GenerationJob {
+    pub generation_id: String,
+    pub model_id: i64,
+}
+
+/// Values of a generated row, with the provenance of every cell that went through the model keyed by column id
+#[derive(Debug, Clone, Default)]
+pub struct GeneratedRow {
+    pub data: Vec<RowData>,
+    pub provenance: HashMap<String, CellProvenance>,
+    /// Problems that didn't stop the row from being generated, such as a cell left empty
+    pub warnings: Vec<String>,
+}
+
+impl GeneratedRow {
+    fn for_job(mut self, job: &GenerationJob) -> Self {
+        for cell in self.provenance.values_mut() {
+            cell.model_id = Some(job.model_id);
+            cell.generation_id = Some(job.generation_id.clone());
+        }
+        self
+    }
+}
+
+/// Stable FNV-1a hash, so provenance hashes stay comparable across builds
+fn content_hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Prompt a generation would send for one or more columns
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptPreview {
+    /// Several columns when the dataset generates `jsonObject` rows
+    pub column_ids: Vec<i64>,
+    pub prompt: String,
+    /// Approximation, the exact count depends on the tokenizer of the model
+    pub estimated_tokens: usize,
+}
+
+impl PromptPreview {
+    fn new(columns: &[Column], prompt: String) -> Self {
+        Self {
+            column_ids: columns.iter().filter_map(|column| column.id).collect(),
+            estimated_tokens: estimate_tokens(&prompt),
+            prompt,
+        }
+    }
+}
+
+/// Rough token count: about four characters per token, one per CJK character
+pub fn estimate_tokens(text: &str) -> usize {
+    let wide = text.chars().filter(|c| *c >= '\u{2E80}').count();
+    let narrow = text.chars().count() - wide;
+    wide + narrow.div_ceil(4)
+}
+
+/// Orders columns so every column comes after the ones its rules (matched by `pattern`) and conditions
+/// refer to, does not need a loaded model
+pub fn sort_columns_by_dependency(columns: &[Column], pattern: &str) -> Result<Vec<Column>, String> {
+    if columns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let regex = Regex::new(pattern).map_err(|e| format!("Failed to compile regex pattern '{}': {}", pattern, e))?;
+
+    let name_to_index: HashMap<&str, usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, col)| (col.name.as_str(), i))
+        .collect();
+
+    let mut reverse_deps: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut in_degree = vec![0; columns.len()];
+
+    for (i, column) in columns.iter().enumerate() {
+        let conditions = &column.settings.conditions;
+        let mut dep_names: Vec<&str> = conditions
+            .iter()
+            .map(|condition| condition.column.trim().trim_start_matches('@'))
+            .collect();
+
+        for rules in std::iter::once(&column.rules).chain(conditions.iter().map(|condition| &condition.rules)) {
+            dep_names.extend(
+                regex
+                    .captures_iter(rules)
+                    .filter_map(|cap| cap.get(1))
+                    .map(|m| m.as_str()),
+            );
+        }
+
+        for dep_name in dep_names {
+            if let Some(&dep_index) = name_to_index.get(dep_name) {
+                if dep_index != i {
+                    reverse_deps.entry(dep_index).or_insert_with(Vec::new).push(i);
+                    in_degree[i] += 1;
+                }
+            }
+        }
+    }
+
+    let mut queue: VecDeque<usize> = in_degree
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &degree)| if degree == 0 { Some(i) } else { None })
+        .collect();
+
+    let mut sorted_indices = Vec::with_capacity(columns.len());
+
+    while let Some(current_index) = queue.pop_front() {
+        sorted_indices.push(current_index);
+
+        if let Some(dependents) = reverse_deps.get(&current_index) {
+            for &dependent_idx in dependents {
+                in_degree[dependent_idx] -= 1;
+                if in_degree[dependent_idx] == 0 {
+                    queue.push_back(dependent_idx);
+                }
+            }
+        }
+    }
+
+    if sorted_indices.len() != columns.len() {
+        return Err("Circular dependency detected in column rules".to_string());
+    }
+
+    Ok(sorted_indices.into_iter().map(|i| columns[i].clone()).collect())
+}
+
+/// Row of the parent dataset that child rows are generated for
+#[derive(Debug, Clone)]
+pub struct ParentRow {
+    pub dataset_id: i64,
+    pub row_id: i64,
+}
+
+/// Range the next value of a column with a distribution target should fall in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl TargetRange {
+    pub fn contains(&self, value: &str) -> bool {
+        value
+            .trim()
+            .parse::<f64>()
+            .is_ok_and(|value| value >= self.min && value <= self.max)
+    }
+
+    /// Brings `value` back into the range, integer columns stay on whole numbers
+    pub fn clamp(&self, value: &str, column_type: &str) -> String {
+        let Ok(parsed) = value.trim().parse::<f64>() else {
+            return value.to_string();
+        };
+
+        if column_type == "INT" {
+            let (min, max) = (self.min.ceil(), self.max.floor());
+            let clamped = if min <= max {
+                parsed.round().clamp(min, max)
+            } else {
+                ((self.min + self.max) / 2.0).round()
+            };
+            (clamped as i64).to_string()
+        } else {
+            parsed.clamp(self.min, self.max).to_string()
+        }
+    }
+}
+
+/// Per-cell context rendered into the prompt alongside the column rule
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    pub template: PromptTemplate,
+    pub dataset_description: String,
+    pub include_context: bool,
+    pub locale: Locale,
+    pub examples: Vec<String>,
+    pub exclusions: Vec<String>,
+    /// Fields of the rows sampled for `REFERENCE` columns, keyed by column name then field name
+    pub references: HashMap<String, HashMap<String, String>>,
+    /// Range the value is steered towards, from the column distribution
+    pub target: Option<TargetRange>,
+}
+
 #[derive(Clone)]
 pub struct GenerationService {
     pub db: DatabaseService,
@@ -209,6 +546,40 @@ pub struct GenerationService {
 }
 
 const MAX_CACHED_MODELS: usize = 2;
+const BENCHMARK_ROUNDS: usize = 3;
+const MAX_UNIQUE_ATTEMPTS: usize = 5;
+const JSON_ROW_TOKENS_PER_COLUMN: usize = 64;
+const MAX_DISTRIBUTION_ATTEMPTS: usize = 3;
+const MAX_PII_ATTEMPTS: usize = 3;
+
+/// Opening and closing quotes a model may wrap a whole value in, ASCII first
+const QUOTE_PAIRS: &[(char, char)] = &[
+    ('"', '"'),
+    ('\'', '\''),
+    ('\u{201c}', '\u{201d}'),
+    ('\u{2018}', '\u{2019}'),
+    ('\u{201e}', '\u{201c}'),
+    ('\u{201e}', '\u{201d}'),
+    ('\u{201a}', '\u{2018}'),
+    ('\u{ab}', '\u{bb}'),
+    ('\u{bb}', '\u{ab}'),
+    ('\u{300c}', '\u{300d}'),
+    ('\u{300e}', '\u{300f}'),
+];
+
+// (name, type, type details, rule) of the synthetic cells run by `benchmark_model`
+const BENCHMARK_WORKLOAD: &[(&str, &str, &str, &str)] = &[
+    ("first_name", "TEXT", "", "A realistic first name"),
+    ("city", "TEXT", "", "A city located in Europe"),
+    ("age", "INT", "", "The age of an adult between 18 and 90"),
+    ("price", "FLOAT", "", "The price of a grocery item, with two decimals"),
+    (
+        "address",
+        "JSON",
+        r#"{"street": "string", "zip_code": "string", "city": "string"}"#,
+        "A postal address in France",
+    ),
+];
 
 impl GenerationService {
     pub fn new(
@@ -261,142 +632,912 @@ impl GenerationService {
             .lock()
             .map_err(|e| GenerationError::ModelError(format!("Failed to lock model cache: {}", e)))?;
 
-        cache.clear();
-        Ok(())
+        cache.clear();
+        Ok(())
+    }
+
+    pub fn benchmark_model(&self, model_id: i64, gpu_layers: u32) -> Result<ModelBenchmark, GenerationError> {
+        let model_info = self
+            .model_service
+            .get_model_info(model_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+        let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+        let model_path = self.model_service.models_dir.join(model_info.filename.clone());
+
+        // loaded outside of the cache so the load time is measured on a cold model
+        let memory_before = resident_memory_bytes();
+        let load_started_at = Instant::now();
+        let model = LlamaModel::load_from_file(&*self.llama_backend, &model_path, &params)?;
+        let load_time = load_started_at.elapsed();
+
+        let config = InferenceConfig::default();
+        let ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(config.context_size))
+            .with_n_batch(config.batch_size as u32)
+            .with_n_ubatch(config.batch_size as u32);
+
+        let mut ctx = model.new_context(&*self.llama_backend, ctx_params)?;
+
+        let columns: Vec<Column> = BENCHMARK_WORKLOAD
+            .iter()
+            .enumerate()
+            .map(|(i, (name, column_type, details, rules))| Column {
+                id: Some(i as i64 + 1),
+                table_name: "benchmark".to_string(),
+                dataset_id: 0,
+                name: name.to_string(),
+                column_type: column_type.to_string(),
+                column_type_details: Some(details.to_string()),
+                rules: rules.to_string(),
+                position: i as i64 + 1,
+                settings: ColumnSettings::default(),
+            })
+            .collect();
+
+        let tokens_generated = Cell::new(0u64);
+        let mut inference_time = Duration::ZERO;
+        let mut cells_total: u32 = 0;
+        let mut cells_parsed: u32 = 0;
+
+        for _ in 0..BENCHMARK_ROUNDS {
+            for column in &columns {
+                let prompt = self.prepare_prompt(&columns, column, &Vec::new(), &PromptContext::default())?;
+
+                let started_at = Instant::now();
+                let response = self.inference(
+                    &model,
+                    &mut ctx,
+                    &prompt,
+                    &config,
+                    Some(|_: &str| tokens_generated.set(tokens_generated.get() + 1)),
+                )?;
+                inference_time += started_at.elapsed();
+
+                cells_total += 1;
+                if Self::is_parsable_as(&column.column_type, &response, Locale::En) {
+                    cells_parsed += 1;
+                }
+            }
+        }
+
+        // what loading the weights, the context buffers and the workload added to the process
+        let memory_usage_bytes = match (memory_before, resident_memory_bytes()) {
+            (Some(before), Some(after)) => after.saturating_sub(before),
+            // no RSS probe on this platform, the weights are the bulk of it
+            _ => model.size(),
+        };
+
+        let inference_seconds = inference_time.as_secs_f64();
+        let tokens_per_second = if inference_seconds > 0.0 {
+            tokens_generated.get() as f64 / inference_seconds
+        } else {
+            0.0
+        };
+
+        let benchmark = ModelBenchmark {
+            id: None,
+            model_id,
+            gpu_layers,
+            load_time_ms: load_time.as_millis() as u64,
+            tokens_generated: tokens_generated.get(),
+            tokens_per_second,
+            memory_usage_bytes,
+            cells_total,
+            cells_parsed,
+            parse_success_rate: if cells_total > 0 {
+                cells_parsed as f64 / cells_total as f64 * 100.0
+            } else {
+                0.0
+            },
+            created_at: String::new(),
+        };
+
+        self.model_service
+            .save_benchmark(&benchmark)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))
+    }
+
+    fn is_parsable_as(column_type: &str, response: &str, locale: Locale) -> bool {
+        match column_type {
+            "INT" => Self::parse_integer(response, locale).is_some(),
+            "FLOAT" => Self::parse_float(response, locale).is_some(),
+            "BOOL" => locale.parse_bool(response).is_some(),
+            "JSON" => Self::parse_json(response).is_ok(),
+            _ => !Self::clean_text_artifacts(response).is_empty(),
+        }
+    }
+
+    pub fn generate(
+        &self,
+        dataset_id: i64,
+        job: &GenerationJob,
+        total_rows_to_generate: i64,
+        gpu_layers: u32,
+        cancel_token: CancellationToken,
+        progress_callback: impl Fn(GeneratedRow, i64, i64) + Send + 'static,
+    ) -> Result<(), GenerationError> {
+        eprintln!("Generating {} rows with {} GPU layers", total_rows_to_generate, gpu_layers);
+        let model = self.load_model(job.model_id, gpu_layers)?;
+        let config = InferenceConfig::default();
+        let mut ctx = self.new_context(&model, &config)?;
+
+        for row_index in 0..total_rows_to_generate {
+            if cancel_token.is_cancelled() {
+                return Err(GenerationError::DatabaseError(
+                    "Generation cancelled by user".to_string(),
+                ));
+            }
+
+            let generated = self
+                .generate_row(dataset_id, None, &model, &mut ctx, &config, &cancel_token)?
+                .for_job(job);
+
+            progress_callback(generated, row_index + 1, total_rows_to_generate);
+        }
+
+        Ok(())
+    }
+
+    /// Generates rows of `dataset_id` followed, depth first, by the child rows of every related dataset.
+    /// Rows are persisted as they are generated and reported with the id of the dataset they belong to, along
+    /// with the warnings raised while generating them
+    pub fn generate_nested(
+        &self,
+        dataset_id: i64,
+        job: &GenerationJob,
+        total_rows_to_generate: i64,
+        gpu_layers: u32,
+        cancel_token: CancellationToken,
+        progress_callback: impl Fn(i64, Row, &[String], i64, i64) + Send + 'static,
+    ) -> Result<(), GenerationError> {
+        let model = self.load_model(job.model_id, gpu_layers)?;
+        let config = InferenceConfig::default();
+        let mut ctx = self.new_context(&model, &config)?;
+        let mut rng = rand::thread_rng();
+
+        for row_index in 0..total_rows_to_generate {
+            let mut pending: Vec<(i64, Option<ParentRow>)> = vec![(dataset_id, None)];
+
+            while let Some((current_dataset_id, parent)) = pending.pop() {
+                if cancel_token.is_cancelled() {
+                    return Err(GenerationError::DatabaseError(
+                        "Generation cancelled by user".to_string(),
+                    ));
+                }
+
+                let mut generated = self
+                    .generate_row(
+                        current_dataset_id,
+                        parent.as_ref(),
+                        &model,
+                        &mut ctx,
+                        &config,
+                        &cancel_token,
+                    )?
+                    .for_job(job);
+                let row = self
+                    .dataset_service
+                    .add_row_with_provenance(current_dataset_id, &generated.data, &generated.provenance)
+                    .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+                if let Err(e) = self.dataset_service.flag_pii(current_dataset_id, &row) {
+                    generated.warnings.push(e.to_string());
+                }
+
+                let relations = self
+                    .dataset_service
+                    .get_child_relations(current_dataset_id)
+                    .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+                // pushed in reverse so children are generated in relation order
+                for relation in relations.iter().rev() {
+                    let child_count = rng.gen_range(relation.min_children..=relation.max_children);
+                    let child_parent = ParentRow {
+                        dataset_id: current_dataset_id,
+                        row_id: row.id,
+                    };
+                    pending.extend((0..child_count).map(|_| (relation.child_dataset_id, Some(child_parent.clone()))));
+                }
+
+                progress_callback(
+                    current_dataset_id,
+                    row,
+                    &generated.warnings,
+                    row_index + 1,
+                    total_rows_to_generate,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_model(&self, model_id: i64, gpu_layers: u32) -> Result<Arc<LlamaModel>, GenerationError> {
+        let model_info = self
+            .model_service
+            .get_model_info(model_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+        let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+        let model_path = self.model_service.models_dir.join(model_info.filename.clone());
+
+        self.get_or_load_model(&model_path, &params)
+    }
+
+    fn new_context<'a>(
+        &self,
+        model: &'a LlamaModel,
+        config: &InferenceConfig,
+    ) -> Result<llama_cpp_2::context::LlamaContext<'a>, GenerationError> {
+        let ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(config.context_size))
+            .with_n_batch(config.batch_size as u32)
+            .with_n_ubatch(config.batch_size as u32);
+
+        Ok(model.new_context(&*self.llama_backend, ctx_params)?)
+    }
+
+    /// Columns of `dataset_id` ordered so that `@column` references are generated first
+    fn sorted_columns(dataset_service: &DatasetService, dataset_id: i64) -> Result<Vec<Column>, GenerationError> {
+        let columns = dataset_service
+            .get_columns(dataset_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+        sort_columns_by_dependency(&columns, r"@(\w+)").map_err(GenerationError::DatabaseError)
+    }
+
+    /// Generates one row of `dataset_id`; with a `parent`, its values are exposed as `@parent.field` and
+    /// `REFERENCE` columns pointing at the parent dataset store the parent row instead of a sampled one
+    pub fn generate_row(
+        &self,
+        dataset_id: i64,
+        parent: Option<&ParentRow>,
+        model: &LlamaModel,
+        ctx: &mut llama_cpp_2::context::LlamaContext,
+        config: &InferenceConfig,
+        cancel_token: &CancellationToken,
+    ) -> Result<GeneratedRow, GenerationError> {
+        let columns = Self::sorted_columns(&self.dataset_service, dataset_id)?;
+        let columns = columns.as_slice();
+
+        if columns.is_empty() {
+            return Ok(GeneratedRow::default());
+        }
+
+        let settings = self
+            .dataset_service
+            .get_settings(dataset_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+        let mut dataset_context = Self::build_dataset_context(&self.dataset_service, dataset_id)?;
+        let mut data = Self::sample_references(&self.dataset_service, columns, parent, &mut dataset_context)?;
+        let mut provenance = HashMap::new();
+        let mut warnings = Vec::new();
+
+        let mut rng = rand::thread_rng();
+
+        // Only the sampled references are known up front, and they never need generating
+        let levels: Vec<Vec<Column>> = columns
+            .chunks(settings.group_size(columns.len()))
+            .flat_map(|group| Self::condition_levels(group, &data))
+            .collect();
+
+        for level in &levels {
+            // Conditions and null rolls are settled once per cell, skipped cells are stored empty (or null in
+            // nullable columns) without being sent to the model
+            let mut missing: Vec<Column> = Vec::new();
+            for column in level {
+                let Some(column_id) = column.id else {
+                    continue;
+                };
+                if data.iter().any(|row_data| row_data.column_id == column_id.to_string()) {
+                    continue;
+                }
+
+                let (column, null_probability) = Self::resolve_conditions(columns, column, &data);
+                if null_probability.is_some_and(|probability| rng.gen_bool(probability.clamp(0.0, 1.0))) {
+                    data.push(RowData {
+                        column_id: column_id.to_string(),
+                        value: (!column.settings.nullable).then(String::new),
+                    });
+                } else {
+                    missing.push(column);
+                }
+            }
+
+            // One target per cell, the JSON check and the per-cell retries steer towards the same window
+            let mut targets = HashMap::new();
+            for column in &missing {
+                if let Some(column_id) = column.id {
+                    targets.insert(
+                        column_id.to_string(),
+                        Self::distribution_target(&self.dataset_service, dataset_id, column)?,
+                    );
+                }
+            }
+
+            if settings.row_strategy == RowStrategy::JsonObject && !missing.is_empty() {
+                if cancel_token.is_cancelled() {
+                    return Err(GenerationError::DatabaseError(
+                        "Generation cancelled by user".to_string(),
+                    ));
+                }
+
+                let prompt = Self::build_row_prompt(columns, &missing, &data, &dataset_context)?;
+                let prompt_hash = content_hash(&prompt);
+                let group_config = InferenceConfig {
+                    seed: Some(config.seed.unwrap_or_else(rand::random)),
+                    ..config.clone()
+                };
+
+                for row_data in
+                    self.generate_json_group(model, ctx, &group_config, &missing, &prompt, dataset_context.locale)?
+                {
+                    let column = missing
+                        .iter()
+                        .find(|column| column.id.is_some_and(|id| id.to_string() == row_data.column_id))
+                        .expect("Generated value should belong to the group");
+
+                    // Values off target are left to the per-cell loop, which steers them
+                    let target = targets.get(&row_data.column_id).copied().flatten();
+                    if target.is_some_and(|target| !target.contains(row_data.value_or_empty())) {
+                        continue;
+                    }
+                    if settings.pii_policy == PiiPolicy::Regenerate && !scan_pii(row_data.value_or_empty()).is_empty() {
+                        continue;
+                    }
+
+                    if !self.is_duplicate_value(dataset_id, column, row_data.value_or_empty())? {
+                        provenance.insert(
+                            row_data.column_id.clone(),
+                            CellProvenance {
+                                prompt_hash: prompt_hash.clone(),
+                                rules_hash: content_hash(&column.rules),
+                                attempts: 1,
+                                seed: group_config.seed,
+                                ..Default::default()
+                            },
+                        );
+                        data.push(row_data);
+                    }
+                }
+            }
+
+            for column in &missing {
+                let column_id = column.id.expect("Column should have an ID").to_string();
+                if data.iter().any(|row_data| row_data.column_id == column_id) {
+                    continue;
+                }
+
+                if cancel_token.is_cancelled() {
+                    return Err(GenerationError::DatabaseError(
+                        "Generation cancelled by user".to_string(),
+                    ));
+                }
+
+                let column_config = config.for_column(column);
+                let target = targets.get(&column_id).copied().flatten();
+                let mut context =
+                    Self::build_prompt_context(&self.dataset_service, dataset_id, column, &dataset_context, target)?;
+                let mut attempts = 0;
+                let mut distribution_attempts = 0;
+                let mut pii_attempts = 0;
+                let mut inferences = 0;
+
+                let value = loop {
+                    let prompt = self.prepare_prompt(columns, column, &data, &context)?;
+                    // A fixed seed still moves on between retries, or they would repeat the same completion
+                    let seed = column_config
+                        .seed
+                        .map_or_else(rand::random, |seed| seed.wrapping_add(inferences as u32));
+                    let attempt_config = InferenceConfig {
+                        seed: Some(seed),
+                        ..column_config.clone()
+                    };
+                    let Some(mut value) =
+                        self.generate_value(model, ctx, &prompt, &attempt_config, column, context.locale)?
+                    else {
+                        break None;
+                    };
+                    inferences += 1;
+
+                    if let Some(target) = context.target.filter(|target| !target.contains(&value)) {
+                        distribution_attempts += 1;
+                        if distribution_attempts < MAX_DISTRIBUTION_ATTEMPTS {
+                            continue;
+                        }
+                        value = target.clamp(&value, &column.column_type);
+                    }
+
+                    // Values still matching after the last attempt are kept and flagged once the row is saved
+                    if settings.pii_policy == PiiPolicy::Regenerate
+                        && pii_attempts + 1 < MAX_PII_ATTEMPTS
+                        && !scan_pii(&value).is_empty()
+                    {
+                        pii_attempts += 1;
+                        context.exclusions.push(value);
+                        continue;
+                    }
+
+                    attempts += 1;
+                    if !self.is_duplicate_value(dataset_id, column, &value)? {
+                        break Some((value, prompt, seed));
+                    }
+
+                    // Empty cells never collide, the row is kept with this one left empty like a null roll
+                    if attempts >= MAX_UNIQUE_ATTEMPTS {
+                        warnings.push(format!(
+                            "Could not generate a unique value for column '{}' after {} attempts, the cell was left empty",
+                            column.name, attempts
+                        ));
+                        data.push(RowData {
+                            column_id: column_id.clone(),
+                            value: (!column.settings.nullable).then(String::new),
+                        });
+                        break None;
+                    }
+
+                    context.exclusions.push(value);
+                };
+
+                if let Some((value, prompt, seed)) = value {
+                    provenance.insert(
+                        column_id.clone(),
+                        CellProvenance {
+                            prompt_hash: content_hash(&prompt),
+                            rules_hash: content_hash(&column.rules),
+                            attempts: inferences,
+                            seed: Some(seed),
+                            ..Default::default()
+                        },
+                    );
+                    data.push(RowData {
+                        column_id,
+                        value: Some(value),
+                    });
+                }
+            }
+        }
+
+        if !settings.record_provenance {
+            provenance.clear();
+        }
+
+        Ok(GeneratedRow {
+            data,
+            provenance,
+            warnings,
+        })
+    }
+
+    /// Renders the prompts a generation of `dataset_id` would send, without loading a model. Upstream values come
+    /// from `values` (by column id) when provided, placeholders otherwise
+    pub fn preview_prompts(
+        dataset_service: &DatasetService,
+        dataset_id: i64,
+        values: &HashMap<i64, String>,
+    ) -> Result<Vec<PromptPreview>, GenerationError> {
+        let columns = Self::sorted_columns(dataset_service, dataset_id)?;
+        let columns = columns.as_slice();
+
+        let settings = dataset_service
+            .get_settings(dataset_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+        let mut dataset_context = Self::build_dataset_context(dataset_service, dataset_id)?;
+        let mut data = Self::sample_references(dataset_service, columns, None, &mut dataset_context)?;
+
+        for (column_id, value) in values {
+            data.retain(|row_data| row_data.column_id != column_id.to_string());
+            data.push(RowData {
+                column_id: column_id.to_string(),
+                value: Some(value.clone()),
+            });
+        }
+
+        let mut previews = Vec::new();
+
+        let levels: Vec<Vec<Column>> = columns
+            .chunks(settings.group_size(columns.len()))
+            .flat_map(|group| Self::condition_levels(group, &data))
+            .collect();
+
+        for level in &levels {
+            let pending: Vec<Column> = level
+                .iter()
+                .map(|column| Self::resolve_conditions(columns, column, &data).0)
+                .collect();
+
+            if settings.row_strategy == RowStrategy::JsonObject {
+                let prompt = Self::build_row_prompt(columns, &pending, &data, &dataset_context)?;
+                previews.push(PromptPreview::new(&pending, prompt));
+            } else {
+                for column in &pending {
+                    let target = Self::distribution_target(dataset_service, dataset_id, column)?;
+                    let context =
+                        Self::build_prompt_context(dataset_service, dataset_id, column, &dataset_context, target)?;
+                    let prompt = Self::build_cell_prompt(columns, column, &data, &context)?;
+                    previews.push(PromptPreview::new(std::slice::from_ref(column), prompt));
+                }
+            }
+
+            data.extend(pending.iter().filter_map(|column| {
+                Some(RowData {
+                    column_id: column.id?.to_string(),
+                    value: Some(format!("<{}>", column.name)),
+                })
+            }));
+        }
+
+        Ok(previews)
+    }
+
+    /// Splits the columns of `group` that have no value yet into levels generated one after the other, so a
+    /// condition never reads a column generated in the same completion
+    pub fn condition_levels(group: &[Column], row_data: &[RowData]) -> Vec<Vec<Column>> {
+        let mut pending: Vec<Column> = group
+            .iter()
+            .filter(|column| {
+                column
+                    .id
+                    .is_some_and(|id| !row_data.iter().any(|row_data| row_data.column_id == id.to_string()))
+            })
+            .cloned()
+            .collect();
+
+        let mut levels = Vec::new();
+        while !pending.is_empty() {
+            let waiting = |column: &Column| {
+                column.settings.conditions.iter().any(|condition| {
+                    let name = condition.column.trim().trim_start_matches('@');
+                    pending
+                        .iter()
+                        .any(|candidate| candidate.name == name && candidate.id != column.id)
+                })
+            };
+            let (level, rest): (Vec<Column>, Vec<Column>) =
+                pending.iter().cloned().partition(|column| !waiting(column));
+            if level.is_empty() {
+                // Columns are sorted by dependency, a cycle slipping through is generated in one go
+                levels.push(std::mem::take(&mut pending));
+                continue;
+            }
+            levels.push(level);
+            pending = rest;
+        }
+
+        levels
+    }
+
+    /// Column with the rules of its first matching condition, and the null probability that applies
+    pub fn resolve_conditions(columns: &[Column], column: &Column, row_data: &[RowData]) -> (Column, Option<f64>) {
+        let mut resolved = column.clone();
+        let mut null_probability = column.settings.null_probability;
+
+        let matched = column.settings.conditions.iter().find(|condition| {
+            let name = condition.column.trim().trim_start_matches('@');
+            let value = columns
+                .iter()
+                .find(|candidate| candidate.name == name)
+                .and_then(|candidate| candidate.id)
+                .and_then(|id| row_data.iter().find(|row_data| row_data.column_id == id.to_string()))
+                .and_then(|row_data| row_data.value.as_deref());
+            condition.matches(value)
+        });
+
+        if let Some(condition) = matched {
+            if !condition.rules.trim().is_empty() {
+                resolved.rules = condition.rules.clone();
+            }
+            null_probability = condition.null_probability.or(null_probability);
+        }
+
+        (resolved, null_probability)
+    }
+
+    /// Samples a referenced row for every `REFERENCE` column and exposes its fields to the rules
+    fn sample_references(
+        dataset_service: &DatasetService,
+        columns: &[Column],
+        parent: Option<&ParentRow>,
+        context: &mut PromptContext,
+    ) -> Result<Vec<RowData>, GenerationError> {
+        let mut data = Vec::new();
+
+        if let Some(parent) = parent {
+            let parent_reference = ReferenceSettings {
+                dataset_id: parent.dataset_id,
+                column_id: None,
+                weight_column_id: None,
+            };
+            let parent_row = dataset_service
+                .get_reference_row(&parent_reference, parent.row_id)
+                .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+            context.references.insert("parent".to_string(), parent_row.fields);
+        }
+
+        for column in columns.iter().filter(|column| column.column_type == "REFERENCE") {
+            let (Some(column_id), Some(reference)) = (column.id, &column.settings.reference) else {
+                continue;
+            };
+
+            let sampled = match parent {
+                Some(parent) if parent.dataset_id == reference.dataset_id => {
+                    dataset_service.get_reference_row(reference, parent.row_id)
+                }
+                _ => dataset_service.sample_reference(reference),
+            }
+            .map_err(|e| GenerationError::DatabaseError(format!("Column '{}': {}", column.name, e)))?;
+
+            data.push(RowData {
+                column_id: column_id.to_string(),
+                value: Some(sampled.value),
+            });
+            context.references.insert(column.name.clone(), sampled.fields);
+        }
+
+        Ok(data)
+    }
+
+    /// Prompt context shared by every column of the dataset
+    pub fn build_dataset_context(
+        dataset_service: &DatasetService,
+        dataset_id: i64,
+    ) -> Result<PromptContext, GenerationError> {
+        let dataset = dataset_service
+            .find_by_id(dataset_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+        let template = dataset_service
+            .get_prompt_template(dataset_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+
+        Ok(PromptContext {
+            template,
+            dataset_description: dataset.description,
+            include_context: dataset.settings.include_context,
+            locale: dataset.settings.locale,
+            ..Default::default()
+        })
+    }
+
+    /// `target` is drawn by the caller, so every check of the same cell sees the same window
+    pub fn build_prompt_context(
+        dataset_service: &DatasetService,
+        dataset_id: i64,
+        column: &Column,
+        dataset_context: &PromptContext,
+        target: Option<TargetRange>,
+    ) -> Result<PromptContext, GenerationError> {
+        let mut context = dataset_context.clone();
+
+        if let (Some(few_shot), Some(column_id)) = (&column.settings.few_shot, column.id) {
+            context.examples = dataset_service
+                .get_example_values(dataset_id, column_id, few_shot)
+                .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+        }
+
+        if let (Some(avoid_recent), Some(column_id)) = (column.settings.avoid_recent, column.id) {
+            context.exclusions = dataset_service
+                .get_recent_values(dataset_id, column_id, avoid_recent)
+                .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
+        }
+
+        context.target = target;
+
+        Ok(context)
     }
 
-    pub fn generate(
-        &self,
+    /// Range for the next value of a column with a distribution target, based on the values so far
+    fn distribution_target(
+        dataset_service: &DatasetService,
         dataset_id: i64,
-        model_id: i64,
-        total_rows_to_generate: i64,
-        gpu_layers: u32,
-        cancel_token: CancellationToken,
-        progress_callback: impl Fn(Vec<RowData>, i64, i64) + Send + 'static,
-    ) -> Result<(), GenerationError> {
-        eprintln!("Generating {} rows with {} GPU layers", total_rows_to_generate, gpu_layers);
-        let columns = self
-            .dataset_service
-            .get_columns(dataset_id)
-            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
-        let model_info = self
-            .model_service
-            .get_model_info(model_id)
-            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
-        let sorted_columns = self
-            .sort_columns_by_dependency(&columns, r"@(\w+)")
-            .expect("Failed to sort columns");
-
-        let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
-        let model_path = self.model_service.models_dir.join(model_info.filename.clone());
+        column: &Column,
+    ) -> Result<Option<TargetRange>, GenerationError> {
+        let (Some(distribution), Some(column_id)) = (&column.settings.distribution, column.id) else {
+            return Ok(None);
+        };
+        if !matches!(column.column_type.as_str(), "INT" | "FLOAT") {
+            return Ok(None);
+        }
 
-        let model = self.get_or_load_model(&model_path, &params)?;
-        let config = InferenceConfig::default();
+        let values = dataset_service
+            .get_numeric_values(dataset_id, column_id)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))?;
 
-        let ctx_params = LlamaContextParams::default()
-            .with_n_ctx(NonZeroU32::new(config.context_size))
-            .with_n_batch(config.batch_size as u32)
-            .with_n_ubatch(config.batch_size as u32);
+        Ok(Self::next_target(distribution, &values, &mut rand::thread_rng()))
+    }
 
-        let mut ctx = model.new_context(&*self.llama_backend, ctx_params)?;
+    /// Buckets pick the range furthest behind its share, a normal target samples a window around a draw,
+    /// otherwise the plain `min..=max` bounds apply
+    pub fn next_target(distribution: &DistributionSettings, values: &[f64], rng: &mut impl Rng) -> Option<TargetRange> {
+        let min = distribution.min.unwrap_or(f64::NEG_INFINITY);
+        let max = distribution.max.unwrap_or(f64::INFINITY);
+
+        let range = if !distribution.buckets.is_empty() {
+            let total_share: f64 = distribution.buckets.iter().map(|bucket| bucket.share).sum();
+            let mut counts = vec![0usize; distribution.buckets.len()];
+            for value in values {
+                if let Some(index) = distribution.bucket_index(*value) {
+                    counts[index] += 1;
+                }
+            }
 
-        for row_index in 0..total_rows_to_generate {
-            if cancel_token.is_cancelled() {
-                return Err(GenerationError::DatabaseError(
-                    "Generation cancelled by user".to_string(),
-                ));
+            let expected_total = (values.len() + 1) as f64;
+            let (bucket, _) = distribution
+                .buckets
+                .iter()
+                .zip(counts)
+                .map(|(bucket, count)| (bucket, bucket.share / total_share * expected_total - count as f64))
+                .fold(None, |best: Option<(_, f64)>, (bucket, deficit)| match best {
+                    Some((_, best_deficit)) if best_deficit >= deficit => best,
+                    _ => Some((bucket, deficit)),
+                })?;
+
+            TargetRange {
+                min: bucket.min,
+                max: bucket.max,
+            }
+        } else if let (Some(mean), Some(stddev)) = (distribution.mean, distribution.stddev) {
+            // Box-Muller draw from the target normal
+            let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+            let u2: f64 = rng.gen();
+            let sample = mean + stddev * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+            let half_width = (stddev / 4.0).max(0.5);
+
+            TargetRange {
+                min: sample - half_width,
+                max: sample + half_width,
             }
+        } else {
+            TargetRange { min, max }
+        };
 
-            let row_data = self.generate_row(
-                &model,
-                &mut ctx,
-                &config,
-                &sorted_columns,
-                &cancel_token,
-            )?;
+        let range = TargetRange {
+            min: range.min.max(min),
+            max: range.max.min(max),
+        };
 
-            progress_callback(row_data, row_index + 1, total_rows_to_generate);
+        if range.min > range.max {
+            // Window entirely out of bounds, fall back on the closest bound
+            let bound = if range.min > max { max } else { min };
+            return Some(TargetRange { min: bound, max: bound });
         }
 
-        Ok(())
+        Some(range)
     }
 
-    pub fn generate_row(
+    fn range_hint(target: Option<&TargetRange>, locale: Locale) -> String {
+        let Some(target) = target.filter(|target| target.min.is_finite() && target.max.is_finite()) else {
+            return String::new();
+        };
+
+        let format_number = |value: f64| {
+            let rounded = (value * 100.0).round() / 100.0;
+            rounded
+                .to_string()
+                .replace('.', &locale.decimal_separator().to_string())
+        };
+
+        locale
+            .strings()
+            .range_hint
+            .replace("{min}", &format_number(target.min))
+            .replace("{max}", &format_number(target.max))
+    }
+
+    /// Asks for every column of `group` in one JSON completion, keeping only the values that fit their type
+    fn generate_json_group(
         &self,
         model: &LlamaModel,
         ctx: &mut llama_cpp_2::context::LlamaContext,
         config: &InferenceConfig,
-        columns: &[Column],
-        cancel_token: &CancellationToken,
+        group: &[Column],
+        prompt: &str,
+        locale: Locale,
     ) -> Result<Vec<RowData>, GenerationError> {
-        if columns.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let mut data: Vec<RowData> = Vec::new();
+        let json_config = InferenceConfig {
+            max_tokens: config.max_tokens.max(JSON_ROW_TOKENS_PER_COLUMN * group.len()),
+            stop: StopCriteria::json_object(),
+            ..config.clone()
+        };
 
-        for column in columns {
-            if cancel_token.is_cancelled() {
-                return Err(GenerationError::DatabaseError(
-                    "Generation cancelled by user".to_string(),
-                ));
-            }
+        let response = self.inference(model, ctx, prompt, &json_config, None::<fn(&str)>)?;
 
-            let prompt = self.prepare_prompt(columns, column, &data)?;
+        let Ok(Value::Object(object)) = Self::parse_json(&response) else {
+            return Ok(Vec::new());
+        };
 
-            if column.column_type == "TEXT" {
-                let value = self.generate_text(model, ctx, &prompt, config)?;
+        Ok(group
+            .iter()
+            .filter_map(|column| {
+                let value = Self::coerce_json_value(&column.column_type, object.get(&column.name)?, locale)?;
+                Some(RowData {
+                    column_id: column.id?.to_string(),
+                    value: Some(value),
+                })
+            })
+            .collect())
+    }
 
-                let row_data: RowData = RowData {
-                    column_id: column.id.expect("Column should have an ID").to_string(),
-                    value,
-                };
-                data.push(row_data);
-            }
+    pub fn coerce_json_value(column_type: &str, value: &Value, locale: Locale) -> Option<String> {
+        match (column_type, value) {
+            (_, Value::Null) => None,
+            ("TEXT", Value::String(text)) => Some(Self::clean_text_artifacts(text)).filter(|text| !text.is_empty()),
+            ("TEXT", Value::Number(_) | Value::Bool(_)) => Some(value.to_string()),
+            ("INT", Value::Number(number)) => number.as_f64().map(|n| (n.round() as i64).to_string()),
+            ("INT", Value::String(text)) => Self::parse_integer(text, locale).map(|n| n.to_string()),
+            ("FLOAT", Value::Number(number)) => number.as_f64().map(|n| n.to_string()),
+            ("FLOAT", Value::String(text)) => Self::parse_float(text, locale).map(|n| n.to_string()),
+            ("BOOL", Value::Bool(flag)) => Some(flag.to_string()),
+            ("BOOL", Value::String(text)) => locale.parse_bool(text).map(|b| b.to_string()),
+            ("JSON", Value::Object(_) | Value::Array(_)) => Some(value.to_string()),
+            ("JSON", Value::String(text)) => Self::parse_json(text).ok().map(|json| json.to_string()),
+            _ => None,
+        }
+    }
 
-            if column.column_type == "INT" {
-                let value = self.generate_integer(model, ctx, &prompt, config)?;
+    /// True once the first top-level JSON object in `response` has been closed
+    pub fn is_json_object_complete(response: &str) -> bool {
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
 
-                let row_data: RowData = RowData {
-                    column_id: column.id.expect("Column should have an ID").to_string(),
-                    value: value.to_string(),
-                };
-                data.push(row_data);
+        for c in response.chars() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
             }
 
-            if column.column_type == "FLOAT" {
-                let value = self.generate_float(model, ctx, &prompt, config)?;
-
-                let row_data: RowData = RowData {
-                    column_id: column.id.expect("Column should have an ID").to_string(),
-                    value: value.to_string(),
-                };
-                data.push(row_data);
+            match c {
+                '"' if depth > 0 => in_string = true,
+                '{' => depth += 1,
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return true;
+                    }
+                }
+                _ => {}
             }
+        }
 
-            if column.column_type == "BOOL" {
-                let value = self.generate_bool(model, ctx, &prompt, config)?;
-                let row_data: RowData = RowData {
-                    column_id: column.id.expect("Column should have an ID").to_string(),
-                    value: value.to_string(),
-                };
+        false
+    }
 
-                data.push(row_data);
-            }
+    fn generate_value(
+        &self,
+        model: &LlamaModel,
+        ctx: &mut llama_cpp_2::context::LlamaContext,
+        prompt: &str,
+        config: &InferenceConfig,
+        column: &Column,
+        locale: Locale,
+    ) -> Result<Option<String>, GenerationError> {
+        let value = match column.column_type.as_str() {
+            "TEXT" => self.generate_text(model, ctx, prompt, config)?,
+            "INT" => self.generate_integer(model, ctx, prompt, config, locale)?.to_string(),
+            "FLOAT" => self.generate_float(model, ctx, prompt, config, locale)?.to_string(),
+            "BOOL" => self.generate_bool(model, ctx, prompt, config, locale)?.to_string(),
+            "JSON" => self.generate_json(model, ctx, prompt, config)?.to_string(),
+            _ => return Ok(None),
+        };
 
-            if column.column_type == "JSON" {
-                let value = self.generate_json(model, ctx, &prompt, config)?;
-                let value_str = value.to_string();
+        Ok(Some(value))
+    }
 
-                let row_data: RowData = RowData {
-                    column_id: column.id.expect("Column should have an ID").to_string(),
-                    value: value_str,
-                };
-                data.push(row_data);
-            }
-        }
+    fn is_duplicate_value(&self, dataset_id: i64, column: &Column, value: &str) -> Result<bool, GenerationError> {
+        let (Some(mode), Some(column_id)) = (column.settings.unique, column.id) else {
+            return Ok(false);
+        };
 
-        Ok(data)
+        self.dataset_service
+            .value_exists(dataset_id, column_id, value, mode, None)
+            .map_err(|e| GenerationError::DatabaseError(e.to_string()))
     }
 
     fn generate_text(
@@ -417,19 +1558,10 @@ impl GenerationService {
         ctx: &mut llama_cpp_2::context::LlamaContext,
         prompt: &str,
         config: &InferenceConfig,
+        locale: Locale,
     ) -> Result<i64, GenerationError> {
         let response = self.inference(model, ctx, prompt, config, None::<fn(&str)>)?;
-
-        let mut numeric_part = String::new();
-        for c in response.chars() {
-            if c.is_numeric() || c == '.' || c == '-' || c == '+' {
-                numeric_part.push(c);
-            } else if !numeric_part.is_empty() {
-                break;
-            }
-        }
-
-        Ok(numeric_part.parse::<f64>().ok().map(|n| n.round() as i64).unwrap_or(0))
+        Ok(Self::parse_integer(&response, locale).unwrap_or(0))
     }
 
     fn generate_float(
@@ -438,19 +1570,10 @@ impl GenerationService {
         ctx: &mut llama_cpp_2::context::LlamaContext,
         prompt: &str,
         config: &InferenceConfig,
+        locale: Locale,
     ) -> Result<f64, GenerationError> {
         let response = self.inference(model, ctx, prompt, config, None::<fn(&str)>)?;
-
-        let mut numeric_part = String::new();
-        for c in response.chars() {
-            if c.is_numeric() || c == '.' || c == '-' || c == '+' {
-                numeric_part.push(c);
-            } else if !numeric_part.is_empty() {
-                break;
-            }
-        }
-
-        Ok(numeric_part.parse::<f64>().unwrap_or(0.0))
+        Ok(Self::parse_float(&response, locale).unwrap_or(0.0))
     }
 
     fn generate_json(
@@ -461,7 +1584,102 @@ impl GenerationService {
         config: &InferenceConfig,
     ) -> Result<Value, GenerationError> {
         let response = self.inference(model, ctx, prompt, config, None::<fn(&str)>)?;
+        Self::parse_json(&response)
+    }
+
+    fn generate_bool(
+        &self,
+        model: &LlamaModel,
+        ctx: &mut llama_cpp_2::context::LlamaContext,
+        prompt: &str,
+        config: &InferenceConfig,
+        locale: Locale,
+    ) -> Result<bool, GenerationError> {
+        let response = self.inference(model, ctx, prompt, config, None::<fn(&str)>)?;
+        Ok(locale.parse_bool(&response).unwrap_or(false))
+    }
+
+    /// First number of `response` in canonical notation, reading separators the way `locale` writes them
+    fn extract_numeric_part(response: &str, locale: Locale) -> String {
+        let chars: Vec<char> = response.chars().map(fold_full_width).collect();
+        let is_digit_at = |i: usize| chars.get(i).is_some_and(|c| c.is_ascii_digit());
+        let mut numeric_part = String::new();
+
+        for (i, &c) in chars.iter().enumerate() {
+            let has_digits = numeric_part.chars().any(|c| c.is_ascii_digit());
+            let is_group =
+                locale.is_group_separator(c) && (1..=3).all(|offset| is_digit_at(i + offset)) && !is_digit_at(i + 4);
+
+            let accepted = match c {
+                '0'..='9' => true,
+                '-' | '+' => numeric_part.is_empty() && is_digit_at(i + 1),
+                '.' | ',' => has_digits && is_digit_at(i + 1),
+                _ => has_digits && is_group,
+            };
 
+            if accepted {
+                numeric_part.push(c);
+            } else if has_digits {
+                break;
+            }
+        }
+
+        Self::normalize_separators(&numeric_part, locale)
+    }
+
+    /// Drops grouping separators and turns the decimal mark into `.`; a lone separator followed by
+    /// exactly three digits is read as grouping unless it is the locale's decimal mark
+    fn normalize_separators(numeric_part: &str, locale: Locale) -> String {
+        let numeric_part: String = numeric_part
+            .chars()
+            .filter(|&c| !locale.is_group_separator(c))
+            .collect();
+        let dots = numeric_part.matches('.').count();
+        let commas = numeric_part.matches(',').count();
+
+        let decimal_mark = match (dots, commas) {
+            (0, 0) => None,
+            (_, 0) | (0, _) => {
+                let mark = if dots > 0 { '.' } else { ',' };
+                let digits_after = numeric_part.len() - numeric_part.rfind(mark).unwrap_or(0) - 1;
+
+                if dots + commas > 1 {
+                    if mark == locale.decimal_separator() {
+                        // several decimal marks, not a number
+                        return numeric_part;
+                    }
+                    None
+                } else if mark == locale.decimal_separator() || digits_after != 3 {
+                    Some(mark)
+                } else {
+                    None
+                }
+            }
+            _ => numeric_part.chars().rev().find(|&c| c == '.' || c == ','),
+        };
+
+        numeric_part
+            .chars()
+            .filter_map(|c| match c {
+                '.' | ',' if Some(c) == decimal_mark => Some('.'),
+                '.' | ',' => None,
+                _ => Some(c),
+            })
+            .collect()
+    }
+
+    pub fn parse_integer(response: &str, locale: Locale) -> Option<i64> {
+        Self::extract_numeric_part(response, locale)
+            .parse::<f64>()
+            .ok()
+            .map(|n| n.round() as i64)
+    }
+
+    pub fn parse_float(response: &str, locale: Locale) -> Option<f64> {
+        Self::extract_numeric_part(response, locale).parse::<f64>().ok()
+    }
+
+    pub fn parse_json(response: &str) -> Result<Value, GenerationError> {
         let mut cleaned = response
             .trim()
             .replace("```json", "")
@@ -506,17 +1724,6 @@ impl GenerationService {
         Ok(json5::from_str(&cleaned)?)
     }
 
-    fn generate_bool(
-        &self,
-        model: &LlamaModel,
-        ctx: &mut llama_cpp_2::context::LlamaContext,
-        prompt: &str,
-        config: &InferenceConfig,
-    ) -> Result<bool, GenerationError> {
-        let response = self.inference(model, ctx, prompt, config, None::<fn(&str)>)?;
-        Ok(response.parse::<bool>().unwrap_or(false))
-    }
-
     pub fn get_or_load_model(
         &self,
         model_path: &PathBuf,
@@ -573,6 +1780,7 @@ impl GenerationService {
 
         let mut repetition_count = 0;
         let mut last_tokens: VecDeque<LlamaToken> = VecDeque::with_capacity(10);
+        let mut rng = StdRng::seed_from_u64(config.seed.unwrap_or_else(rand::random).into());
 
         loop {
             let logits_iter = ctx.candidates_ith(batch.n_tokens() - 1);
@@ -614,7 +1822,7 @@ impl GenerationService {
                 break;
             }
 
-            let next_token = if config.temperature > 0.0 && candidates.len() > 1 {
+            let mut next_token = if config.temperature > 0.0 && candidates.len() > 1 {
                 let scaled_logits: Vec<f32> = candidates
                     .iter()
                     .map(|c| c.logit() / config.temperature)
@@ -625,7 +1833,6 @@ impl GenerationService {
                 let sum_exp: f32 = exp_logits.iter().sum();
                 let probabilities: Vec<f32> = exp_logits.iter().map(|&e| e / sum_exp).collect();
 
-                let mut rng = rand::thread_rng();
                 let random_value: f32 = rng.gen();
                 let mut cumulative = 0.0;
                 let mut selected_idx = 0;
@@ -644,7 +1851,14 @@ impl GenerationService {
             };
 
             if next_token == model.token_eos() {
-                break;
+                if config.stop.can_stop(&response, tokens_generated) {
+                    break;
+                }
+
+                match candidates.iter().map(|c| c.id()).find(|&id| id != model.token_eos()) {
+                    Some(token) => next_token = token,
+                    None => break,
+                }
             }
 
             if last_tokens.len() >= 10 && last_tokens.iter().all(|t| *t == next_token) {
@@ -669,26 +1883,8 @@ impl GenerationService {
             let token_str = model.token_to_str(next_token, Special::Plaintext)?;
             response.push_str(&token_str);
 
-            if tokens_generated > 3 {
-                let trimmed = response.trim();
-
-                if trimmed.contains("```") {
-                    break;
-                }
-
-                if trimmed.contains("\n") {
-                    break;
-                }
-
-                if tokens_generated > 10 {
-                    if trimmed.ends_with(".") || trimmed.ends_with("!") || trimmed.ends_with("?") {
-                        break;
-                    }
-                }
-
-                if response.len() > 200 {
-                    break;
-                }
+            if config.stop.should_stop(&response, tokens_generated) {
+                break;
             }
 
             if let Some(ref callback) = token_callback {
@@ -702,16 +1898,172 @@ impl GenerationService {
             ctx.decode(&mut batch)?;
         }
 
-        Ok(response)
+        Ok(config.stop.truncate(&response))
     }
 
     pub fn prepare_prompt(
         &self,
         columns: &[Column],
         for_column: &Column,
-        row_data: &Vec<RowData>,
+        row_data: &[RowData],
+        context: &PromptContext,
+    ) -> Result<String, GenerationError> {
+        Self::build_cell_prompt(columns, for_column, row_data, context)
+    }
+
+    /// Chat prompt for a single cell, does not need a loaded model
+    pub fn build_cell_prompt(
+        columns: &[Column],
+        for_column: &Column,
+        row_data: &[RowData],
+        context: &PromptContext,
+    ) -> Result<String, GenerationError> {
+        let strings = context.locale.strings();
+        let processed_rules = Self::process_rules(columns, &for_column.rules, row_data, &[], context)
+            + &Self::range_hint(context.target.as_ref(), context.locale);
+        let format_str = Self::localized_format_description(for_column, strings);
+
+        let examples = if context.examples.is_empty() {
+            String::new()
+        } else {
+            let lines: Vec<String> = context
+                .examples
+                .iter()
+                .map(|example| format!("- {}", example.replace('\n', " ")))
+                .collect();
+            format!("\n{}\n{}\n", strings.examples_header, lines.join("\n"))
+        };
+
+        let exclusions = if context.exclusions.is_empty() {
+            String::new()
+        } else {
+            let lines: Vec<String> = context
+                .exclusions
+                .iter()
+                .map(|exclusion| format!("- {}", exclusion.replace('\n', " ")))
+                .collect();
+            format!("\n{}\n{}\n", strings.exclusions_header, lines.join("\n"))
+        };
+
+        let cell_template = context
+            .template
+            .cell_template
+            .as_deref()
+            .unwrap_or(strings.cell_template);
+        let context_block = Self::context_block(columns, row_data, context);
+
+        let user_prompt = if cell_template.contains("{context}") {
+            cell_template.to_string()
+        } else {
+            format!("{}{}", context_block.trim_start(), cell_template)
+        };
+
+        let user_prompt = user_prompt
+            .replace("{context}", &context_block)
+            .replace("{column_name}", &for_column.name)
+            .replace("{column_rule}", &processed_rules)
+            .replace("{format}", &format_str)
+            .replace("{dataset_description}", &context.dataset_description)
+            .replace("{row_context}", &Self::row_context(columns, row_data, strings))
+            .replace("{examples}", &examples)
+            .replace("{exclusions}", &exclusions)
+            .replace("{line_rule}", &Self::line_rule(for_column, strings));
+
+        let system_prompt = context
+            .template
+            .system_prompt
+            .as_deref()
+            .unwrap_or(strings.system_prompt);
+
+        Ok(render_chat_prompt(system_prompt, &user_prompt))
+    }
+
+    /// Chat prompt asking for the `group` columns as one JSON object, does not need a loaded model
+    pub fn build_row_prompt(
+        columns: &[Column],
+        group: &[Column],
+        row_data: &[RowData],
+        context: &PromptContext,
     ) -> Result<String, GenerationError> {
+        let strings = context.locale.strings();
+        let fields: Vec<String> = group
+            .iter()
+            .map(|column| {
+                format!(
+                    "- \"{}\" ({}): {}",
+                    column.name,
+                    Self::format_description(column),
+                    Self::process_rules(columns, &column.rules, row_data, group, context).replace('\n', " ")
+                )
+            })
+            .collect();
+
+        let user_prompt = strings
+            .row_template
+            .replace("{fields}", &fields.join("\n"))
+            .replace("{context}", &Self::context_block(columns, row_data, context));
+        // The custom prompt is appended, `parse_json` relies on the JSON-only instruction
+        let system_prompt = match context.template.system_prompt.as_deref() {
+            Some(custom) => format!("{}\n\n{}", strings.row_system_prompt, custom.trim()),
+            None => strings.row_system_prompt.to_string(),
+        };
+
+        Ok(render_chat_prompt(&system_prompt, &user_prompt))
+    }
+
+    /// Opt-in block with the dataset purpose and the row's generated values
+    fn context_block(columns: &[Column], row_data: &[RowData], context: &PromptContext) -> String {
+        if !context.include_context {
+            return String::new();
+        }
+
+        let strings = context.locale.strings();
+        let mut block = String::new();
 
+        let description = context.dataset_description.trim();
+        if !description.is_empty() {
+            block.push_str(&format!(
+                "\n{} {}\n",
+                strings.dataset_purpose,
+                description.replace('\n', " ")
+            ));
+        }
+
+        block.push_str(&Self::row_context(columns, row_data, strings));
+        block
+    }
+
+    /// Values already generated for the current row, in column order
+    fn row_context(columns: &[Column], row_data: &[RowData], strings: &LocaleStrings) -> String {
+        let lines: Vec<String> = columns
+            .iter()
+            .filter_map(|column| {
+                let column_id = column.id?.to_string();
+                let data = row_data.iter().find(|data| data.column_id == column_id)?;
+                Some(format!(
+                    "- {}: {}",
+                    column.name,
+                    data.value_or_empty().replace('\n', " ")
+                ))
+            })
+            .collect();
+
+        if lines.is_empty() {
+            return String::new();
+        }
+
+        format!("\n{}\n{}\n", strings.known_values_header, lines.join("\n"))
+    }
+
+    /// Expands random commands, `@column` and `@reference.field` references; columns in `pending` are
+    /// referred to by key
+    fn process_rules(
+        columns: &[Column],
+        rules: &str,
+        row_data: &[RowData],
+        pending: &[Column],
+        context: &PromptContext,
+    ) -> String {
         let id_to_name: HashMap<String, &str> = columns
             .iter()
             .filter_map(|col| col.id.map(|id| (id.to_string(), col.name.as_str())))
@@ -720,13 +2072,13 @@ impl GenerationService {
         let mut name_to_value: HashMap<&str, &str> = HashMap::with_capacity(row_data.len());
         for row in row_data {
             if let Some(&name) = id_to_name.get(&row.column_id) {
-                name_to_value.insert(name, row.value.as_str());
+                name_to_value.insert(name, row.value_or_empty());
             }
         }
 
         let random_range_regex = get_random_int_range_regex();
         let mut rng = rand::thread_rng();
-        let after_range_random = random_range_regex.replace_all(&for_column.rules, |caps: &regex::Captures| {
+        let after_range_random = random_range_regex.replace_all(rules, |caps: &regex::Captures| {
             let start: i64 = caps.get(1).unwrap().as_str().parse().unwrap_or(0);
             let end: i64 = caps.get(2).unwrap().as_str().parse().unwrap_or(0);
             let random_value = rng.gen_range(start..=end);
@@ -741,85 +2093,74 @@ impl GenerationService {
         });
 
         let column_ref_regex = get_column_ref_regex();
-        let processed_rules = column_ref_regex.replace_all(&after_single_random, |caps: &regex::Captures| {
-            caps.get(1)
-                .and_then(|m| name_to_value.get(m.as_str()))
-                .copied()
-                .unwrap_or("")
-        });
+        column_ref_regex
+            .replace_all(&after_single_random, |caps: &regex::Captures| {
+                let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+                let field = caps.get(2).map(|m| m.as_str());
 
-        let format_str = if for_column.column_type == "JSON" {
-            let details = for_column.column_type_details.as_deref().unwrap_or("");
-            format!("well formatted {} structure, structure details: {}", for_column.column_type, details)
-        } else {
-            for_column.column_type.clone()
-        };
+                if let (Some(field), Some(fields)) = (field, context.references.get(name)) {
+                    return fields.get(field).cloned().unwrap_or_default();
+                }
 
-        let prompt = CELL_PROMPT_TEMPLATE
-            .replace("{column_name}", &for_column.name)
-            .replace("{column_rule}", &processed_rules)
-            .replace("{format}", &format_str);
+                let value = if pending.iter().any(|column| column.name == name) {
+                    context.locale.strings().pending_value.replace("{name}", name)
+                } else {
+                    name_to_value.get(name).copied().unwrap_or("").to_string()
+                };
 
-        Ok(prompt)
+                match field {
+                    Some(field) => format!("{}.{}", value, field),
+                    None => value,
+                }
+            })
+            .into_owned()
     }
 
-    pub fn sort_columns_by_dependency(&self, columns: &[Column], pattern: &str) -> Result<Vec<Column>, String> {
-        if columns.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let regex = Regex::new(pattern).map_err(|e| format!("Failed to compile regex pattern '{}': {}", pattern, e))?;
-
-        let name_to_index: HashMap<&str, usize> = columns
-            .iter()
-            .enumerate()
-            .map(|(i, col)| (col.name.as_str(), i))
-            .collect();
-
-        let mut reverse_deps: HashMap<usize, Vec<usize>> = HashMap::new();
-        let mut in_degree = vec![0; columns.len()];
+    fn line_rule(column: &Column, strings: &LocaleStrings) -> String {
+        let stop = column.settings.stop.clone().unwrap_or_default();
 
-        for (i, column) in columns.iter().enumerate() {
-            for cap in regex.captures_iter(&column.rules) {
-                if let Some(dep_name) = cap.get(1) {
-                    let dep_name = dep_name.as_str();
+        let mut rule = if stop.multiline {
+            strings.multiline_rule.to_string()
+        } else {
+            strings.single_line_rule.to_string()
+        };
 
-                    if let Some(&dep_index) = name_to_index.get(dep_name) {
-                        if dep_index != i {
-                            reverse_deps.entry(dep_index).or_insert_with(Vec::new).push(i);
-                            in_degree[i] += 1;
-                        }
-                    }
-                }
-            }
+        if let Some(max_chars) = stop.max_chars {
+            rule.push_str(&strings.max_chars_rule.replace("{max_chars}", &max_chars.to_string()));
         }
 
-        let mut queue: VecDeque<usize> = in_degree
-            .iter()
-            .enumerate()
-            .filter_map(|(i, &degree)| if degree == 0 { Some(i) } else { None })
-            .collect();
-
-        let mut sorted_indices = Vec::with_capacity(columns.len());
+        rule
+    }
 
-        while let Some(current_index) = queue.pop_front() {
-            sorted_indices.push(current_index);
+    /// Cell prompts spell out the locale's number notation, JSON rows keep plain JSON numbers
+    fn localized_format_description(column: &Column, strings: &LocaleStrings) -> String {
+        let hint = match column.column_type.as_str() {
+            "INT" => strings.integer_hint,
+            "FLOAT" => strings.float_hint,
+            _ => "",
+        };
 
-            if let Some(dependents) = reverse_deps.get(&current_index) {
-                for &dependent_idx in dependents {
-                    in_degree[dependent_idx] -= 1;
-                    if in_degree[dependent_idx] == 0 {
-                        queue.push_back(dependent_idx);
-                    }
-                }
-            }
+        if hint.is_empty() {
+            Self::format_description(column)
+        } else {
+            format!("{} ({})", Self::format_description(column), hint)
         }
+    }
 
-        if sorted_indices.len() != columns.len() {
-            return Err("Circular dependency detected in column rules".to_string());
+    fn format_description(column: &Column) -> String {
+        if column.column_type == "JSON" {
+            let details = column.column_type_details.as_deref().unwrap_or("");
+            format!(
+                "well formatted {} structure, structure details: {}",
+                column.column_type, details
+            )
+        } else {
+            column.column_type.clone()
         }
+    }
 
-        Ok(sorted_indices.into_iter().map(|i| columns[i].clone()).collect())
+    pub fn sort_columns_by_dependency(&self, columns: &[Column], pattern: &str) -> Result<Vec<Column>, String> {
+        sort_columns_by_dependency(columns, pattern)
     }
 
     fn clean_text_artifacts(text: &str) -> String {
@@ -850,12 +2191,14 @@ impl GenerationService {
 
         let trimmed = cleaned.trim();
 
-        if trimmed.len() > 1 {
-            if (trimmed.starts_with('"') && trimmed.ends_with('"'))
-                || (trimmed.starts_with('\'') && trimmed.ends_with('\''))
-            {
-                return trimmed[1..trimmed.len() - 1].trim().to_string();
-            } else if trimmed.starts_with('"') && !trimmed.ends_with('"') {
+        if trimmed.chars().count() > 1 {
+            for &(open, close) in QUOTE_PAIRS {
+                if let Some(inner) = trimmed.strip_prefix(open).and_then(|rest| rest.strip_suffix(close)) {
+                    return inner.trim().to_string();
+                }
+            }
+
+            if trimmed.starts_with('"') && !trimmed.ends_with('"') {
                 return trimmed[1..].trim().to_string();
             } else if !trimmed.starts_with('"') && trimmed.ends_with('"') {
                 return trimmed[..trimmed.len() - 1].trim().to_string();
@@ -877,6 +2220,7 @@ mod tests {
 
     mod generation_service {
         use super::*;
+        use crate::services::dataset::{ConditionOperator, ConditionalRule, DatasetSettings};
         use std::sync::Once;
 
         static INIT: Once = Once::new();
@@ -941,6 +2285,7 @@ mod tests {
                         column_type_details: None,
                         rules: "Generate a first name".to_string(),
                         position: 1,
+                        settings: ColumnSettings::default(),
                     },
                     Column {
                         id: Some(2),
@@ -951,6 +2296,7 @@ mod tests {
                         column_type_details: None,
                         rules: "Generate a last name".to_string(),
                         position: 2,
+                        settings: ColumnSettings::default(),
                     },
                     Column {
                         id: Some(3),
@@ -961,6 +2307,7 @@ mod tests {
                         column_type_details: None,
                         rules: "Generate full name using @first_name and @last_name".to_string(),
                         position: 3,
+                        settings: ColumnSettings::default(),
                     },
                 ]
             }
@@ -996,6 +2343,7 @@ mod tests {
                             column_type_details: None,
                             rules: "Depends on @column2".to_string(),
                             position: 1,
+                            settings: ColumnSettings::default(),
                         },
                         Column {
                             id: Some(2),
@@ -1006,6 +2354,7 @@ mod tests {
                             column_type_details: None,
                             rules: "Depends on @column1".to_string(),
                             position: 2,
+                            settings: ColumnSettings::default(),
                         },
                     ];
 
@@ -1016,6 +2365,24 @@ mod tests {
                 }
             }
 
+            #[test]
+            fn test_sort_columns_by_dependency_follows_conditions() {
+                let mut columns = create_test_columns();
+                columns[0].settings.conditions = vec![ConditionalRule {
+                    column: "full_name".to_string(),
+                    operator: ConditionOperator::IsEmpty,
+                    value: String::new(),
+                    rules: "A nickname".to_string(),
+                    null_probability: None,
+                }];
+                columns[2].rules = "Generate full name".to_string();
+
+                let sorted = sort_columns_by_dependency(&columns, r"@(\w+)").expect("Failed to sort columns");
+                let first_name = sorted.iter().position(|column| column.name == "first_name").unwrap();
+                let full_name = sorted.iter().position(|column| column.name == "full_name").unwrap();
+                assert!(full_name < first_name);
+            }
+
             #[test]
             fn test_sort_columns_by_dependency_invalid_regex() {
                 setup_test_environment();
@@ -1029,6 +2396,122 @@ mod tests {
             }
         }
 
+        mod conditional_rules {
+            use super::*;
+
+            fn column(id: i64, name: &str, settings: ColumnSettings) -> Column {
+                Column {
+                    id: Some(id),
+                    table_name: "test_table".to_string(),
+                    dataset_id: 1,
+                    name: name.to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: format!("A {}", name),
+                    position: id,
+                    settings,
+                }
+            }
+
+            fn condition(operator: ConditionOperator, value: &str, rules: &str) -> ConditionalRule {
+                ConditionalRule {
+                    column: "@country".to_string(),
+                    operator,
+                    value: value.to_string(),
+                    rules: rules.to_string(),
+                    null_probability: None,
+                }
+            }
+
+            #[test]
+            fn test_resolve_conditions_picks_first_match() {
+                let postal_code = column(
+                    2,
+                    "postal_code",
+                    ColumnSettings {
+                        null_probability: Some(0.2),
+                        conditions: vec![
+                            condition(ConditionOperator::Equals, "us", "A 5 digit ZIP code"),
+                            ConditionalRule {
+                                null_probability: Some(1.0),
+                                ..condition(ConditionOperator::IsEmpty, "", "")
+                            },
+                        ],
+                        ..Default::default()
+                    },
+                );
+                let columns = vec![column(1, "country", ColumnSettings::default()), postal_code.clone()];
+                let row_data = |country: &str| {
+                    vec![RowData {
+                        column_id: "1".to_string(),
+                        value: Some(country.to_string()),
+                    }]
+                };
+
+                let (resolved, null_probability) =
+                    GenerationService::resolve_conditions(&columns, &postal_code, &row_data(" US "));
+                assert_eq!(resolved.rules, "A 5 digit ZIP code");
+                assert_eq!(null_probability, Some(0.2));
+
+                let (resolved, null_probability) =
+                    GenerationService::resolve_conditions(&columns, &postal_code, &row_data("France"));
+                assert_eq!(resolved.rules, "A postal_code");
+                assert_eq!(null_probability, Some(0.2));
+
+                let (resolved, null_probability) = GenerationService::resolve_conditions(&columns, &postal_code, &[]);
+                assert_eq!(resolved.rules, "A postal_code", "Empty branch keeps the column rules");
+                assert_eq!(null_probability, Some(1.0));
+            }
+
+            #[test]
+            fn test_condition_levels_wait_for_the_tested_column() {
+                let postal_code = column(
+                    2,
+                    "postal_code",
+                    ColumnSettings {
+                        conditions: vec![condition(ConditionOperator::Equals, "US", "A 5 digit ZIP code")],
+                        ..Default::default()
+                    },
+                );
+                let group = vec![
+                    column(1, "country", ColumnSettings::default()),
+                    postal_code,
+                    column(3, "city", ColumnSettings::default()),
+                ];
+                let names = |levels: Vec<Vec<Column>>| {
+                    levels
+                        .into_iter()
+                        .map(|level| level.into_iter().map(|column| column.name).collect::<Vec<_>>())
+                        .collect::<Vec<_>>()
+                };
+
+                assert_eq!(
+                    names(GenerationService::condition_levels(&group, &[])),
+                    vec![vec!["country", "city"], vec!["postal_code"]]
+                );
+
+                let row_data = vec![RowData {
+                    column_id: "1".to_string(),
+                    value: Some("US".to_string()),
+                }];
+                assert_eq!(
+                    names(GenerationService::condition_levels(&group, &row_data)),
+                    vec![vec!["postal_code", "city"]],
+                    "Columns with a value are left out and no longer hold back their dependents"
+                );
+            }
+
+            #[test]
+            fn test_condition_operators() {
+                assert!(condition(ConditionOperator::NotEquals, "US", "").matches(Some("FR")));
+                assert!(!condition(ConditionOperator::NotEquals, "US", "").matches(Some("us")));
+                assert!(condition(ConditionOperator::Contains, "king", "").matches(Some("United Kingdom")));
+                assert!(condition(ConditionOperator::IsEmpty, "", "").matches(Some("  ")));
+                assert!(condition(ConditionOperator::IsNotEmpty, "", "").matches(Some("US")));
+                assert!(!condition(ConditionOperator::IsNotEmpty, "", "").matches(None));
+            }
+        }
+
         mod prompt_preparation {
             use super::*;
 
@@ -1043,6 +2526,7 @@ mod tests {
                         column_type_details: None,
                         rules: "Generate a first name".to_string(),
                         position: 1,
+                        settings: ColumnSettings::default(),
                     },
                     Column {
                         id: Some(2),
@@ -1053,6 +2537,7 @@ mod tests {
                         column_type_details: None,
                         rules: "Generate a last name using @first_name".to_string(),
                         position: 2,
+                        settings: ColumnSettings::default(),
                     },
                 ]
             }
@@ -1064,11 +2549,11 @@ mod tests {
                     let columns = create_test_columns();
                     let row_data = vec![RowData {
                         column_id: "1".to_string(),
-                        value: "John".to_string(),
+                        value: Some("John".to_string()),
                     }];
 
                     let prompt = generation_service
-                        .prepare_prompt(&columns, &columns[1], &row_data)
+                        .prepare_prompt(&columns, &columns[1], &row_data, &PromptContext::default())
                         .expect("Failed to prepare prompt");
 
                     assert!(prompt.contains("last_name"));
@@ -1092,11 +2577,12 @@ mod tests {
                         column_type_details: Some(r#"{"name": "string", "age": "number"}"#.to_string()),
                         rules: "Generate user data".to_string(),
                         position: 1,
+                        settings: ColumnSettings::default(),
                     }];
                     let row_data = vec![];
 
                     let prompt = generation_service
-                        .prepare_prompt(&columns, &columns[0], &row_data)
+                        .prepare_prompt(&columns, &columns[0], &row_data, &PromptContext::default())
                         .expect("Failed to prepare prompt");
 
                     assert!(prompt.contains("JSON"));
@@ -1107,6 +2593,212 @@ mod tests {
                 }
             }
 
+            #[test]
+            fn test_prepare_prompt_with_examples() {
+                let columns = create_test_columns();
+                let context = PromptContext {
+                    examples: vec!["Smith".to_string(), "Dupont".to_string()],
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_cell_prompt(&columns, &columns[0], &[], &context)
+                    .expect("Failed to prepare prompt");
+
+                assert!(prompt.contains("Examples of existing values"));
+                assert!(prompt.contains("- Smith"));
+                assert!(prompt.contains("- Dupont"));
+            }
+
+            #[test]
+            fn test_prepare_prompt_with_exclusions() {
+                let columns = create_test_columns();
+                let context = PromptContext {
+                    exclusions: vec!["John".to_string()],
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_cell_prompt(&columns, &columns[0], &[], &context)
+                    .expect("Failed to prepare prompt");
+
+                assert!(prompt.contains("do NOT reuse them"));
+                assert!(prompt.contains("- John"));
+                assert!(!prompt.contains("{exclusions}"));
+            }
+
+            #[test]
+            fn test_prepare_prompt_without_examples() {
+                let columns = create_test_columns();
+
+                let prompt =
+                    GenerationService::build_cell_prompt(&columns, &columns[0], &[], &PromptContext::default())
+                        .expect("Failed to prepare prompt");
+
+                assert!(!prompt.contains("Examples of existing values"));
+                assert!(!prompt.contains("{examples}"));
+            }
+
+            #[test]
+            fn test_prepare_row_prompt_lists_group_fields() {
+                let columns = create_test_columns();
+
+                let prompt = GenerationService::build_row_prompt(&columns, &columns, &[], &PromptContext::default())
+                    .expect("Failed to prepare row prompt");
+
+                assert!(prompt.contains("- \"first_name\" (TEXT): Generate a first name"));
+                assert!(prompt.contains("the \"first_name\" value"));
+                assert!(!prompt.contains("{fields}"));
+            }
+
+            #[test]
+            fn test_prepare_prompt_with_custom_template() {
+                let columns = create_test_columns();
+                let row_data = vec![RowData {
+                    column_id: "1".to_string(),
+                    value: Some("John".to_string()),
+                }];
+                let context = PromptContext {
+                    template: PromptTemplate {
+                        system_prompt: Some("Réponds en français.".to_string()),
+                        cell_template: Some(
+                            "{dataset_description}{row_context}{column_name}: {column_rule} ({format})".to_string(),
+                        ),
+                    },
+                    dataset_description: "Patients of a clinic".to_string(),
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_cell_prompt(&columns, &columns[1], &row_data, &context)
+                    .expect("Failed to prepare prompt");
+
+                assert!(prompt.contains("Réponds en français."));
+                assert!(prompt.contains("Patients of a clinic"));
+                assert!(prompt.contains("- first_name: John"));
+                assert!(prompt.contains("last_name: Generate a last name using John (TEXT)"));
+                assert!(!prompt.contains("CRITICAL"));
+            }
+
+            #[test]
+            fn test_prepare_row_prompt_keeps_json_instruction_with_custom_system_prompt() {
+                let columns = create_test_columns();
+                let context = PromptContext {
+                    template: PromptTemplate {
+                        system_prompt: Some("Réponds en français.".to_string()),
+                        cell_template: None,
+                    },
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_row_prompt(&columns, &columns, &[], &context)
+                    .expect("Failed to prepare row prompt");
+
+                assert!(prompt.contains(Locale::En.strings().row_system_prompt));
+                assert!(prompt.contains("Réponds en français."));
+            }
+
+            #[test]
+            fn test_prepare_prompt_with_context_block() {
+                let columns = create_test_columns();
+                let row_data = vec![RowData {
+                    column_id: "1".to_string(),
+                    value: Some("John".to_string()),
+                }];
+                let mut context = PromptContext {
+                    dataset_description: "Customers of a bakery".to_string(),
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_cell_prompt(&columns, &columns[1], &row_data, &context)
+                    .expect("Failed to prepare prompt");
+                assert!(!prompt.contains("Dataset purpose"), "Context should be opt-in");
+
+                context.include_context = true;
+                let prompt = GenerationService::build_cell_prompt(&columns, &columns[1], &row_data, &context)
+                    .expect("Failed to prepare prompt");
+
+                assert!(prompt.contains("Dataset purpose: Customers of a bakery"));
+                assert!(prompt.contains("Known values of this record:\n- first_name: John"));
+                assert!(!prompt.contains("{context}"));
+            }
+
+            #[test]
+            fn test_process_rules_expands_reference_fields() {
+                let mut columns = create_test_columns();
+                columns[0].name = "customer".to_string();
+                columns[0].column_type = "REFERENCE".to_string();
+
+                let row_data = vec![RowData {
+                    column_id: "1".to_string(),
+                    value: Some("7".to_string()),
+                }];
+                let mut context = PromptContext::default();
+                context.references.insert(
+                    "customer".to_string(),
+                    HashMap::from([
+                        ("name".to_string(), "Alice".to_string()),
+                        ("city".to_string(), "Lyon".to_string()),
+                    ]),
+                );
+
+                let rules = "Order #@customer for @customer.name from @customer.city, @customer.phone, @last_name.x";
+                let processed = GenerationService::process_rules(&columns, rules, &row_data, &[], &context);
+
+                assert_eq!(processed, "Order #7 for Alice from Lyon, , .x");
+            }
+
+            #[test]
+            fn test_prepare_prompt_with_locale() {
+                let mut columns = create_test_columns();
+                columns[1].column_type = "FLOAT".to_string();
+                let row_data = vec![RowData {
+                    column_id: "1".to_string(),
+                    value: Some("Amélie".to_string()),
+                }];
+                let context = PromptContext {
+                    dataset_description: "Clients d'une boulangerie".to_string(),
+                    include_context: true,
+                    locale: Locale::Fr,
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_cell_prompt(&columns, &columns[1], &row_data, &context)
+                    .expect("Failed to prepare prompt");
+
+                assert!(prompt.contains("Génère une valeur FLOAT (nombre décimal, ex. 3,14)"));
+                assert!(prompt.contains(Locale::Fr.strings().system_prompt));
+                assert!(prompt.contains("Objectif du jeu de données : Clients d'une boulangerie"));
+                assert!(prompt.contains("- first_name: Amélie"));
+                assert!(prompt.contains("Réponds sur UNE SEULE LIGNE"));
+                assert!(!prompt.contains("CRITICAL"));
+
+                let prompt = GenerationService::build_row_prompt(&columns, &columns[1..], &[], &context)
+                    .expect("Failed to prepare row prompt");
+                assert!(
+                    prompt.contains("- \"last_name\" (FLOAT): "),
+                    "Row prompts keep JSON numbers"
+                );
+                assert!(prompt.contains(Locale::Fr.strings().row_system_prompt));
+            }
+
+            #[test]
+            fn test_prepare_row_prompt_with_context_block() {
+                let columns = create_test_columns();
+                let row_data = vec![RowData {
+                    column_id: "1".to_string(),
+                    value: Some("John".to_string()),
+                }];
+                let context = PromptContext {
+                    dataset_description: "Customers of a bakery".to_string(),
+                    include_context: true,
+                    ..Default::default()
+                };
+
+                let prompt = GenerationService::build_row_prompt(&columns, &columns[1..], &row_data, &context)
+                    .expect("Failed to prepare row prompt");
+
+                assert!(prompt.contains("Dataset purpose: Customers of a bakery"));
+                assert!(prompt.contains("- first_name: John"));
+            }
+
             #[test]
             fn test_random_int_commands_produce_different_values() {
                 setup_test_environment();
@@ -1120,13 +2812,14 @@ mod tests {
                         column_type_details: None,
                         rules: "Patient age: @RANDOM_INT_18_85".to_string(),
                         position: 1,
+                        settings: ColumnSettings::default(),
                     }];
                     let row_data = vec![];
 
                     let mut generated_rules = Vec::new();
                     for _ in 0..5 {
                         let prompt = generation_service
-                            .prepare_prompt(&columns, &columns[0], &row_data)
+                            .prepare_prompt(&columns, &columns[0], &row_data, &PromptContext::default())
                             .expect("Failed to prepare prompt");
 
                         if let Some(start) = prompt.find("Rule: ") {
@@ -1146,6 +2839,282 @@ mod tests {
 
         }
 
+        mod value_parsing {
+            use super::*;
+
+            #[test]
+            fn test_parse_integer_rounds_first_number() {
+                assert_eq!(
+                    GenerationService::parse_integer("Age: 42.6 years", Locale::En),
+                    Some(43)
+                );
+                assert_eq!(GenerationService::parse_integer("-12", Locale::En), Some(-12));
+            }
+
+            #[test]
+            fn test_parse_integer_without_number() {
+                assert_eq!(GenerationService::parse_integer("forty two", Locale::En), None);
+            }
+
+            #[test]
+            fn test_parse_float() {
+                assert_eq!(GenerationService::parse_float("2.75 euros", Locale::En), Some(2.75));
+                assert_eq!(GenerationService::parse_float("none", Locale::En), None);
+            }
+
+            #[test]
+            fn test_parse_localized_numbers() {
+                assert_eq!(GenerationService::parse_float("2,75", Locale::Fr), Some(2.75));
+                assert_eq!(
+                    GenerationService::parse_float("1\u{202f}234,5 €", Locale::Fr),
+                    Some(1234.5)
+                );
+                assert_eq!(GenerationService::parse_float("1 234,5", Locale::Fr), Some(1234.5));
+                assert_eq!(GenerationService::parse_integer("1.500 Stück", Locale::De), Some(1500));
+                assert_eq!(
+                    GenerationService::parse_float("1.234.567,89", Locale::De),
+                    Some(1234567.89)
+                );
+                assert_eq!(GenerationService::parse_float("2.75", Locale::De), Some(2.75));
+                assert_eq!(GenerationService::parse_integer("１，２３４円", Locale::Ja), Some(1234));
+                assert_eq!(GenerationService::parse_float("－１２．５度", Locale::Ja), Some(-12.5));
+                assert_eq!(
+                    GenerationService::parse_integer("年齢は４２歳です", Locale::Ja),
+                    Some(42)
+                );
+            }
+
+            #[test]
+            fn test_parse_numbers_with_grouping() {
+                assert_eq!(
+                    GenerationService::parse_integer("1,234,567 people", Locale::En),
+                    Some(1234567)
+                );
+                assert_eq!(GenerationService::parse_float("1,234.5", Locale::En), Some(1234.5));
+                assert_eq!(GenerationService::parse_float("2,5", Locale::En), Some(2.5));
+                assert_eq!(GenerationService::parse_integer("12 34", Locale::En), Some(12));
+                assert_eq!(GenerationService::parse_float("1.2.3", Locale::En), None);
+            }
+
+            #[test]
+            fn test_coerce_localized_json_value() {
+                assert_eq!(
+                    GenerationService::coerce_json_value("FLOAT", &serde_json::json!("2,75"), Locale::De),
+                    Some("2.75".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("BOOL", &serde_json::json!("oui"), Locale::Fr),
+                    Some("true".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("BOOL", &serde_json::json!("いいえ"), Locale::Ja),
+                    Some("false".to_string())
+                );
+            }
+
+            #[test]
+            fn test_parse_json_repairs_unbalanced_object() {
+                let value =
+                    GenerationService::parse_json("```json\n{\"city\": \"Paris\"").expect("Failed to parse JSON");
+                assert_eq!(value["city"], "Paris");
+            }
+
+            #[test]
+            fn test_is_parsable_as() {
+                assert!(GenerationService::is_parsable_as("INT", "12", Locale::En));
+                assert!(!GenerationService::is_parsable_as("INT", "twelve", Locale::En));
+                assert!(GenerationService::is_parsable_as("JSON", "{\"a\": 1}", Locale::En));
+                assert!(GenerationService::is_parsable_as("TEXT", "Paris", Locale::En));
+                assert!(!GenerationService::is_parsable_as("TEXT", "```", Locale::En));
+            }
+
+            #[test]
+            fn test_is_parsable_as_bool_matches_generation() {
+                // the benchmark counts what generation would actually store
+                assert!(GenerationService::is_parsable_as("BOOL", "True.", Locale::En));
+                assert!(GenerationService::is_parsable_as("BOOL", "oui", Locale::Fr));
+                assert!(!GenerationService::is_parsable_as("BOOL", "maybe", Locale::En));
+            }
+        }
+
+        mod stop_criteria {
+            use super::*;
+
+            #[test]
+            fn test_default_stops_on_newline_and_sentence_end() {
+                let stop = StopCriteria::default();
+
+                assert!(stop.should_stop("Paris\nLyon", 3));
+                assert!(!stop.should_stop("\nParis", 3));
+                assert!(!stop.should_stop("A short sentence.", 5));
+                assert!(stop.should_stop("A short sentence.", 11));
+                assert!(stop.should_stop(&"a".repeat(201), 50));
+            }
+
+            #[test]
+            fn test_multiline_keeps_paragraphs() {
+                let stop = StopCriteria::from_settings(&StopSettings {
+                    multiline: true,
+                    ..Default::default()
+                });
+
+                assert!(!stop.should_stop("First sentence.\nSecond one.", 40));
+                assert!(!stop.should_stop(&"a".repeat(500), 200));
+            }
+
+            #[test]
+            fn test_stop_sequences_and_truncate() {
+                let stop = StopCriteria::from_settings(&StopSettings {
+                    stop_sequences: vec!["END".to_string()],
+                    ..Default::default()
+                });
+
+                assert!(
+                    !stop.should_stop("END of", 2),
+                    "A leading stop sequence should be ignored"
+                );
+                assert!(stop.should_stop("value END", 2));
+                assert_eq!(stop.truncate("  value END trailing"), "value ");
+                assert_eq!(stop.truncate("value"), "value");
+            }
+
+            #[test]
+            fn test_min_length_delays_stop() {
+                let stop = StopCriteria::from_settings(&StopSettings {
+                    min_tokens: Some(20),
+                    min_chars: Some(10),
+                    ..Default::default()
+                });
+
+                assert!(!stop.can_stop("short", 30));
+                assert!(!stop.can_stop("long enough text", 5));
+                assert!(!stop.should_stop("Done.\nMore", 12));
+                assert!(stop.should_stop("long enough text.\nMore", 25));
+            }
+
+            #[test]
+            fn test_max_chars_override() {
+                let stop = StopCriteria::from_settings(&StopSettings {
+                    max_chars: Some(5),
+                    ..Default::default()
+                });
+
+                assert!(!stop.should_stop("abcd", 1));
+                assert!(stop.should_stop("abcde", 1));
+            }
+
+            #[test]
+            fn test_truncate_clips_to_max_chars() {
+                let stop = StopCriteria::from_settings(&StopSettings {
+                    max_chars: Some(5),
+                    stop_sequences: vec!["END".to_string()],
+                    ..Default::default()
+                });
+
+                assert_eq!(stop.truncate("abcdefgh"), "abcde");
+                assert_eq!(stop.truncate("  héllo wörld"), "héllo");
+                assert_eq!(stop.truncate("日本語のテキスト"), "日本語のテ");
+                assert_eq!(stop.truncate("abEND cdef"), "ab");
+                assert_eq!(stop.truncate("  abc"), "abc");
+            }
+
+            #[test]
+            fn test_config_for_column() {
+                let mut column = Column {
+                    id: Some(1),
+                    table_name: "test_table".to_string(),
+                    dataset_id: 1,
+                    name: "bio".to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: "A biography".to_string(),
+                    position: 1,
+                    settings: ColumnSettings::default(),
+                };
+
+                let config = InferenceConfig::default();
+                assert_eq!(config.for_column(&column).stop, StopCriteria::default());
+
+                column.settings.stop = Some(StopSettings {
+                    max_tokens: Some(600),
+                    multiline: true,
+                    ..Default::default()
+                });
+
+                let column_config = config.for_column(&column);
+                assert_eq!(column_config.max_tokens, 600);
+                assert!(column_config.stop.multiline);
+                assert_eq!(column_config.stop.sentence_end_after, None);
+            }
+        }
+
+        mod json_rows {
+            use super::*;
+
+            #[test]
+            fn test_is_json_object_complete() {
+                assert!(!GenerationService::is_json_object_complete("{\"name\": \"Jo"));
+                assert!(!GenerationService::is_json_object_complete("{\"a\": {\"b\": 1}"));
+                assert!(GenerationService::is_json_object_complete("{\"a\": {\"b\": 1}}"));
+                assert!(GenerationService::is_json_object_complete("Sure! {\"a\": 1} trailing"));
+            }
+
+            #[test]
+            fn test_is_json_object_complete_ignores_braces_in_strings() {
+                assert!(!GenerationService::is_json_object_complete("{\"a\": \"}\""));
+                assert!(!GenerationService::is_json_object_complete("{\"a\": \"\\\"}\""));
+                assert!(GenerationService::is_json_object_complete("{\"a\": \"}\"}"));
+            }
+
+            #[test]
+            fn test_coerce_json_value() {
+                assert_eq!(
+                    GenerationService::coerce_json_value("TEXT", &serde_json::json!("Paris"), Locale::En),
+                    Some("Paris".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("INT", &serde_json::json!(41.7), Locale::En),
+                    Some("42".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("INT", &serde_json::json!("12 years"), Locale::En),
+                    Some("12".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("FLOAT", &serde_json::json!(2.5), Locale::En),
+                    Some("2.5".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("BOOL", &serde_json::json!("True"), Locale::En),
+                    Some("true".to_string())
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("JSON", &serde_json::json!({"a": 1}), Locale::En),
+                    Some("{\"a\":1}".to_string())
+                );
+            }
+
+            #[test]
+            fn test_coerce_json_value_rejects_mismatched_types() {
+                assert_eq!(
+                    GenerationService::coerce_json_value("INT", &serde_json::json!("none"), Locale::En),
+                    None
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("BOOL", &serde_json::json!(1), Locale::En),
+                    None
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("TEXT", &serde_json::Value::Null, Locale::En),
+                    None
+                );
+                assert_eq!(
+                    GenerationService::coerce_json_value("JSON", &serde_json::json!(true), Locale::En),
+                    None
+                );
+            }
+        }
+
         mod text_cleaning {
             use super::*;
 
@@ -1236,6 +3205,331 @@ mod tests {
                 let result = GenerationService::clean_text_artifacts(input);
                 assert_eq!(result, expected);
             }
+
+            #[test]
+            fn test_clean_text_artifacts_non_ascii_quotes() {
+                let cases = [
+                    ("«\u{a0}Bonjour à tous\u{a0}»", "Bonjour à tous"),
+                    ("„Grüße aus München“", "Grüße aus München"),
+                    ("»Straße«", "Straße"),
+                    ("“Smart quotes”", "Smart quotes"),
+                    ("「東京タワー」", "東京タワー"),
+                    ("『吾輩は猫である』```", "吾輩は猫である"),
+                ];
+
+                for (input, expected) in cases {
+                    assert_eq!(GenerationService::clean_text_artifacts(input), expected, "{}", input);
+                }
+            }
+
+            #[test]
+            fn test_clean_text_artifacts_preserves_non_ascii_text() {
+                let input = "Café « Le Zèbre », 東京「渋谷」";
+                assert_eq!(GenerationService::clean_text_artifacts(input), input);
+                assert_eq!(GenerationService::clean_text_artifacts("\"Ünïcödé\""), "Ünïcödé");
+                assert_eq!(GenerationService::clean_text_artifacts("日本語\""), "日本語");
+            }
+        }
+
+        mod distribution_targets {
+            use super::*;
+            use crate::services::dataset::HistogramBucket;
+            use rand::rngs::StdRng;
+            use rand::SeedableRng;
+
+            #[test]
+            fn test_next_target_fills_bucket_deficit() {
+                let distribution = DistributionSettings {
+                    buckets: vec![
+                        HistogramBucket {
+                            min: 0.0,
+                            max: 9.0,
+                            share: 1.0,
+                        },
+                        HistogramBucket {
+                            min: 10.0,
+                            max: 19.0,
+                            share: 1.0,
+                        },
+                    ],
+                    ..Default::default()
+                };
+                let mut rng = StdRng::seed_from_u64(7);
+
+                let target = GenerationService::next_target(&distribution, &[1.0, 2.0, 15.0], &mut rng);
+                assert_eq!(target, Some(TargetRange { min: 10.0, max: 19.0 }));
+
+                let target = GenerationService::next_target(&distribution, &[15.0, 16.0], &mut rng);
+                assert_eq!(target, Some(TargetRange { min: 0.0, max: 9.0 }));
+            }
+
+            #[test]
+            fn test_next_target_stays_within_bounds() {
+                let distribution = DistributionSettings {
+                    min: Some(0.0),
+                    max: Some(10.0),
+                    mean: Some(5.0),
+                    stddev: Some(20.0),
+                    ..Default::default()
+                };
+                let mut rng = StdRng::seed_from_u64(42);
+
+                for _ in 0..100 {
+                    let target = GenerationService::next_target(&distribution, &[], &mut rng).unwrap();
+                    assert!(
+                        target.min >= 0.0 && target.max <= 10.0 && target.min <= target.max,
+                        "{:?}",
+                        target
+                    );
+                }
+
+                assert_eq!(
+                    GenerationService::next_target(&DistributionSettings::default(), &[], &mut rng),
+                    Some(TargetRange {
+                        min: f64::NEG_INFINITY,
+                        max: f64::INFINITY
+                    })
+                );
+            }
+
+            #[test]
+            fn test_target_range_contains_and_clamp() {
+                let target = TargetRange { min: 1.5, max: 4.5 };
+
+                assert!(target.contains("2"));
+                assert!(target.contains(" 4.5 "));
+                assert!(!target.contains("5"));
+                assert!(!target.contains("two"));
+
+                assert_eq!(target.clamp("9", "INT"), "4");
+                assert_eq!(target.clamp("-3", "INT"), "2");
+                assert_eq!(target.clamp("9.5", "FLOAT"), "4.5");
+                assert_eq!(TargetRange { min: 1.2, max: 1.8 }.clamp("7", "INT"), "2");
+            }
+
+            #[test]
+            fn test_range_hint_uses_locale() {
+                let target = TargetRange { min: 1.5, max: 20.0 };
+
+                assert_eq!(
+                    GenerationService::range_hint(Some(&target), Locale::En),
+                    " (a value between 1.5 and 20)"
+                );
+                assert_eq!(
+                    GenerationService::range_hint(Some(&target), Locale::Fr),
+                    " (une valeur entre 1,5 et 20)"
+                );
+                assert_eq!(GenerationService::range_hint(None, Locale::En), "");
+
+                let unbounded = TargetRange {
+                    min: 0.0,
+                    max: f64::INFINITY,
+                };
+                assert_eq!(GenerationService::range_hint(Some(&unbounded), Locale::En), "");
+            }
+        }
+
+        mod provenance {
+            use super::*;
+
+            #[test]
+            fn test_content_hash_is_stable() {
+                assert_eq!(content_hash(""), "cbf29ce484222325");
+                assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+                assert_ne!(content_hash("A first name"), content_hash("A last name"));
+            }
+
+            #[test]
+            fn test_generated_row_for_job() {
+                let generated = GeneratedRow {
+                    data: Vec::new(),
+                    provenance: HashMap::from([(
+                        "1".to_string(),
+                        CellProvenance {
+                            prompt_hash: content_hash("prompt"),
+                            attempts: 1,
+                            ..Default::default()
+                        },
+                    )]),
+                    ..Default::default()
+                };
+                let job = GenerationJob {
+                    generation_id: "gen_1_42".to_string(),
+                    model_id: 7,
+                };
+
+                let cell = &generated.for_job(&job).provenance["1"];
+                assert_eq!(cell.model_id, Some(7));
+                assert_eq!(cell.generation_id.as_deref(), Some("gen_1_42"));
+                assert_eq!(cell.prompt_hash, content_hash("prompt"));
+            }
+        }
+
+        mod prompt_preview {
+            use super::*;
+
+            #[test]
+            fn test_estimate_tokens() {
+                assert_eq!(estimate_tokens(""), 0);
+                assert_eq!(estimate_tokens("abcd"), 1);
+                assert_eq!(estimate_tokens("A first name"), 3);
+                assert_eq!(estimate_tokens("東京タワー"), 5);
+                assert_eq!(estimate_tokens("Tokyo 東京"), 4);
+            }
+
+            #[test]
+            fn test_prompt_preview_new() {
+                let column = Column {
+                    id: Some(4),
+                    table_name: "test_table".to_string(),
+                    dataset_id: 1,
+                    name: "city".to_string(),
+                    column_type: "TEXT".to_string(),
+                    column_type_details: None,
+                    rules: "A city".to_string(),
+                    position: 1,
+                    settings: ColumnSettings::default(),
+                };
+
+                let preview = PromptPreview::new(&[column], "Generate a city".to_string());
+                assert_eq!(preview.column_ids, vec![4]);
+                assert_eq!(preview.estimated_tokens, 4);
+
+                let json = serde_json::to_value(&preview).unwrap();
+                assert_eq!(json["columnIds"], serde_json::json!([4]));
+                assert_eq!(json["estimatedTokens"], 4);
+            }
+
+            /// Dataset with a `first_name` column and a `greeting` column that uses it
+            fn setup_preview_dataset() -> (DatasetService, i64, Vec<i64>) {
+                let db = DatabaseService::new(None).expect("Failed to create database");
+                let dataset = DatasetService::new(db).expect("Failed to create dataset service");
+                let metadata = dataset
+                    .create("people", "People to greet")
+                    .expect("Failed to create dataset");
+                let columns = dataset
+                    .add_columns(
+                        metadata.id,
+                        &[
+                            ("first_name", "A first name"),
+                            ("greeting", "A greeting for @first_name"),
+                        ]
+                        .map(|(name, rules)| Column {
+                            id: None,
+                            table_name: metadata.table_name.clone(),
+                            dataset_id: metadata.id,
+                            name: name.to_string(),
+                            column_type: "TEXT".to_string(),
+                            column_type_details: None,
+                            rules: rules.to_string(),
+                            position: 1,
+                            settings: ColumnSettings::default(),
+                        }),
+                    )
+                    .expect("Failed to add columns");
+
+                (
+                    dataset,
+                    metadata.id,
+                    columns.iter().map(|column| column.id.unwrap()).collect(),
+                )
+            }
+
+            #[test]
+            fn test_preview_prompts_per_cell() {
+                let (dataset, dataset_id, column_ids) = setup_preview_dataset();
+
+                let previews = GenerationService::preview_prompts(&dataset, dataset_id, &HashMap::new())
+                    .expect("Failed to preview prompts");
+                assert_eq!(previews.len(), 2);
+                assert_eq!(previews[0].column_ids, vec![column_ids[0]]);
+                assert_eq!(previews[1].column_ids, vec![column_ids[1]]);
+                assert!(previews[0].prompt.contains("A first name"));
+                assert!(
+                    previews[1].prompt.contains("<first_name>"),
+                    "Upstream values get placeholders"
+                );
+                for preview in &previews {
+                    assert!(preview.estimated_tokens > 0);
+                    assert_eq!(preview.estimated_tokens, estimate_tokens(&preview.prompt));
+                }
+
+                let values = HashMap::from([(column_ids[0], "Ada".to_string())]);
+                let previews = GenerationService::preview_prompts(&dataset, dataset_id, &values)
+                    .expect("Failed to preview prompts");
+                assert_eq!(previews.len(), 1, "Columns with a value are not previewed");
+                assert_eq!(previews[0].column_ids, vec![column_ids[1]]);
+                assert!(previews[0].prompt.contains("Ada"));
+            }
+
+            #[test]
+            fn test_preview_prompts_json_rows() {
+                let (dataset, dataset_id, column_ids) = setup_preview_dataset();
+                dataset
+                    .update_settings(
+                        dataset_id,
+                        &DatasetSettings {
+                            row_strategy: RowStrategy::JsonObject,
+                            ..Default::default()
+                        },
+                    )
+                    .expect("Failed to update settings");
+
+                let previews = GenerationService::preview_prompts(&dataset, dataset_id, &HashMap::new())
+                    .expect("Failed to preview prompts");
+                assert_eq!(previews.len(), 1);
+                assert_eq!(previews[0].column_ids, column_ids);
+                assert!(previews[0].prompt.contains("first_name") && previews[0].prompt.contains("greeting"));
+                assert_eq!(previews[0].estimated_tokens, estimate_tokens(&previews[0].prompt));
+            }
+
+            #[test]
+            fn test_preview_prompts_reports_reference_errors() {
+                let (dataset, dataset_id, _) = setup_preview_dataset();
+                let empty = dataset.create("empty", "").expect("Failed to create dataset");
+                let empty_column = dataset
+                    .add_columns(
+                        empty.id,
+                        &[Column {
+                            id: None,
+                            table_name: empty.table_name.clone(),
+                            dataset_id: empty.id,
+                            name: "name".to_string(),
+                            column_type: "TEXT".to_string(),
+                            column_type_details: None,
+                            rules: "A name".to_string(),
+                            position: 1,
+                            settings: ColumnSettings::default(),
+                        }],
+                    )
+                    .expect("Failed to add column");
+                let metadata = dataset.find_by_id(dataset_id).expect("Failed to find dataset");
+                dataset
+                    .add_columns(
+                        dataset_id,
+                        &[Column {
+                            id: None,
+                            table_name: metadata.table_name,
+                            dataset_id,
+                            name: "friend".to_string(),
+                            column_type: "REFERENCE".to_string(),
+                            column_type_details: None,
+                            rules: String::new(),
+                            position: 3,
+                            settings: ColumnSettings {
+                                reference: Some(ReferenceSettings {
+                                    dataset_id: empty.id,
+                                    column_id: empty_column[0].id,
+                                    weight_column_id: None,
+                                }),
+                                ..Default::default()
+                            },
+                        }],
+                    )
+                    .expect("Failed to add reference column");
+
+                assert!(GenerationService::preview_prompts(&dataset, dataset_id, &HashMap::new()).is_err());
+            }
         }
     }
 }
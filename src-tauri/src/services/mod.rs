@@ -3,9 +3,11 @@ pub mod dataset;
 pub mod export;
 pub mod generation;
 pub mod model;
+pub mod template;
 
 pub use database::{DatabaseError, DatabaseService};
 pub use dataset::{DatasetMetadata, DatasetService};
 pub use export::ExportService;
-pub use generation::{GenerationService, RowGenerationProgress, RowGenerationStatus};
+pub use generation::{GenerationJob, GenerationService, RowGenerationProgress, RowGenerationStatus};
 pub use model::ModelService;
+pub use template::TemplateService;
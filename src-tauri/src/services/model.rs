@@ -85,6 +85,23 @@ pub struct ModelInfo {
     pub updated_at: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelBenchmark {
+    pub id: Option<i64>,
+    pub model_id: i64,
+    pub gpu_layers: u32,
+    pub load_time_ms: u64,
+    pub tokens_generated: u64,
+    pub tokens_per_second: f64,
+    /// Resident memory added by loading the model, creating its context and running the workload
+    pub memory_usage_bytes: u64,
+    pub cells_total: u32,
+    pub cells_parsed: u32,
+    pub parse_success_rate: f64,
+    pub created_at: String,
+}
+
 #[derive(Clone)]
 pub struct ModelService {
     pub db: DatabaseService,
@@ -149,6 +166,54 @@ impl ModelService {
         Ok(())
     }
 
+    pub fn save_benchmark(&self, benchmark: &ModelBenchmark) -> Result<ModelBenchmark, ModelError> {
+        self.get_model_info(benchmark.model_id)?;
+
+        self.db.execute(
+            "INSERT INTO model_benchmarks (model_id, gpu_layers, load_time_ms, tokens_generated, tokens_per_second, memory_usage_bytes, cells_total, cells_parsed, parse_success_rate) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                benchmark.model_id,
+                benchmark.gpu_layers,
+                benchmark.load_time_ms as i64,
+                benchmark.tokens_generated as i64,
+                benchmark.tokens_per_second,
+                benchmark.memory_usage_bytes as i64,
+                benchmark.cells_total,
+                benchmark.cells_parsed,
+                benchmark.parse_success_rate,
+            ],
+        )?;
+
+        self.list_benchmarks(benchmark.model_id)?
+            .into_iter()
+            .next()
+            .ok_or(ModelError::DatabaseError("Benchmark not found".to_string()))
+    }
+
+    pub fn list_benchmarks(&self, model_id: i64) -> Result<Vec<ModelBenchmark>, ModelError> {
+        let benchmarks = self.db.query(
+            "SELECT id, model_id, gpu_layers, load_time_ms, tokens_generated, tokens_per_second, memory_usage_bytes, cells_total, cells_parsed, parse_success_rate, created_at FROM model_benchmarks WHERE model_id = ? ORDER BY id DESC",
+            [model_id],
+            |row| {
+                Ok(ModelBenchmark {
+                    id: row.get::<_, Option<i64>>(0)?,
+                    model_id: row.get::<_, i64>(1)?,
+                    gpu_layers: row.get::<_, u32>(2)?,
+                    load_time_ms: row.get::<_, i64>(3)? as u64,
+                    tokens_generated: row.get::<_, i64>(4)? as u64,
+                    tokens_per_second: row.get::<_, f64>(5)?,
+                    memory_usage_bytes: row.get::<_, i64>(6)? as u64,
+                    cells_total: row.get::<_, u32>(7)?,
+                    cells_parsed: row.get::<_, u32>(8)?,
+                    parse_success_rate: row.get::<_, f64>(9)?,
+                    created_at: row.get::<_, String>(10)?,
+                })
+            },
+        )?;
+
+        Ok(benchmarks)
+    }
+
     pub fn get_model_info(&self, id: i64) -> Result<ModelInfo, ModelError> {
         let model = self.db.query("SELECT id, filename, quantization, label, model_type, size, created_at, updated_at FROM models WHERE id = ?", [id], |row| {
             Ok(ModelInfo {
@@ -652,6 +717,105 @@ mod tests {
         }
     }
 
+    mod benchmarks {
+        use super::*;
+        use crate::services::DatasetService;
+
+        /// Benchmarks live in a table the migrations create, which `DatasetService::new` applies
+        fn setup() -> (DatabaseService, ModelService) {
+            let db = DatabaseService::new(None).expect("Failed to create database");
+            DatasetService::new(db.clone()).expect("Failed to create dataset service");
+            let model_service = ModelService::new(None, db.clone()).expect("Failed to create model service");
+
+            (db, model_service)
+        }
+
+        fn insert_test_model(db: &DatabaseService) {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO models (filename, quantization, label, model_type, size) VALUES (?, ?, ?, ?, ?)",
+                ["model1.gguf", "Q4_K_M", "Test Model 1", "llm", "1000"],
+            )
+            .expect("Failed to insert model1");
+        }
+
+        fn create_test_benchmark(model_id: i64, gpu_layers: u32) -> ModelBenchmark {
+            ModelBenchmark {
+                id: None,
+                model_id,
+                gpu_layers,
+                load_time_ms: 1200,
+                tokens_generated: 300,
+                tokens_per_second: 42.5,
+                memory_usage_bytes: 2_000_000_000,
+                cells_total: 15,
+                cells_parsed: 12,
+                parse_success_rate: 80.0,
+                created_at: String::new(),
+            }
+        }
+
+        #[test]
+        fn test_save_benchmark() {
+            let (db, model_service) = setup();
+            insert_test_model(&db);
+
+            let saved = model_service
+                .save_benchmark(&create_test_benchmark(1, 20))
+                .expect("Failed to save benchmark");
+
+            assert!(saved.id.is_some(), "Saved benchmark should have an id");
+            assert_eq!(saved.model_id, 1);
+            assert_eq!(saved.gpu_layers, 20);
+            assert_eq!(saved.memory_usage_bytes, 2_000_000_000);
+            assert_eq!(saved.parse_success_rate, 80.0);
+            assert!(!saved.created_at.is_empty(), "Saved benchmark should have a timestamp");
+        }
+
+        #[test]
+        fn test_save_benchmark_unknown_model() {
+            let (_db, model_service) = setup();
+
+            let result = model_service.save_benchmark(&create_test_benchmark(42, 20));
+            assert!(result.is_err(), "Saving a benchmark for an unknown model should fail");
+        }
+
+        #[test]
+        fn test_list_benchmarks_most_recent_first() {
+            let (db, model_service) = setup();
+            insert_test_model(&db);
+
+            model_service
+                .save_benchmark(&create_test_benchmark(1, 0))
+                .expect("Failed to save benchmark");
+            model_service
+                .save_benchmark(&create_test_benchmark(1, 35))
+                .expect("Failed to save benchmark");
+
+            let benchmarks = model_service.list_benchmarks(1).expect("Failed to list benchmarks");
+
+            assert_eq!(benchmarks.len(), 2, "Should have 2 benchmarks");
+            assert_eq!(benchmarks[0].gpu_layers, 35, "Most recent benchmark should come first");
+            assert_eq!(benchmarks[1].gpu_layers, 0);
+        }
+
+        #[test]
+        fn test_benchmarks_deleted_with_model() {
+            let (db, model_service) = setup();
+            insert_test_model(&db);
+
+            model_service
+                .save_benchmark(&create_test_benchmark(1, 20))
+                .expect("Failed to save benchmark");
+
+            db.execute("DELETE FROM models WHERE id = ?", [1])
+                .expect("Failed to delete model");
+
+            let benchmarks = model_service.list_benchmarks(1).expect("Failed to list benchmarks");
+            assert!(benchmarks.is_empty(), "Benchmarks should be deleted with their model");
+        }
+    }
+
     mod download {
         use super::*;
 
@@ -0,0 +1,663 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::database::DatabaseError;
+use crate::services::dataset::{Column, ColumnSettings, DatasetError};
+use crate::services::{DatabaseService, DatasetMetadata, DatasetService};
+
+#[derive(Debug)]
+pub enum TemplateError {
+    NotFound(String),
+    DatabaseError(String),
+    FsError(String),
+    InvalidInput(String),
+    DatasetError(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            TemplateError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
+            TemplateError::FsError(msg) => write!(f, "File system error: {}", msg),
+            TemplateError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            TemplateError::DatasetError(msg) => write!(f, "Dataset error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<rusqlite::Error> for TemplateError {
+    fn from(err: rusqlite::Error) -> Self {
+        TemplateError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for TemplateError {
+    fn from(err: std::io::Error) -> Self {
+        TemplateError::FsError(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for TemplateError {
+    fn from(err: serde_json::Error) -> Self {
+        TemplateError::InvalidInput(err.to_string())
+    }
+}
+
+impl From<DatabaseError> for TemplateError {
+    fn from(err: DatabaseError) -> Self {
+        TemplateError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<DatasetError> for TemplateError {
+    fn from(err: DatasetError) -> Self {
+        TemplateError::DatasetError(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateColumn {
+    pub name: String,
+    pub column_type: String,
+    #[serde(default)]
+    pub column_type_details: Option<String>,
+    #[serde(default)]
+    pub rules: String,
+}
+
+/// Columns of a dataset without its rows or settings, ready to be instantiated into a new dataset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetTemplate {
+    /// `None` for the templates shipped with the app
+    pub id: Option<i64>,
+    pub name: String,
+    pub description: String,
+    pub columns: Vec<TemplateColumn>,
+    pub builtin: bool,
+    pub created_at: Option<String>,
+}
+
+/// Standalone JSON file written by `export` and read by `import`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TemplateFile {
+    name: String,
+    #[serde(default)]
+    description: String,
+    columns: Vec<TemplateColumn>,
+}
+
+/// Types a template column can have, `REFERENCE` columns point at a dataset that may not exist elsewhere
+const TEMPLATE_COLUMN_TYPES: &[&str] = &["TEXT", "INT", "FLOAT", "BOOL", "JSON"];
+
+/// (name, type, type details, rules) of a built-in template column
+type ColumnDefinition = (&'static str, &'static str, &'static str, &'static str);
+
+// (name, description, columns) of the templates shipped with the app
+const BUILTIN_TEMPLATES: &[(&str, &str, &[ColumnDefinition])] = &[
+    (
+        "Users",
+        "User accounts of a web application",
+        &[
+            ("first_name", "TEXT", "", "A realistic first name"),
+            ("last_name", "TEXT", "", "A realistic last name"),
+            (
+                "email",
+                "TEXT",
+                "",
+                "An email address built from @first_name and @last_name",
+            ),
+            ("age", "INT", "", "The age of an adult between 18 and 90"),
+            ("country", "TEXT", "", "A country name"),
+            ("is_active", "BOOL", "", "Whether the account is still in use"),
+        ],
+    ),
+    (
+        "E-commerce orders",
+        "Orders placed on an online store",
+        &[
+            ("order_number", "TEXT", "", "An order reference such as ORD-2024-00123"),
+            ("customer_name", "TEXT", "", "The full name of the customer"),
+            ("product", "TEXT", "", "A product sold by an online store"),
+            ("quantity", "INT", "", "The number of items ordered, between 1 and 5"),
+            (
+                "unit_price",
+                "FLOAT",
+                "",
+                "The price of one @product in euros, with two decimals",
+            ),
+            (
+                "shipping_address",
+                "JSON",
+                r#"{"street": "string", "zip_code": "string", "city": "string", "country": "string"}"#,
+                "The address @customer_name gets the order delivered to",
+            ),
+            (
+                "status",
+                "TEXT",
+                "",
+                "One of pending, paid, shipped, delivered or refunded",
+            ),
+        ],
+    ),
+    (
+        "Support tickets",
+        "Customer support requests and how they were handled",
+        &[
+            ("subject", "TEXT", "", "The short subject line of a support request"),
+            (
+                "message",
+                "TEXT",
+                "",
+                "The message a customer wrote about @subject, two to four sentences",
+            ),
+            ("category", "TEXT", "", "One of billing, technical, account or other"),
+            ("priority", "TEXT", "", "One of low, medium, high or urgent"),
+            ("resolved", "BOOL", "", "Whether the ticket has been resolved"),
+            (
+                "resolution_hours",
+                "FLOAT",
+                "",
+                "Hours it took to resolve a @priority priority ticket",
+            ),
+        ],
+    ),
+    (
+        "Medical encounters",
+        "Fictional patient visits, for testing healthcare software without real records",
+        &[
+            ("patient_name", "TEXT", "", "A fictional patient full name"),
+            ("age", "INT", "", "The age of the patient between 0 and 100"),
+            (
+                "encounter_type",
+                "TEXT",
+                "",
+                "One of consultation, emergency, follow-up or telehealth",
+            ),
+            (
+                "chief_complaint",
+                "TEXT",
+                "",
+                "The main reason a @age year old patient came for a @encounter_type",
+            ),
+            ("diagnosis", "TEXT", "", "A plausible diagnosis for @chief_complaint"),
+            (
+                "vitals",
+                "JSON",
+                r#"{"heart_rate": "number", "blood_pressure": "string", "temperature": "number"}"#,
+                "Vital signs consistent with @diagnosis",
+            ),
+        ],
+    ),
+];
+
+pub fn builtin_templates() -> Vec<DatasetTemplate> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .map(|(name, description, columns)| DatasetTemplate {
+            id: None,
+            name: name.to_string(),
+            description: description.to_string(),
+            columns: columns
+                .iter()
+                .map(|(name, column_type, details, rules)| TemplateColumn {
+                    name: name.to_string(),
+                    column_type: column_type.to_string(),
+                    column_type_details: Some(details.to_string()).filter(|details| !details.is_empty()),
+                    rules: rules.to_string(),
+                })
+                .collect(),
+            builtin: true,
+            created_at: None,
+        })
+        .collect()
+}
+
+pub fn validate_template(name: &str, columns: &[TemplateColumn]) -> Result<(), TemplateError> {
+    if name.trim().is_empty() {
+        return Err(TemplateError::InvalidInput("Template name cannot be empty".to_string()));
+    }
+    if columns.is_empty() {
+        return Err(TemplateError::InvalidInput(
+            "Template needs at least one column".to_string(),
+        ));
+    }
+
+    let mut names = HashSet::new();
+    for column in columns {
+        let name = column.name.trim();
+        if name.is_empty() {
+            return Err(TemplateError::InvalidInput("Column name cannot be empty".to_string()));
+        }
+        if !names.insert(name) {
+            return Err(TemplateError::InvalidInput(format!("Duplicate column '{}'", name)));
+        }
+        if !TEMPLATE_COLUMN_TYPES.contains(&column.column_type.trim()) {
+            return Err(TemplateError::InvalidInput(format!(
+                "Column '{}' has unsupported type {}",
+                name, column.column_type
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+const TEMPLATE_FIELDS: &str = "id, name, description, columns, created_at";
+
+fn template_from_row(row: &rusqlite::Row) -> Result<DatasetTemplate, DatabaseError> {
+    let columns: String = row.get(3)?;
+
+    Ok(DatasetTemplate {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        description: row.get(2)?,
+        columns: serde_json::from_str(&columns)?,
+        builtin: false,
+        created_at: Some(row.get(4)?),
+    })
+}
+
+#[derive(Clone)]
+pub struct TemplateService {
+    pub db: DatabaseService,
+    pub dataset_service: DatasetService,
+}
+
+impl TemplateService {
+    pub fn new(db: DatabaseService, dataset_service: DatasetService) -> Self {
+        Self { db, dataset_service }
+    }
+
+    /// Built-in templates first, then the saved ones by name
+    pub fn list(&self) -> Result<Vec<DatasetTemplate>, TemplateError> {
+        let mut templates = builtin_templates();
+        templates.extend(self.db.query(
+            &format!(
+                "SELECT {} FROM dataset_templates ORDER BY name COLLATE NOCASE ASC, id ASC",
+                TEMPLATE_FIELDS
+            ),
+            [],
+            template_from_row,
+        )?);
+
+        Ok(templates)
+    }
+
+    pub fn find_by_id(&self, id: i64) -> Result<DatasetTemplate, TemplateError> {
+        self.db
+            .query(
+                &format!("SELECT {} FROM dataset_templates WHERE id = ?", TEMPLATE_FIELDS),
+                [id],
+                template_from_row,
+            )?
+            .into_iter()
+            .next()
+            .ok_or_else(|| TemplateError::NotFound(format!("Template with id {} not found", id)))
+    }
+
+    pub fn save(
+        &self,
+        name: &str,
+        description: &str,
+        columns: &[TemplateColumn],
+    ) -> Result<DatasetTemplate, TemplateError> {
+        validate_template(name, columns)?;
+
+        let columns = columns
+            .iter()
+            .map(|column| TemplateColumn {
+                name: column.name.trim().to_string(),
+                column_type: column.column_type.trim().to_string(),
+                column_type_details: column
+                    .column_type_details
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|details| !details.is_empty())
+                    .map(str::to_string),
+                rules: column.rules.trim().to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        let id = {
+            let conn = self
+                .db
+                .conn
+                .lock()
+                .map_err(|_| TemplateError::DatabaseError("Failed to acquire mutex lock".to_string()))?;
+            conn.execute(
+                "INSERT INTO dataset_templates (name, description, columns) VALUES (?, ?, ?)",
+                rusqlite::params![name.trim(), description.trim(), serde_json::to_string(&columns)?],
+            )?;
+            conn.last_insert_rowid()
+        };
+
+        self.find_by_id(id)
+    }
+
+    /// Saves the columns of a dataset, rows and column settings are left out
+    pub fn save_from_dataset(
+        &self,
+        dataset_id: i64,
+        name: &str,
+        description: &str,
+    ) -> Result<DatasetTemplate, TemplateError> {
+        let columns = self.dataset_service.get_columns(dataset_id)?;
+        if let Some(column) = columns.iter().find(|column| column.column_type == "REFERENCE") {
+            return Err(TemplateError::InvalidInput(format!(
+                "Reference column '{}' cannot be saved in a template",
+                column.name
+            )));
+        }
+
+        let columns = columns
+            .into_iter()
+            .map(|column| TemplateColumn {
+                name: column.name,
+                column_type: column.column_type,
+                column_type_details: column.column_type_details,
+                rules: column.rules,
+            })
+            .collect::<Vec<_>>();
+
+        self.save(name, description, &columns)
+    }
+
+    pub fn delete(&self, id: i64) -> Result<(), TemplateError> {
+        self.find_by_id(id)?;
+        self.db.execute("DELETE FROM dataset_templates WHERE id = ?", [id])?;
+
+        Ok(())
+    }
+
+    /// Creates a dataset with the template columns, named after the template unless `name` is set
+    pub fn instantiate(
+        &self,
+        template: &DatasetTemplate,
+        name: Option<&str>,
+    ) -> Result<DatasetMetadata, TemplateError> {
+        validate_template(&template.name, &template.columns)?;
+
+        let name = name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&template.name);
+        let dataset = self.dataset_service.create(name, &template.description)?;
+
+        let columns = template
+            .columns
+            .iter()
+            .zip(1..)
+            .map(|(column, position)| Column {
+                id: None,
+                table_name: dataset.table_name.clone(),
+                dataset_id: dataset.id,
+                name: column.name.clone(),
+                column_type: column.column_type.trim().to_string(),
+                column_type_details: column.column_type_details.clone(),
+                rules: column.rules.clone(),
+                position,
+                settings: ColumnSettings::default(),
+            })
+            .collect::<Vec<_>>();
+        if let Err(e) = self.dataset_service.add_columns(dataset.id, &columns) {
+            if let Err(cleanup) = self.dataset_service.discard(&dataset) {
+                return Err(TemplateError::DatasetError(format!(
+                    "{}, the new dataset could not be removed: {}",
+                    e, cleanup
+                )));
+            }
+            return Err(e.into());
+        }
+
+        Ok(self.dataset_service.find_by_id(dataset.id)?)
+    }
+
+    pub fn export(&self, template: &DatasetTemplate, file_path: &str) -> Result<(), TemplateError> {
+        validate_template(&template.name, &template.columns)?;
+
+        let file = TemplateFile {
+            name: template.name.clone(),
+            description: template.description.clone(),
+            columns: template.columns.clone(),
+        };
+        fs::write(file_path, serde_json::to_string_pretty(&file)?)?;
+
+        Ok(())
+    }
+
+    /// Reads a file written by `export` and saves it as a new template
+    pub fn import(&self, file_path: &str) -> Result<DatasetTemplate, TemplateError> {
+        let file: TemplateFile = serde_json::from_str(&fs::read_to_string(file_path)?)?;
+
+        self.save(&file.name, &file.description, &file.columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::dataset::RowData;
+    use tempfile::tempdir;
+
+    fn setup() -> TemplateService {
+        let db = DatabaseService::new(None).expect("Failed to create database");
+        let dataset_service = DatasetService::new(db.clone()).expect("Failed to create dataset service");
+
+        TemplateService::new(db, dataset_service)
+    }
+
+    fn template_column(name: &str, column_type: &str, rules: &str) -> TemplateColumn {
+        TemplateColumn {
+            name: name.to_string(),
+            column_type: column_type.to_string(),
+            column_type_details: None,
+            rules: rules.to_string(),
+        }
+    }
+
+    mod builtin {
+        use super::*;
+
+        #[test]
+        fn test_builtin_templates_are_valid() {
+            let templates = builtin_templates();
+            assert_eq!(templates.len(), 4);
+
+            for template in &templates {
+                assert!(template.builtin && template.id.is_none());
+                assert!(
+                    validate_template(&template.name, &template.columns).is_ok(),
+                    "{} should be valid",
+                    template.name
+                );
+            }
+        }
+
+        #[test]
+        fn test_instantiate_builtin_template() {
+            let service = setup();
+            let template = builtin_templates()
+                .into_iter()
+                .find(|template| template.name == "E-commerce orders")
+                .unwrap();
+
+            let dataset = service.instantiate(&template, Some("Orders Q1")).unwrap();
+            assert_eq!(dataset.name, "Orders Q1");
+            assert_eq!(dataset.description, template.description);
+
+            let columns = service.dataset_service.get_columns(dataset.id).unwrap();
+            assert_eq!(
+                columns.iter().map(|column| column.name.as_str()).collect::<Vec<_>>(),
+                template
+                    .columns
+                    .iter()
+                    .map(|column| column.name.as_str())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                columns.iter().map(|column| column.position).collect::<Vec<_>>(),
+                (1..=template.columns.len() as i64).collect::<Vec<_>>()
+            );
+            let address = columns.iter().find(|column| column.name == "shipping_address").unwrap();
+            assert_eq!(address.column_type, "JSON");
+            assert!(address.column_type_details.as_deref().unwrap().contains("zip_code"));
+
+            let data = columns
+                .iter()
+                .map(|column| RowData {
+                    column_id: column.id.unwrap().to_string(),
+                    value: Some("1".to_string()),
+                })
+                .collect::<Vec<_>>();
+            service
+                .dataset_service
+                .add_row(dataset.id, &data)
+                .expect("Instantiated datasets accept rows");
+        }
+
+        #[test]
+        fn test_failed_instantiate_removes_the_dataset() {
+            let service = setup();
+            let template = builtin_templates().into_iter().next().unwrap();
+            // A leftover table without a data column makes adding the columns fail
+            service
+                .db
+                .execute("CREATE TABLE dataset1 (id INTEGER PRIMARY KEY, other TEXT)", [])
+                .unwrap();
+
+            assert!(service.instantiate(&template, None).is_err());
+            assert!(service.dataset_service.find_all().unwrap().is_empty());
+            for table in ["dataset1", "dataset1_fts"] {
+                assert!(!service.db.table_exists(table).unwrap(), "{} was left behind", table);
+            }
+        }
+    }
+
+    mod saved {
+        use super::*;
+
+        #[test]
+        fn test_save_list_and_delete() {
+            let service = setup();
+            let columns = [
+                template_column(" title ", "TEXT", "A book title"),
+                template_column("pages", "INT", "The page count of @title"),
+            ];
+
+            let template = service.save(" Books ", "Library catalog", &columns).unwrap();
+            assert_eq!(template.name, "Books");
+            assert!(!template.builtin);
+            assert_eq!(template.columns[0].name, "title");
+
+            let listed = service.list().unwrap();
+            assert_eq!(listed.len(), builtin_templates().len() + 1);
+            assert_eq!(listed.last(), Some(&template));
+
+            service.delete(template.id.unwrap()).unwrap();
+            assert_eq!(service.list().unwrap().len(), builtin_templates().len());
+            assert!(matches!(
+                service.delete(template.id.unwrap()),
+                Err(TemplateError::NotFound(_))
+            ));
+        }
+
+        #[test]
+        fn test_rejects_invalid_templates() {
+            let service = setup();
+            let column = template_column("title", "TEXT", "A book title");
+
+            assert!(matches!(
+                service.save("  ", "", std::slice::from_ref(&column)),
+                Err(TemplateError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                service.save("Books", "", &[]),
+                Err(TemplateError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                service.save("Books", "", &[column.clone(), column.clone()]),
+                Err(TemplateError::InvalidInput(_))
+            ));
+            assert!(matches!(
+                service.save("Books", "", &[template_column("author", "REFERENCE", "")]),
+                Err(TemplateError::InvalidInput(_))
+            ));
+            assert_eq!(service.list().unwrap().len(), builtin_templates().len());
+        }
+
+        #[test]
+        fn test_save_from_dataset() {
+            let service = setup();
+            let users = builtin_templates().into_iter().next().unwrap();
+            let dataset = service.instantiate(&users, None).unwrap();
+            assert_eq!(dataset.name, "Users");
+
+            let template = service
+                .save_from_dataset(dataset.id, "My users", "Copied from a dataset")
+                .unwrap();
+            assert_eq!(template.columns, users.columns);
+
+            let copy = service.instantiate(&template, None).unwrap();
+            assert_eq!(copy.name, "My users");
+            assert_ne!(copy.table_name, dataset.table_name);
+            assert_eq!(
+                service.dataset_service.get_columns(copy.id).unwrap().len(),
+                users.columns.len()
+            );
+        }
+    }
+
+    mod files {
+        use super::*;
+
+        #[test]
+        fn test_export_and_import() {
+            let service = setup();
+            let dir = tempdir().expect("Failed to create temp directory");
+            let path = dir.path().join("tickets.json");
+            let path = path.to_str().unwrap();
+            let tickets = builtin_templates()
+                .into_iter()
+                .find(|template| template.name == "Support tickets")
+                .unwrap();
+
+            service.export(&tickets, path).unwrap();
+            let content = fs::read_to_string(path).unwrap();
+            assert!(content.contains("\"columnType\""));
+            assert!(!content.contains("\"builtin\""));
+
+            let imported = service.import(path).unwrap();
+            assert!(imported.id.is_some() && !imported.builtin);
+            assert_eq!(imported.name, tickets.name);
+            assert_eq!(imported.columns, tickets.columns);
+        }
+
+        #[test]
+        fn test_import_rejects_invalid_files() {
+            let service = setup();
+            let dir = tempdir().expect("Failed to create temp directory");
+            let path = dir.path().join("template.json");
+            let path = path.to_str().unwrap();
+
+            fs::write(path, "not json").unwrap();
+            assert!(matches!(service.import(path), Err(TemplateError::InvalidInput(_))));
+
+            fs::write(path, r#"{"name": "Empty", "columns": []}"#).unwrap();
+            assert!(matches!(service.import(path), Err(TemplateError::InvalidInput(_))));
+
+            assert!(matches!(
+                service.import(dir.path().join("missing.json").to_str().unwrap()),
+                Err(TemplateError::FsError(_))
+            ));
+        }
+    }
+}
@@ -1,17 +1,117 @@
-pub static CELL_PROMPT_TEMPLATE: &str = r#"<|begin_of_text|><|start_header_id|>system<|end_header_id|>
-You are a data generator. You must respond with ONLY the requested value. No explanations, no code, no markdown, no extra text.<|eot_id|>
+use regex::Regex;
+
+pub static CHAT_TEMPLATE: &str = r#"<|begin_of_text|><|start_header_id|>system<|end_header_id|>
+{system_prompt}<|eot_id|>
 
 <|start_header_id|>user<|end_header_id|>
-Generate a {format} value for column "{column_name}".
+{user_prompt}
 
-Rule: {column_rule}
+<|eot_id|>
+
+<|start_header_id|>assistant<|end_header_id|>
+"#;
+
+pub static CELL_SYSTEM_PROMPT: &str = "You are a data generator. You must respond with ONLY the requested value. No explanations, no code, no markdown, no extra text.";
 
+pub static CELL_PROMPT_TEMPLATE: &str = r#"Generate a {format} value for column "{column_name}".
+
+Rule: {column_rule}
+{context}{examples}{exclusions}
 CRITICAL:
 - If the rule references other values from the same record, your response MUST be logically consistent with those values
-- Reply with a SINGLE LINE only - no newlines, no extra content
-- Output ONLY the raw value, nothing else
+- {line_rule}
+- Output ONLY the raw value, nothing else"#;
 
-<|eot_id|>
+pub const REQUIRED_CELL_PLACEHOLDERS: &[&str] = &["column_name", "column_rule", "format"];
 
-<|start_header_id|>assistant<|end_header_id|>
-"#;
+pub const OPTIONAL_CELL_PLACEHOLDERS: &[&str] = &[
+    "context",
+    "dataset_description",
+    "row_context",
+    "examples",
+    "exclusions",
+    "line_rule",
+];
+
+pub fn render_chat_prompt(system_prompt: &str, user_prompt: &str) -> String {
+    CHAT_TEMPLATE
+        .replace("{system_prompt}", system_prompt)
+        .replace("{user_prompt}", user_prompt)
+}
+
+pub fn validate_cell_template(template: &str) -> Result<(), String> {
+    let missing: Vec<String> = REQUIRED_CELL_PLACEHOLDERS
+        .iter()
+        .filter(|placeholder| !template.contains(&format!("{{{}}}", placeholder)))
+        .map(|placeholder| format!("{{{}}}", placeholder))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(format!(
+            "Template is missing required placeholders: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let placeholder_regex = Regex::new(r"\{([a-z_]+)\}").map_err(|e| e.to_string())?;
+    let unknown: Vec<&str> = placeholder_regex
+        .captures_iter(template)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str()))
+        .filter(|name| !REQUIRED_CELL_PLACEHOLDERS.contains(name) && !OPTIONAL_CELL_PLACEHOLDERS.contains(name))
+        .collect();
+
+    if !unknown.is_empty() {
+        return Err(format!(
+            "Template uses unknown placeholders: {{{}}}",
+            unknown.join("}, {")
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_cell_template_is_valid() {
+        assert!(validate_cell_template(CELL_PROMPT_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn test_validate_cell_template_missing_placeholders() {
+        let result = validate_cell_template("Give me a {format} for {column_name}");
+
+        assert_eq!(
+            result,
+            Err("Template is missing required placeholders: {column_rule}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_cell_template_unknown_placeholders() {
+        let result = validate_cell_template("{column_name} {column_rule} {format} {colum_rule} {tone}");
+
+        assert_eq!(
+            result,
+            Err("Template uses unknown placeholders: {colum_rule}, {tone}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_cell_template_ignores_json_braces() {
+        let template = r#"{column_name} {column_rule} {format} {row_context} like {"key": "value"}"#;
+
+        assert!(validate_cell_template(template).is_ok());
+    }
+
+    #[test]
+    fn test_render_chat_prompt() {
+        let prompt = render_chat_prompt("Be terse.", "Say hi");
+
+        assert!(prompt.contains("system<|end_header_id|>\nBe terse.<|eot_id|>"));
+        assert!(prompt.contains("user<|end_header_id|>\nSay hi\n"));
+        assert!(prompt.ends_with("assistant<|end_header_id|>\n"));
+    }
+}
@@ -136,6 +136,28 @@ pub fn detect_optimal_gpu_layers() -> u32 {
     }
 }
 
+/// Resident set size of the current process, in bytes.
+pub fn resident_memory_bytes() -> Option<u64> {
+    let kb = if cfg!(target_os = "linux") {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+        line.trim_start_matches("VmRSS:").trim().trim_end_matches("kB").trim().to_string()
+    } else if cfg!(target_os = "macos") {
+        let output = Command::new("ps")
+            .arg("-o")
+            .arg("rss=")
+            .arg("-p")
+            .arg(std::process::id().to_string())
+            .output()
+            .ok()?;
+        String::from_utf8(output.stdout).ok()?.trim().to_string()
+    } else {
+        return None;
+    };
+
+    kb.parse::<u64>().ok().map(|kb| kb * 1024)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,4 +172,12 @@ mod tests {
             layers
         );
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_resident_memory_bytes() {
+        let rss = resident_memory_bytes().expect("VmRSS should be readable on Linux");
+
+        assert!(rss > 0, "RSS should be positive, got {}", rss);
+    }
 }
@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+
+use super::{CELL_PROMPT_TEMPLATE, CELL_SYSTEM_PROMPT, ROW_PROMPT_TEMPLATE, ROW_SYSTEM_PROMPT};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+    De,
+    Ja,
+}
+
+/// Prompt wording for one locale; `{name}` and `{max_chars}` are filled in by the prompt builder
+pub struct LocaleStrings {
+    pub system_prompt: &'static str,
+    pub cell_template: &'static str,
+    pub row_system_prompt: &'static str,
+    pub row_template: &'static str,
+    pub examples_header: &'static str,
+    pub exclusions_header: &'static str,
+    pub dataset_purpose: &'static str,
+    pub known_values_header: &'static str,
+    pub pending_value: &'static str,
+    pub single_line_rule: &'static str,
+    pub multiline_rule: &'static str,
+    pub max_chars_rule: &'static str,
+    pub range_hint: &'static str,
+    pub integer_hint: &'static str,
+    pub float_hint: &'static str,
+}
+
+static EN: LocaleStrings = LocaleStrings {
+    system_prompt: CELL_SYSTEM_PROMPT,
+    cell_template: CELL_PROMPT_TEMPLATE,
+    row_system_prompt: ROW_SYSTEM_PROMPT,
+    row_template: ROW_PROMPT_TEMPLATE,
+    examples_header: "Examples of existing values for this column (match their style, do not copy them):",
+    exclusions_header: "These values are already taken, do NOT reuse them:",
+    dataset_purpose: "Dataset purpose:",
+    known_values_header: "Known values of this record:",
+    pending_value: "the \"{name}\" value",
+    single_line_rule: "Reply with a SINGLE LINE only - no newlines, no extra content",
+    multiline_rule: "Multiple lines are allowed - no extra content",
+    max_chars_rule: " - at most {max_chars} characters",
+    range_hint: " (a value between {min} and {max})",
+    integer_hint: "",
+    float_hint: "",
+};
+
+static FR: LocaleStrings = LocaleStrings {
+    system_prompt: "Tu es un générateur de données. Tu dois répondre UNIQUEMENT avec la valeur demandée. Pas d'explications, pas de code, pas de markdown, pas de texte supplémentaire. Rédige les valeurs textuelles en français.",
+    cell_template: r#"Génère une valeur {format} pour la colonne "{column_name}".
+
+Règle : {column_rule}
+{context}{examples}{exclusions}
+IMPORTANT :
+- Si la règle fait référence à d'autres valeurs du même enregistrement, ta réponse DOIT être cohérente avec ces valeurs
+- Les nombres utilisent la virgule décimale (3,14) et les dates le format JJ/MM/AAAA
+- {line_rule}
+- Renvoie UNIQUEMENT la valeur brute, rien d'autre"#,
+    row_system_prompt: "Tu es un générateur de données. Tu dois répondre UNIQUEMENT avec un seul objet JSON. Pas d'explications, pas de code, pas de markdown, pas de texte supplémentaire.",
+    row_template: r#"Génère un enregistrement sous forme d'objet JSON avec exactement ces clés, dans cet ordre :
+{fields}
+{context}
+IMPORTANT :
+- Chaque valeur doit respecter sa règle et être cohérente avec les autres valeurs de l'enregistrement
+- Utilise exactement les noms de clés ci-dessus, sans clé supplémentaire
+- Les textes sont en français, les dates au format JJ/MM/AAAA et les nombres sont des nombres JSON
+- Renvoie UNIQUEMENT l'objet JSON sur une seule ligne"#,
+    examples_header: "Exemples de valeurs existantes pour cette colonne (imite leur style, ne les copie pas) :",
+    exclusions_header: "Ces valeurs sont déjà prises, ne les réutilise PAS :",
+    dataset_purpose: "Objectif du jeu de données :",
+    known_values_header: "Valeurs connues de cet enregistrement :",
+    pending_value: "la valeur \"{name}\"",
+    single_line_rule: "Réponds sur UNE SEULE LIGNE - pas de retour à la ligne, pas de contenu supplémentaire",
+    multiline_rule: "Plusieurs lignes sont autorisées - pas de contenu supplémentaire",
+    max_chars_rule: " - au plus {max_chars} caractères",
+    range_hint: " (une valeur entre {min} et {max})",
+    integer_hint: "nombre entier, ex. 1 250",
+    float_hint: "nombre décimal, ex. 3,14",
+};
+
+static DE: LocaleStrings = LocaleStrings {
+    system_prompt: "Du bist ein Datengenerator. Du antwortest NUR mit dem angeforderten Wert. Keine Erklärungen, kein Code, kein Markdown, kein zusätzlicher Text. Schreibe Textwerte auf Deutsch.",
+    cell_template: r#"Erzeuge einen {format}-Wert für die Spalte "{column_name}".
+
+Regel: {column_rule}
+{context}{examples}{exclusions}
+WICHTIG:
+- Wenn die Regel auf andere Werte desselben Datensatzes verweist, MUSS deine Antwort logisch zu diesen Werten passen
+- Zahlen verwenden das Dezimalkomma (3,14), Datumsangaben das Format TT.MM.JJJJ
+- {line_rule}
+- Gib NUR den reinen Wert aus, sonst nichts"#,
+    row_system_prompt: "Du bist ein Datengenerator. Du antwortest NUR mit einem einzigen JSON-Objekt. Keine Erklärungen, kein Code, kein Markdown, kein zusätzlicher Text.",
+    row_template: r#"Erzeuge einen Datensatz als JSON-Objekt mit genau diesen Schlüsseln, in dieser Reihenfolge:
+{fields}
+{context}
+WICHTIG:
+- Jeder Wert muss seiner Regel folgen und logisch zu den anderen Werten des Datensatzes passen
+- Verwende genau die obigen Schlüsselnamen, keine zusätzlichen Schlüssel
+- Texte auf Deutsch, Datumsangaben im Format TT.MM.JJJJ, Zahlen als JSON-Zahlen
+- Gib NUR das JSON-Objekt in einer einzigen Zeile aus"#,
+    examples_header: "Beispiele vorhandener Werte dieser Spalte (übernimm ihren Stil, kopiere sie nicht):",
+    exclusions_header: "Diese Werte sind bereits vergeben, verwende sie NICHT erneut:",
+    dataset_purpose: "Zweck des Datasets:",
+    known_values_header: "Bekannte Werte dieses Datensatzes:",
+    pending_value: "der Wert \"{name}\"",
+    single_line_rule: "Antworte in EINER EINZIGEN ZEILE - keine Zeilenumbrüche, kein zusätzlicher Inhalt",
+    multiline_rule: "Mehrere Zeilen sind erlaubt - kein zusätzlicher Inhalt",
+    max_chars_rule: " - höchstens {max_chars} Zeichen",
+    range_hint: " (ein Wert zwischen {min} und {max})",
+    integer_hint: "ganze Zahl, z. B. 1.250",
+    float_hint: "Dezimalzahl, z. B. 3,14",
+};
+
+static JA: LocaleStrings = LocaleStrings {
+    system_prompt: "あなたはデータ生成器です。要求された値のみを出力してください。説明、コード、Markdown、余分なテキストは一切不要です。テキストの値は日本語で書いてください。",
+    cell_template: r#"列「{column_name}」の{format}値を生成してください。
+
+ルール: {column_rule}
+{context}{examples}{exclusions}
+重要:
+- ルールが同じレコードの他の値を参照している場合、回答はそれらの値と論理的に一貫していなければなりません
+- 数値は半角数字（例: 3.14）、日付はYYYY年MM月DD日の形式で書いてください
+- {line_rule}
+- 値そのものだけを出力し、それ以外は何も出力しないでください"#,
+    row_system_prompt: "あなたはデータ生成器です。JSONオブジェクトを1つだけ出力してください。説明、コード、Markdown、余分なテキストは一切不要です。",
+    row_template: r#"次のキーを正確にこの順序で持つJSONオブジェクトとして、レコードを1件生成してください:
+{fields}
+{context}
+重要:
+- 各値はそのルールに従い、レコードの他の値と論理的に一貫していなければなりません
+- 上記のキー名をそのまま使い、余分なキーを追加しないでください
+- テキストは日本語、日付はYYYY年MM月DD日の形式、数値はJSONの数値で書いてください
+- JSONオブジェクトのみを1行で出力してください"#,
+    examples_header: "この列の既存の値の例（スタイルを合わせ、そのままコピーしないこと）:",
+    exclusions_header: "次の値は既に使われています。再利用しないでください:",
+    dataset_purpose: "データセットの目的:",
+    known_values_header: "このレコードの既知の値:",
+    pending_value: "「{name}」の値",
+    single_line_rule: "1行だけで回答してください - 改行や余分な内容は不要です",
+    multiline_rule: "複数行も可 - 余分な内容は不要です",
+    max_chars_rule: " - {max_chars}文字以内",
+    range_hint: "（{min}から{max}までの値）",
+    integer_hint: "整数、半角数字",
+    float_hint: "小数、例: 3.14",
+};
+
+impl Locale {
+    pub fn strings(&self) -> &'static LocaleStrings {
+        match self {
+            Locale::En => &EN,
+            Locale::Fr => &FR,
+            Locale::De => &DE,
+            Locale::Ja => &JA,
+        }
+    }
+
+    pub fn decimal_separator(&self) -> char {
+        match self {
+            Locale::Fr | Locale::De => ',',
+            Locale::En | Locale::Ja => '.',
+        }
+    }
+
+    /// Digit grouping characters, in addition to whichever of `.` / `,` is not the decimal separator
+    pub fn is_group_separator(&self, c: char) -> bool {
+        match self {
+            Locale::Fr | Locale::De => matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '\''),
+            Locale::En | Locale::Ja => false,
+        }
+    }
+
+    pub fn parse_bool(&self, text: &str) -> Option<bool> {
+        let word = text.trim().trim_end_matches(['.', '。']).to_lowercase();
+        if let Ok(flag) = word.parse::<bool>() {
+            return Some(flag);
+        }
+
+        let (truthy, falsy): (&[&str], &[&str]) = match self {
+            Locale::En => (&[], &[]),
+            Locale::Fr => (&["vrai", "oui"], &["faux", "non"]),
+            Locale::De => (&["wahr", "ja"], &["falsch", "nein"]),
+            Locale::Ja => (&["真", "はい"], &["偽", "いいえ"]),
+        };
+
+        if truthy.contains(&word.as_str()) {
+            Some(true)
+        } else if falsy.contains(&word.as_str()) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Maps full-width digits and signs (common in Japanese output) to their ASCII forms
+pub fn fold_full_width(c: char) -> char {
+    match c {
+        '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+        '．' => '.',
+        '，' => ',',
+        '－' | '−' => '-',
+        '＋' => '+',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::validate_cell_template;
+
+    const LOCALES: [Locale; 4] = [Locale::En, Locale::Fr, Locale::De, Locale::Ja];
+
+    #[test]
+    fn test_english_strings_match_default_templates() {
+        assert_eq!(Locale::En.strings().cell_template, CELL_PROMPT_TEMPLATE);
+        assert_eq!(Locale::En.strings().system_prompt, CELL_SYSTEM_PROMPT);
+        assert_eq!(Locale::default(), Locale::En);
+    }
+
+    #[test]
+    fn test_localized_templates_are_valid() {
+        for locale in LOCALES {
+            let strings = locale.strings();
+            assert!(validate_cell_template(strings.cell_template).is_ok(), "{:?}", locale);
+            assert!(strings.cell_template.contains("{line_rule}"), "{:?}", locale);
+            assert!(strings.row_template.contains("{fields}"), "{:?}", locale);
+            assert!(strings.row_template.contains("{context}"), "{:?}", locale);
+            assert!(strings.pending_value.contains("{name}"), "{:?}", locale);
+            assert!(strings.max_chars_rule.contains("{max_chars}"), "{:?}", locale);
+            assert!(
+                strings.range_hint.contains("{min}") && strings.range_hint.contains("{max}"),
+                "{:?}",
+                locale
+            );
+        }
+    }
+
+    #[test]
+    fn test_locale_serialization() {
+        assert_eq!(serde_json::to_string(&Locale::Ja).unwrap(), "\"ja\"");
+        assert_eq!(serde_json::from_str::<Locale>("\"de\"").unwrap(), Locale::De);
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        assert_eq!(Locale::Fr.parse_bool("Vrai"), Some(true));
+        assert_eq!(Locale::De.parse_bool("nein."), Some(false));
+        assert_eq!(Locale::Ja.parse_bool("はい。"), Some(true));
+        assert_eq!(Locale::Ja.parse_bool("false"), Some(false));
+        assert_eq!(Locale::En.parse_bool("oui"), None);
+    }
+
+    #[test]
+    fn test_fold_full_width() {
+        let folded: String = "－１２．５".chars().map(fold_full_width).collect();
+        assert_eq!(folded, "-12.5");
+    }
+}
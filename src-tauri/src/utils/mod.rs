@@ -1,5 +1,11 @@
 mod hardware;
 mod cell_prompt_template;
+mod row_prompt_template;
+mod locale;
+mod pii;
 
 pub use hardware::*;
-pub use cell_prompt_template::CELL_PROMPT_TEMPLATE;
+pub use cell_prompt_template::*;
+pub use row_prompt_template::*;
+pub use locale::*;
+pub use pii::*;
@@ -0,0 +1,299 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PiiKind {
+    CreditCard,
+    Email,
+    Ssn,
+    Phone,
+    PublicFigure,
+}
+
+impl PiiKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PiiKind::CreditCard => "creditCard",
+            PiiKind::Email => "email",
+            PiiKind::Ssn => "ssn",
+            PiiKind::Phone => "phone",
+            PiiKind::PublicFigure => "publicFigure",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            PiiKind::CreditCard,
+            PiiKind::Email,
+            PiiKind::Ssn,
+            PiiKind::Phone,
+            PiiKind::PublicFigure,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+    pub kind: PiiKind,
+    pub value: String,
+}
+
+/// Domains reserved for documentation and testing (RFC 2606), safe in synthetic emails
+const RESERVED_EMAIL_DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+const RESERVED_EMAIL_TLDS: &[&str] = &["example", "test", "invalid", "localhost"];
+
+/// Real people small models tend to reproduce from their training data
+const PUBLIC_FIGURES: &[&str] = &[
+    "Angela Merkel",
+    "Barack Obama",
+    "Beyoncé",
+    "Bill Gates",
+    "Brad Pitt",
+    "Cristiano Ronaldo",
+    "Donald Trump",
+    "Elon Musk",
+    "Emma Watson",
+    "Emmanuel Macron",
+    "Jeff Bezos",
+    "Jennifer Aniston",
+    "Joe Biden",
+    "Justin Bieber",
+    "Kanye West",
+    "Keanu Reeves",
+    "Kim Kardashian",
+    "Lady Gaga",
+    "LeBron James",
+    "Leonardo DiCaprio",
+    "Lionel Messi",
+    "Mark Zuckerberg",
+    "Michael Jordan",
+    "Narendra Modi",
+    "Oprah Winfrey",
+    "Rihanna",
+    "Serena Williams",
+    "Taylor Swift",
+    "Tom Cruise",
+    "Tom Hanks",
+    "Vladimir Putin",
+    "Warren Buffett",
+];
+
+/// `(prefix digits, first prefix, last prefix, lengths)` of the Visa, Mastercard, American Express and
+/// Discover numbering ranges
+const CARD_ISSUERS: &[(usize, u32, u32, &[usize])] = &[
+    (1, 4, 4, &[13, 16, 19]),
+    (2, 51, 55, &[16]),
+    (4, 2221, 2720, &[16]),
+    (2, 34, 34, &[15]),
+    (2, 37, 37, &[15]),
+    (4, 6011, 6011, &[16, 17, 18, 19]),
+    (2, 65, 65, &[16, 17, 18, 19]),
+];
+
+static CARD_REGEX: OnceLock<Regex> = OnceLock::new();
+static EMAIL_REGEX: OnceLock<Regex> = OnceLock::new();
+static SSN_REGEX: OnceLock<Regex> = OnceLock::new();
+static PHONE_REGEX: OnceLock<Regex> = OnceLock::new();
+static PUBLIC_FIGURE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Real-looking personal data in `text`: Luhn-valid card numbers in an issuer range, emails on non-reserved
+/// domains, assignable SSNs, North American phone numbers outside the fictional 555 exchange and well-known
+/// public figures
+pub fn scan_pii(text: &str) -> Vec<PiiMatch> {
+    let card_regex = CARD_REGEX.get_or_init(|| Regex::new(r"\b[2-6](?:[ -]?\d){12,18}\b").unwrap());
+    let email_regex = EMAIL_REGEX
+        .get_or_init(|| Regex::new(r"(?i)\b[a-z0-9._%+-]+@([a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,})\b").unwrap());
+    let ssn_regex = SSN_REGEX.get_or_init(|| Regex::new(r"\b(\d{3})-(\d{2})-(\d{4})\b").unwrap());
+    let phone_regex = PHONE_REGEX
+        .get_or_init(|| Regex::new(r"(?:\+1[ .-]?)?(?:\(\d{3}\)\s?|\b\d{3}[ .-])(\d{3})[ .-](\d{4})\b").unwrap());
+    let public_figure_regex = PUBLIC_FIGURE_REGEX.get_or_init(|| {
+        let names = PUBLIC_FIGURES
+            .iter()
+            .map(|name| regex::escape(name).replace(' ', r"\s+"))
+            .collect::<Vec<_>>()
+            .join("|");
+        Regex::new(&format!(r"(?i)\b(?:{})\b", names)).unwrap()
+    });
+
+    let mut matches = Vec::new();
+
+    for found in card_regex.find_iter(text) {
+        let digits: Vec<u32> = found.as_str().chars().filter_map(|c| c.to_digit(10)).collect();
+        if is_card_issuer(&digits) && is_luhn_valid(&digits) {
+            matches.push(PiiMatch {
+                kind: PiiKind::CreditCard,
+                value: found.as_str().to_string(),
+            });
+        }
+    }
+
+    for captures in email_regex.captures_iter(text) {
+        if !is_reserved_domain(&captures[1]) {
+            matches.push(PiiMatch {
+                kind: PiiKind::Email,
+                value: captures[0].to_string(),
+            });
+        }
+    }
+
+    for captures in ssn_regex.captures_iter(text) {
+        let area = &captures[1];
+        let assignable = area != "000" && area != "666" && !area.starts_with('9');
+        if assignable && &captures[2] != "00" && &captures[3] != "0000" {
+            matches.push(PiiMatch {
+                kind: PiiKind::Ssn,
+                value: captures[0].to_string(),
+            });
+        }
+    }
+
+    for captures in phone_regex.captures_iter(text) {
+        if &captures[1] != "555" {
+            matches.push(PiiMatch {
+                kind: PiiKind::Phone,
+                value: captures[0].trim().to_string(),
+            });
+        }
+    }
+
+    for found in public_figure_regex.find_iter(text) {
+        matches.push(PiiMatch {
+            kind: PiiKind::PublicFigure,
+            value: found.as_str().to_string(),
+        });
+    }
+
+    matches
+}
+
+fn is_card_issuer(digits: &[u32]) -> bool {
+    CARD_ISSUERS.iter().any(|(prefix_len, first, last, lengths)| {
+        let prefix = digits
+            .iter()
+            .take(*prefix_len)
+            .fold(0, |prefix, digit| prefix * 10 + digit);
+        lengths.contains(&digits.len()) && (*first..=*last).contains(&prefix)
+    })
+}
+
+fn is_luhn_valid(digits: &[u32]) -> bool {
+    if digits.len() < 13 || digits.iter().all(|digit| *digit == digits[0]) {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| {
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                *digit
+            }
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+fn is_reserved_domain(domain: &str) -> bool {
+    let domain = domain.to_lowercase();
+    let tld = domain.rsplit('.').next().unwrap_or_default();
+
+    RESERVED_EMAIL_TLDS.contains(&tld)
+        || RESERVED_EMAIL_DOMAINS
+            .iter()
+            .any(|reserved| domain == *reserved || domain.ends_with(&format!(".{}", reserved)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<PiiKind> {
+        scan_pii(text).into_iter().map(|found| found.kind).collect()
+    }
+
+    #[test]
+    fn test_detects_luhn_valid_cards() {
+        assert_eq!(kinds("Card: 4539 1488 0343 6467"), vec![PiiKind::CreditCard]);
+        assert_eq!(kinds("4539-1488-0343-6468"), Vec::<PiiKind>::new());
+        assert_eq!(kinds("0000000000000000"), Vec::<PiiKind>::new());
+        assert_eq!(kinds("Order 12345"), Vec::<PiiKind>::new());
+    }
+
+    #[test]
+    fn test_cards_need_an_issuer_range() {
+        for card in [
+            "4222222222222",
+            "5555 5555 5555 4444",
+            "2223003122003222",
+            "3782 822463 10005",
+            "6011111111111117",
+        ] {
+            assert_eq!(kinds(card), vec![PiiKind::CreditCard], "{}", card);
+        }
+
+        // Luhn-valid millisecond timestamp
+        assert!(kinds("Created at 1700000000004").is_empty());
+        // Luhn-valid, but Amex numbers are 15 digits long
+        assert!(kinds("3400000000000000").is_empty());
+    }
+
+    #[test]
+    fn test_detects_real_domain_emails() {
+        assert_eq!(kinds("john.smith@gmail.com"), vec![PiiKind::Email]);
+        assert!(kinds("john.smith@example.com").is_empty());
+        assert!(kinds("jane@mail.example.org").is_empty());
+        assert!(kinds("bob@company.test").is_empty());
+    }
+
+    #[test]
+    fn test_detects_assignable_ssns() {
+        assert_eq!(kinds("SSN 123-45-6789"), vec![PiiKind::Ssn]);
+        assert!(kinds("000-12-3456").is_empty());
+        assert!(kinds("666-12-3456").is_empty());
+        assert!(kinds("912-12-3456").is_empty());
+        assert!(kinds("123-00-4567").is_empty());
+    }
+
+    #[test]
+    fn test_detects_phone_numbers() {
+        assert_eq!(kinds("Call (212) 736-5000"), vec![PiiKind::Phone]);
+        assert_eq!(kinds("+1 212.736.5000"), vec![PiiKind::Phone]);
+        assert!(kinds("Call 212-555-0143").is_empty());
+    }
+
+    #[test]
+    fn test_detects_public_figures() {
+        assert_eq!(kinds("Interview with taylor  swift"), vec![PiiKind::PublicFigure]);
+        assert_eq!(kinds("Signed by Beyoncé."), vec![PiiKind::PublicFigure]);
+        assert!(kinds("Taylor Smith").is_empty());
+        assert!(kinds("Tom Hanksley").is_empty());
+    }
+
+    #[test]
+    fn test_kind_names_round_trip() {
+        for kind in [
+            PiiKind::CreditCard,
+            PiiKind::Email,
+            PiiKind::Ssn,
+            PiiKind::Phone,
+            PiiKind::PublicFigure,
+        ] {
+            assert_eq!(PiiKind::from_name(kind.as_str()), Some(kind));
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+        assert_eq!(PiiKind::from_name("name"), None);
+    }
+}
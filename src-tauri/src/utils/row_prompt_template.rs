@@ -0,0 +1,9 @@
+pub static ROW_SYSTEM_PROMPT: &str = "You are a data generator. You must respond with ONLY a single JSON object. No explanations, no code, no markdown, no extra text.";
+
+pub static ROW_PROMPT_TEMPLATE: &str = r#"Generate one record as a JSON object with exactly these keys, in this order:
+{fields}
+{context}
+CRITICAL:
+- Every value must follow its rule and be logically consistent with the other values of the record
+- Use the exact key names above, no extra keys
+- Output ONLY the JSON object on a single line"#;
@@ -322,6 +322,17 @@ export const useDatasetStore = create<DatasetState>((set, get) => ({
     const currentDataset = get().currentDataset;
     const datasetName = currentDataset?.name || "Dataset";
 
+    // The generation keeps running, its status stays as it was
+    if (status === "warning") {
+      if (generation && generation.datasetId === currentDataset?.id) {
+        showInfoToast(
+          i18n.t("datasets.notifications.generation_warning"),
+          message ?? undefined
+        );
+      }
+      return;
+    }
+
     if (status === "completed") {
       set((state) => ({
         generations: state.generations.filter(